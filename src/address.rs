@@ -0,0 +1,296 @@
+//! Short, checksummed account addresses.
+//!
+//! An address is a BLAKE3 hash of the account's algorithm id and public key, or of its multisig
+//! policy. Its human-readable form is a network prefix followed by the Base58 encoding of the
+//! address kind, the hash and a 4-byte checksum, e.g. `uc_2VfUX...`. For single keys the kind is
+//! the algorithm id, so the address tells whether the account uses a hybrid key. The checksum
+//! covers the prefix, so a typo or an address from another network is detected when parsing.
+
+use std::{fmt, str::FromStr};
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    crypto::{PublicKey, SignatureAlgorithm},
+    encoding::Encoder,
+    multisig::MultisigPolicy,
+};
+
+/// Domain separation tag prefixed to the preimage of an address hash.
+pub const ADDRESS_TAG: &[u8] = b"ucoin/address/v1";
+
+/// Length of an address in its binary form: network id, kind and hash.
+pub const ADDRESS_LEN: usize = 34;
+
+const CHECKSUM_LEN: usize = 4;
+
+/// The network an address belongs to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Network {
+    Mainnet,
+    Testnet,
+}
+
+impl Network {
+    pub fn id(self) -> u8 {
+        match self {
+            Self::Mainnet => 0,
+            Self::Testnet => 1,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Self::Mainnet),
+            1 => Some(Self::Testnet),
+            _ => None,
+        }
+    }
+
+    /// Prefix of the human-readable form of addresses on this network.
+    pub fn prefix(self) -> &'static str {
+        match self {
+            Self::Mainnet => "uc",
+            Self::Testnet => "tuc",
+        }
+    }
+
+    /// Lowercase name, e.g. `mainnet`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Mainnet => "mainnet",
+            Self::Testnet => "testnet",
+        }
+    }
+
+    fn from_prefix(prefix: &str) -> Option<Self> {
+        [Self::Mainnet, Self::Testnet]
+            .into_iter()
+            .find(|n| n.prefix() == prefix)
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The name is not one of [`Network::name`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownNetwork(pub String);
+
+impl fmt::Display for UnknownNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown network {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownNetwork {}
+
+impl FromStr for Network {
+    type Err = UnknownNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Self::Mainnet, Self::Testnet]
+            .into_iter()
+            .find(|n| n.name() == s)
+            .ok_or_else(|| UnknownNetwork(s.to_owned()))
+    }
+}
+
+/// What kind of account an address commits to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AddressKind {
+    /// A single public key of the given algorithm.
+    Key(SignatureAlgorithm),
+    /// An m-of-n multisig policy.
+    Multisig,
+}
+
+impl AddressKind {
+    /// The kind byte. For single keys this is the [`SignatureAlgorithm::id`].
+    pub fn id(self) -> u8 {
+        match self {
+            Self::Key(algorithm) => algorithm.id(),
+            Self::Multisig => 0x80,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0x80 => Some(Self::Multisig),
+            id => SignatureAlgorithm::from_id(id).map(Self::Key),
+        }
+    }
+}
+
+/// Reasons an address can fail to parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddressError {
+    /// The address does not start with a known network prefix.
+    UnknownPrefix,
+    /// The address body is not valid Base58.
+    BadEncoding(bs58::decode::Error),
+    /// The decoded address has the wrong number of bytes.
+    BadLength(usize),
+    /// The checksum does not match, usually because of a typo.
+    BadChecksum,
+    UnknownNetwork(u8),
+    UnknownKind(u8),
+}
+
+impl fmt::Display for AddressError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownPrefix => write!(f, "unknown network prefix"),
+            Self::BadEncoding(e) => write!(f, "bad encoding: {e}"),
+            Self::BadLength(len) => write!(f, "bad length: {len} bytes"),
+            Self::BadChecksum => write!(f, "bad checksum"),
+            Self::UnknownNetwork(id) => write!(f, "unknown network {id}"),
+            Self::UnknownKind(id) => write!(f, "unknown address kind {id}"),
+        }
+    }
+}
+
+impl std::error::Error for AddressError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Address {
+    network: Network,
+    kind: AddressKind,
+    hash: [u8; 32],
+}
+
+impl Address {
+    /// Derives the address of a single-key account.
+    pub fn from_public_key(
+        network: Network,
+        algorithm: SignatureAlgorithm,
+        pk: &PublicKey,
+    ) -> Self {
+        let mut encoder = Encoder::new();
+        encoder
+            .bytes(ADDRESS_TAG)
+            .u8(algorithm.id())
+            .bytes(pk.as_ref());
+
+        Self {
+            network,
+            kind: AddressKind::Key(algorithm),
+            hash: *blake3::hash(&encoder.finish()).as_bytes(),
+        }
+    }
+
+    /// Derives the address of a multisig account from its policy.
+    pub fn from_multisig(network: Network, policy: &MultisigPolicy) -> Self {
+        let mut encoder = Encoder::new();
+        encoder.bytes(ADDRESS_TAG).u8(AddressKind::Multisig.id());
+        policy.encode(&mut encoder);
+
+        Self {
+            network,
+            kind: AddressKind::Multisig,
+            hash: *blake3::hash(&encoder.finish()).as_bytes(),
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn kind(&self) -> AddressKind {
+        self.kind
+    }
+
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
+    /// Binary form: network id, kind and hash.
+    pub fn to_bytes(&self) -> [u8; ADDRESS_LEN] {
+        let mut bytes = [0; ADDRESS_LEN];
+        bytes[0] = self.network.id();
+        bytes[1] = self.kind.id();
+        bytes[2..].copy_from_slice(&self.hash);
+
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, AddressError> {
+        if bytes.len() != ADDRESS_LEN {
+            return Err(AddressError::BadLength(bytes.len()));
+        }
+
+        let network = Network::from_id(bytes[0]).ok_or(AddressError::UnknownNetwork(bytes[0]))?;
+        let kind = AddressKind::from_id(bytes[1]).ok_or(AddressError::UnknownKind(bytes[1]))?;
+        let hash = bytes[2..].try_into().unwrap();
+
+        Ok(Self {
+            network,
+            kind,
+            hash,
+        })
+    }
+
+    fn checksum(prefix: &str, body: &[u8]) -> [u8; CHECKSUM_LEN] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(prefix.as_bytes()).update(body);
+
+        hasher.finalize().as_bytes()[..CHECKSUM_LEN]
+            .try_into()
+            .unwrap()
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prefix = self.network.prefix();
+
+        let mut body = vec![self.kind.id()];
+        body.extend_from_slice(&self.hash);
+        body.extend_from_slice(&Self::checksum(prefix, &body));
+
+        write!(f, "{prefix}_{}", bs58::encode(body).into_string())
+    }
+}
+
+impl FromStr for Address {
+    type Err = AddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, body) = s.split_once('_').ok_or(AddressError::UnknownPrefix)?;
+        let network = Network::from_prefix(prefix).ok_or(AddressError::UnknownPrefix)?;
+
+        let body = bs58::decode(body)
+            .into_vec()
+            .map_err(AddressError::BadEncoding)?;
+        if body.len() != ADDRESS_LEN - 1 + CHECKSUM_LEN {
+            return Err(AddressError::BadLength(body.len()));
+        }
+
+        let (body, checksum) = body.split_at(body.len() - CHECKSUM_LEN);
+        if checksum != Self::checksum(prefix, body) {
+            return Err(AddressError::BadChecksum);
+        }
+
+        let mut bytes = vec![network.id()];
+        bytes.extend_from_slice(body);
+
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl Serialize for Address {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Address {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
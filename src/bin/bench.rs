@@ -0,0 +1,77 @@
+use ucoin::{
+    crypto, AccountKey, Address, Network, SignatureAlgorithm, SignedTransaction, Transaction,
+};
+
+fn main() {
+    crypto::init();
+
+    // The algorithm name may be given as the first argument, e.g. `ml-dsa-65`.
+    let algorithm: SignatureAlgorithm = match std::env::args().nth(1) {
+        Some(name) => name.parse().unwrap_or_else(|e| {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }),
+        None => SignatureAlgorithm::default(),
+    };
+    let sig = algorithm.sig().unwrap_or_else(|e| {
+        eprintln!("error: {e}");
+        std::process::exit(1);
+    });
+
+    let (sender_pk, sender_sk) = algorithm.keypair().unwrap();
+    let (receiver, _) = algorithm.keypair().unwrap();
+    let receiver = Address::from_public_key(Network::Mainnet, algorithm, &receiver);
+
+    let start = std::time::Instant::now();
+    let sender = AccountKey::new(algorithm, sender_pk).into();
+    let transaction = Transaction::new(Network::Mainnet, &[], &sender, 0, 1000, &receiver)
+        .sign(&sender_sk)
+        .unwrap();
+    let sign_duration = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let verified = transaction.verify().is_ok();
+    let verify_duration = start.elapsed();
+
+    let serialized = serde_json::to_string_pretty(&transaction).unwrap();
+
+    println!("{serialized}");
+    println!(
+        "{algorithm} VERIFIED: {}, {:?} sign, {:?} verify, NIST Level {}",
+        verified,
+        sign_duration,
+        verify_duration,
+        sig.claimed_nist_level()
+    );
+
+    let start = std::time::Instant::now();
+    let serialized = serde_json::to_string(&transaction).unwrap();
+    let ser_duration = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let _deserialized: SignedTransaction = serde_json::from_str(&serialized).unwrap();
+    let de_duration = start.elapsed();
+
+    println!(
+        "JSON: {} bytes, {:?} ser, {:?} de",
+        serialized.len(),
+        ser_duration,
+        de_duration
+    );
+
+    let start = std::time::Instant::now();
+    let encoded = transaction.to_bytes();
+    let ser_duration = start.elapsed();
+
+    let start = std::time::Instant::now();
+    let _decoded = SignedTransaction::from_bytes(&encoded).unwrap();
+    let de_duration = start.elapsed();
+
+    println!(
+        "BINARY: {} bytes ({:.0}% of JSON), {:?} ser, {:?} de",
+        encoded.len(),
+        encoded.len() as f64 * 100.0 / serialized.len() as f64,
+        ser_duration,
+        de_duration
+    );
+}
@@ -0,0 +1,427 @@
+//! Command-line wallet.
+//!
+//! Works on a keystore and a state directory holding a [`Store`]. A `genesis.json` placed in a new
+//! state directory sets the initial balances.
+
+use std::{
+    error::Error,
+    fs,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use clap::{Parser, Subcommand};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use ucoin::{
+    crypto,
+    keystore::{KdfParams, Keystore},
+    ledger::{Report, Status},
+    seed, Address, Ledger, Network, PartiallySignedTransaction, Seed, Sender, SignatureAlgorithm,
+    SignedTransaction, Store, TipSelector, Transaction, WorldState,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+#[derive(Parser)]
+#[command(name = "ucoin", about = "ucoin wallet")]
+struct Cli {
+    /// State directory [default: ~/.ucoin]
+    #[arg(long, env = "UCOIN_DIR", global = true)]
+    dir: Option<PathBuf>,
+
+    /// Keystore file [default: <dir>/keystore.json]
+    #[arg(long, env = "UCOIN_KEYSTORE", global = true)]
+    keystore: Option<PathBuf>,
+
+    /// Network of a state directory without genesis.json
+    #[arg(long, default_value = "mainnet", global = true)]
+    network: Network,
+
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new keystore
+    Keygen {
+        #[arg(long, default_value_t = SignatureAlgorithm::default())]
+        algorithm: SignatureAlgorithm,
+        /// Derive the key from a new seed phrase, which is printed once on standard error
+        #[arg(long)]
+        mnemonic: bool,
+        /// Print the new seed phrase on standard output instead, in the JSON output with --json
+        #[arg(long, requires = "mnemonic")]
+        print_mnemonic: bool,
+        /// Derive the key from an existing seed phrase, read from standard input
+        #[arg(long, conflicts_with = "mnemonic")]
+        restore: bool,
+        /// Number of words of a new seed phrase
+        #[arg(long, default_value_t = 24)]
+        words: usize,
+        /// Account index to derive from the seed phrase
+        #[arg(long, default_value_t = 0)]
+        index: u32,
+        /// Argon2 memory in KiB
+        #[arg(long, default_value_t = KdfParams::default().memory)]
+        kdf_memory: u32,
+        /// Argon2 passes
+        #[arg(long, default_value_t = KdfParams::default().iterations)]
+        kdf_iterations: u32,
+        /// Overwrite an existing keystore
+        #[arg(long)]
+        force: bool,
+    },
+    /// Print the keystore's address
+    Address,
+    /// Print the balance of an address [default: the keystore's]
+    Balance { address: Option<Address> },
+    /// Sign and submit a payment
+    Send { to: Address, amount: u64 },
+    /// List the accepted transactions of an address [default: the keystore's]
+    History { address: Option<Address> },
+    /// Sign a transaction or add a cosignature, reading JSON from a file or `-`
+    SignTx {
+        input: PathBuf,
+        /// Write the result here instead of standard output
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+    /// Check the signatures of a signed transaction, reading JSON from a file or `-`
+    VerifyTx { input: PathBuf },
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    crypto::init();
+
+    match run(&cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            if cli.json {
+                println!("{}", json!({ "error": e.to_string() }));
+            } else {
+                eprintln!("error: {e}");
+            }
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(cli: &Cli) -> Result<()> {
+    let dir = match &cli.dir {
+        Some(dir) => dir.clone(),
+        None => PathBuf::from(std::env::var_os("HOME").ok_or("HOME is not set, use --dir")?)
+            .join(".ucoin"),
+    };
+    let keystore = cli
+        .keystore
+        .clone()
+        .unwrap_or_else(|| dir.join("keystore.json"));
+
+    match &cli.command {
+        Command::Keygen {
+            algorithm,
+            mnemonic,
+            print_mnemonic,
+            restore,
+            words,
+            index,
+            kdf_memory,
+            kdf_iterations,
+            force,
+        } => {
+            if keystore.exists() && !force {
+                return Err(format!("{} exists, use --force", keystore.display()).into());
+            }
+            let network = genesis(&dir, cli.network)?.network();
+
+            let mut phrase = None;
+            let key = if *mnemonic || *restore {
+                let m = match restore {
+                    true => seed::Mnemonic::parse(read_line("Seed phrase: ")?.trim())?,
+                    false => seed::generate_mnemonic(*words)?,
+                };
+                let key = Seed::from_mnemonic(&m, "").keystore(*algorithm, *index)?;
+                if *mnemonic {
+                    phrase = Some(m.to_string());
+                }
+                key
+            } else {
+                Keystore::generate(*algorithm)?
+            };
+
+            let params = KdfParams {
+                memory: *kdf_memory,
+                iterations: *kdf_iterations,
+                ..KdfParams::default()
+            };
+            if let Some(parent) = keystore.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            key.save(&keystore, &new_password()?, &params)?;
+
+            let address = key.address(network);
+            let printed = phrase.as_ref().filter(|_| *print_mnemonic);
+            let output = json!({
+                "address": address,
+                "algorithm": key.algorithm(),
+                "keystore": keystore,
+                "mnemonic": printed,
+            });
+            emit(cli, output, || {
+                println!("{address}");
+                if let Some(phrase) = printed {
+                    println!("{phrase}");
+                }
+            })?;
+
+            if let Some(phrase) = phrase.filter(|_| !print_mnemonic) {
+                eprintln!("Write down your seed phrase, it is the only backup of the key:");
+                eprintln!("{phrase}");
+            }
+
+            Ok(())
+        }
+        Command::Address => {
+            let network = genesis(&dir, cli.network)?.network();
+            let key = Keystore::read_account_key(&keystore)?;
+            let address = Sender::from(key).address(network);
+
+            emit(cli, json!({ "address": address }), || println!("{address}"))
+        }
+        Command::Balance { address } => {
+            let (_, ledger) = open(&dir, cli.network)?;
+            let address = account(address, &keystore, &ledger)?;
+            let state = ledger.state();
+            let (balance, sequence) = (state.balance(&address), state.sequence(&address));
+
+            let output = json!({ "address": address, "balance": balance, "sequence": sequence });
+            emit(cli, output, || println!("{balance}"))
+        }
+        Command::Send { to, amount } => {
+            let (mut store, mut ledger) = open(&dir, cli.network)?;
+            let key = Keystore::load(&keystore, &password()?)?;
+            let network = ledger.state().network();
+            if to.network() != network {
+                return Err(format!("{to} is not a {network} address").into());
+            }
+
+            let sender = Sender::from(key.account_key());
+            let address = sender.address(network);
+            let sequence = ledger.state().sequence(&address);
+            let parents =
+                TipSelector::default().select(&ledger, Some(&address), &mut rand::thread_rng());
+            let transaction = Transaction::new(network, &parents, &sender, sequence, *amount, to)
+                .sign(key.secret_key())?;
+
+            let hash = transaction.hash();
+            let report = submit(&mut store, &mut ledger, transaction)?;
+            let status = ledger.status(&hash);
+            if report.rejected.contains(&hash) {
+                return Err("transaction rejected, is the balance sufficient?".into());
+            }
+
+            let output = json!({ "hash": hash, "status": status_name(status) });
+            emit(cli, output, || println!("{hash}"))
+        }
+        Command::History { address } => {
+            let (_, ledger) = open(&dir, cli.network)?;
+            let address = account(address, &keystore, &ledger)?;
+
+            let entries = ledger.history(&address);
+
+            emit(cli, json!(entries), || {
+                for entry in &entries {
+                    println!(
+                        "{} {:3} {:>20} {}",
+                        entry.hash,
+                        entry.direction.name(),
+                        entry.amount,
+                        entry.counterparty,
+                    );
+                }
+            })
+        }
+        Command::SignTx { input, out } => {
+            #[derive(Deserialize)]
+            #[serde(untagged)]
+            enum Unsigned {
+                Partial(PartiallySignedTransaction),
+                Transaction(Transaction),
+            }
+
+            let unsigned: Unsigned = serde_json::from_str(&read_input(input)?)?;
+            let key = Keystore::load(&keystore, &password()?)?;
+
+            let mut partial = match unsigned {
+                Unsigned::Partial(partial) => partial,
+                Unsigned::Transaction(transaction) => match transaction.sender() {
+                    Sender::Key(sender) if *sender != key.account_key() => {
+                        return Err("the transaction is not sent from this keystore".into());
+                    }
+                    Sender::Key(_) => {
+                        let signed = transaction.sign(key.secret_key())?;
+                        return write_output(out, &serde_json::to_string_pretty(&signed)?);
+                    }
+                    Sender::Multisig(_) => PartiallySignedTransaction::new(transaction),
+                },
+            };
+
+            let index = match partial.transaction().sender() {
+                Sender::Multisig(policy) => policy.index_of(&key.account_key()),
+                Sender::Key(_) => None,
+            };
+            let index = index.ok_or("this keystore is not a cosigner of the transaction")?;
+            partial.sign(index, key.secret_key())?;
+
+            let document = match partial.is_complete() {
+                true => serde_json::to_string_pretty(&partial.finalize()?)?,
+                false => serde_json::to_string_pretty(&partial)?,
+            };
+            write_output(out, &document)
+        }
+        Command::VerifyTx { input } => {
+            let signed: SignedTransaction = serde_json::from_str(&read_input(input)?)?;
+            let transaction = signed.transaction();
+            let result = signed.verify();
+
+            let output = json!({
+                "hash": signed.hash(),
+                "valid": result.is_ok(),
+                "error": result.as_ref().err().map(ToString::to_string),
+                "network": transaction.network(),
+                "sender": transaction.sender_address(),
+                "receiver": transaction.receiver(),
+                "amount": transaction.amount(),
+                "sequence": transaction.sequence(),
+            });
+            emit(cli, output, || {
+                if result.is_ok() {
+                    println!("valid: {}", signed.hash());
+                }
+            })?;
+
+            result.map_err(Into::into)
+        }
+    }
+}
+
+/// Prints `output` as JSON, or runs `human` to print it as text.
+fn emit(cli: &Cli, output: Value, human: impl FnOnce()) -> Result<()> {
+    if cli.json {
+        println!("{output}");
+    } else {
+        human();
+    }
+
+    Ok(())
+}
+
+/// The genesis state of the state directory, or an empty one on `network`.
+fn genesis(dir: &Path, network: Network) -> Result<WorldState> {
+    match fs::read_to_string(dir.join("genesis.json")) {
+        Ok(json) => Ok(serde_json::from_str(&json)?),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(WorldState::new(network)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Opens the store in the state directory and replays it into a ledger.
+fn open(dir: &Path, network: Network) -> Result<(Store, Ledger)> {
+    let store = Store::open(dir, &genesis(dir, network)?)?;
+    let ledger = store.load()?;
+
+    Ok((store, ledger))
+}
+
+/// Inserts a transaction into the ledger and, unless it was rejected, the store.
+fn submit(
+    store: &mut Store,
+    ledger: &mut Ledger,
+    transaction: SignedTransaction,
+) -> Result<Report> {
+    let hash = transaction.hash();
+
+    let report = ledger.insert(transaction.clone())?;
+    if !report.rejected.contains(&hash) {
+        store.append(&transaction)?;
+        store.checkpoint(ledger)?;
+    }
+
+    Ok(report)
+}
+
+/// The given address, or the keystore's.
+fn account(address: &Option<Address>, keystore: &Path, ledger: &Ledger) -> Result<Address> {
+    match address {
+        Some(address) => Ok(*address),
+        None => {
+            let key = Keystore::read_account_key(keystore)?;
+            Ok(Sender::from(key).address(ledger.state().network()))
+        }
+    }
+}
+
+fn status_name(status: Option<Status>) -> &'static str {
+    match status {
+        Some(Status::Accepted) => "accepted",
+        Some(Status::Pending) => "pending",
+        Some(Status::Rejected) => "rejected",
+        None => "unknown",
+    }
+}
+
+/// The keystore password, from `UCOIN_PASSWORD` or the terminal.
+fn password() -> Result<String> {
+    match std::env::var("UCOIN_PASSWORD") {
+        Ok(password) => Ok(password),
+        Err(_) => Ok(rpassword::prompt_password("Password: ")?),
+    }
+}
+
+fn new_password() -> Result<String> {
+    if let Ok(password) = std::env::var("UCOIN_PASSWORD") {
+        return Ok(password);
+    }
+
+    let password = rpassword::prompt_password("New password: ")?;
+    if rpassword::prompt_password("Repeat password: ")? != password {
+        return Err("passwords do not match".into());
+    }
+
+    Ok(password)
+}
+
+fn read_line(prompt: &str) -> Result<String> {
+    eprint!("{prompt}");
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+
+    Ok(line)
+}
+
+fn read_input(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        return Ok(input);
+    }
+
+    Ok(fs::read_to_string(path)?)
+}
+
+fn write_output(out: &Option<PathBuf>, document: &str) -> Result<()> {
+    match out {
+        Some(path) => fs::write(path, format!("{document}\n"))?,
+        None => println!("{document}"),
+    }
+
+    Ok(())
+}
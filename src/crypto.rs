@@ -0,0 +1,599 @@
+//! Post-quantum signature primitives used by ucoin accounts.
+//!
+//! Hashes, public keys and signatures are kept as raw bytes in strongly-typed wrappers. Their
+//! `Display`, `FromStr` and serde representations are standard padded Base64.
+
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    str::FromStr,
+};
+
+use base64ct::{Base64, Encoding};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+pub use oqs::sig::Sig;
+
+/// Length of the Ed25519 part of a hybrid public key.
+pub const ED25519_PUBLIC_KEY_LEN: usize = ed25519_dalek::PUBLIC_KEY_LENGTH;
+
+/// Length of the Ed25519 part of a hybrid secret key.
+pub const ED25519_SECRET_KEY_LEN: usize = ed25519_dalek::SECRET_KEY_LENGTH;
+
+/// Length of the Ed25519 part of a hybrid signature.
+pub const ED25519_SIGNATURE_LEN: usize = ed25519_dalek::SIGNATURE_LENGTH;
+
+/// Initializes liboqs. Must be called once before any other cryptographic operation.
+pub fn init() {
+    oqs::init();
+}
+
+/// Signature schemes an account can use.
+///
+/// Each algorithm has a stable one-byte id that is carried in transactions and addresses. Falcon
+/// is always available; ML-DSA and SPHINCS+ are enabled by the `ml-dsa` and `sphincs` cargo
+/// features. Ids of disabled algorithms still parse, but keys of those accounts cannot sign or
+/// verify.
+///
+/// Hybrid algorithms pair Ed25519 with Falcon: keys and signatures are the Ed25519 part followed
+/// by the Falcon part, and a signature is only valid if both parts are.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum SignatureAlgorithm {
+    Falcon512,
+    #[default]
+    Falcon1024,
+    MlDsa44,
+    MlDsa65,
+    MlDsa87,
+    SphincsSha2128f,
+    SphincsSha2128s,
+    SphincsSha2192f,
+    SphincsSha2192s,
+    SphincsSha2256f,
+    SphincsSha2256s,
+    Ed25519Falcon512,
+    Ed25519Falcon1024,
+}
+
+impl SignatureAlgorithm {
+    pub const ALL: [Self; 13] = [
+        Self::Falcon512,
+        Self::Falcon1024,
+        Self::MlDsa44,
+        Self::MlDsa65,
+        Self::MlDsa87,
+        Self::SphincsSha2128f,
+        Self::SphincsSha2128s,
+        Self::SphincsSha2192f,
+        Self::SphincsSha2192s,
+        Self::SphincsSha2256f,
+        Self::SphincsSha2256s,
+        Self::Ed25519Falcon512,
+        Self::Ed25519Falcon1024,
+    ];
+
+    pub fn id(self) -> u8 {
+        match self {
+            Self::Falcon1024 => 1,
+            Self::Falcon512 => 2,
+            Self::MlDsa44 => 3,
+            Self::MlDsa65 => 4,
+            Self::MlDsa87 => 5,
+            Self::SphincsSha2128f => 6,
+            Self::SphincsSha2128s => 7,
+            Self::SphincsSha2192f => 8,
+            Self::SphincsSha2192s => 9,
+            Self::SphincsSha2256f => 10,
+            Self::SphincsSha2256s => 11,
+            Self::Ed25519Falcon512 => 12,
+            Self::Ed25519Falcon1024 => 13,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|a| a.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Falcon512 => "falcon-512",
+            Self::Falcon1024 => "falcon-1024",
+            Self::MlDsa44 => "ml-dsa-44",
+            Self::MlDsa65 => "ml-dsa-65",
+            Self::MlDsa87 => "ml-dsa-87",
+            Self::SphincsSha2128f => "sphincs-sha2-128f",
+            Self::SphincsSha2128s => "sphincs-sha2-128s",
+            Self::SphincsSha2192f => "sphincs-sha2-192f",
+            Self::SphincsSha2192s => "sphincs-sha2-192s",
+            Self::SphincsSha2256f => "sphincs-sha2-256f",
+            Self::SphincsSha2256s => "sphincs-sha2-256s",
+            Self::Ed25519Falcon512 => "ed25519-falcon-512",
+            Self::Ed25519Falcon1024 => "ed25519-falcon-1024",
+        }
+    }
+
+    /// Whether keys and signatures also carry an Ed25519 part.
+    pub fn is_hybrid(self) -> bool {
+        matches!(self, Self::Ed25519Falcon512 | Self::Ed25519Falcon1024)
+    }
+
+    /// The corresponding liboqs algorithm; for hybrids, the post-quantum part.
+    pub fn oqs(self) -> oqs::sig::Algorithm {
+        use oqs::sig::Algorithm;
+
+        match self {
+            Self::Falcon512 | Self::Ed25519Falcon512 => Algorithm::Falcon512,
+            Self::Falcon1024 | Self::Ed25519Falcon1024 => Algorithm::Falcon1024,
+            Self::MlDsa44 => Algorithm::MlDsa44,
+            Self::MlDsa65 => Algorithm::MlDsa65,
+            Self::MlDsa87 => Algorithm::MlDsa87,
+            Self::SphincsSha2128f => Algorithm::SphincsSha2128fSimple,
+            Self::SphincsSha2128s => Algorithm::SphincsSha2128sSimple,
+            Self::SphincsSha2192f => Algorithm::SphincsSha2192fSimple,
+            Self::SphincsSha2192s => Algorithm::SphincsSha2192sSimple,
+            Self::SphincsSha2256f => Algorithm::SphincsSha2256fSimple,
+            Self::SphincsSha2256s => Algorithm::SphincsSha2256sSimple,
+        }
+    }
+
+    /// Whether this build can sign and verify with the algorithm.
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Self::Falcon512
+            | Self::Falcon1024
+            | Self::Ed25519Falcon512
+            | Self::Ed25519Falcon1024 => true,
+            Self::MlDsa44 | Self::MlDsa65 | Self::MlDsa87 => cfg!(feature = "ml-dsa"),
+            Self::SphincsSha2128f
+            | Self::SphincsSha2128s
+            | Self::SphincsSha2192f
+            | Self::SphincsSha2192s
+            | Self::SphincsSha2256f
+            | Self::SphincsSha2256s => cfg!(feature = "sphincs"),
+        }
+    }
+
+    /// Instantiates the algorithm in liboqs; for hybrids, the post-quantum part.
+    pub fn sig(self) -> oqs::Result<Sig> {
+        if !self.is_enabled() {
+            return Err(oqs::Error::AlgorithmDisabled);
+        }
+
+        Sig::new(self.oqs())
+    }
+
+    /// Generates a fresh keypair.
+    pub fn keypair(self) -> oqs::Result<(PublicKey, SecretKey)> {
+        let (pk, sk) = self.sig()?.keypair()?;
+        if !self.is_hybrid() {
+            return Ok((pk.into(), sk.into()));
+        }
+
+        let classical = ed25519_dalek::SigningKey::generate(&mut rand::rngs::OsRng);
+
+        Ok(hybrid_keypair(&classical, &pk, &sk))
+    }
+
+    /// Generates the keypair determined by `seed`: the same seed always yields the same keypair.
+    ///
+    /// Key generation draws from a BLAKE3 stream keyed with `seed`. For hybrids, the first 32
+    /// bytes are the Ed25519 secret key; the post-quantum part reads the rest.
+    pub fn keypair_from_seed(self, seed: &[u8; 32]) -> oqs::Result<(PublicKey, SecretKey)> {
+        let sig = self.sig()?;
+
+        let mut stream = blake3::Hasher::new_keyed(seed).finalize_xof();
+        let mut classical = Zeroizing::new([0; 32]);
+        if self.is_hybrid() {
+            stream.fill(classical.as_mut());
+        }
+
+        let (pk, sk) = seeded::with_stream(stream, || sig.keypair())?;
+        if !self.is_hybrid() {
+            return Ok((pk.into(), sk.into()));
+        }
+
+        let classical = ed25519_dalek::SigningKey::from_bytes(&classical);
+
+        Ok(hybrid_keypair(&classical, &pk, &sk))
+    }
+}
+
+/// Concatenates the Ed25519 and post-quantum halves of a hybrid keypair.
+fn hybrid_keypair(
+    classical: &ed25519_dalek::SigningKey,
+    pk: &oqs::sig::PublicKey,
+    sk: &oqs::sig::SecretKey,
+) -> (PublicKey, SecretKey) {
+    let mut public = classical.verifying_key().to_bytes().to_vec();
+    public.extend_from_slice(pk.as_ref());
+    let mut secret = SecretKey(classical.to_bytes().to_vec());
+    secret.0.extend_from_slice(sk.as_ref());
+
+    (PublicKey(public), secret)
+}
+
+/// Deterministic randomness for liboqs key generation.
+///
+/// liboqs reads randomness from a process-wide source, so seeded key generation swaps in a
+/// callback for its duration. Seeded generations are serialized, and other threads that draw
+/// randomness meanwhile, e.g. to sign, are still served by the operating system.
+mod seeded {
+    use std::{
+        sync::{Mutex, MutexGuard, PoisonError},
+        thread::{self, ThreadId},
+    };
+
+    use rand::RngCore;
+
+    static GENERATION: Mutex<()> = Mutex::new(());
+    static STREAM: Mutex<Option<(ThreadId, blake3::OutputReader)>> = Mutex::new(None);
+
+    fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+        mutex.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `f` with liboqs drawing this thread's randomness from `stream`.
+    pub fn with_stream<T>(stream: blake3::OutputReader, f: impl FnOnce() -> T) -> T {
+        let _generation = lock(&GENERATION);
+        let _restore = Restore;
+
+        *lock(&STREAM) = Some((thread::current().id(), stream));
+        // SAFETY: `randombytes` writes exactly `bytes_to_read` bytes to `random_array`.
+        unsafe { oqs_sys::rand::OQS_randombytes_custom_algorithm(Some(randombytes)) };
+
+        f()
+    }
+
+    /// Reinstates the system source, even if key generation panics.
+    struct Restore;
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            // SAFETY: "system" is a valid, NUL-terminated algorithm name.
+            unsafe { oqs_sys::rand::OQS_randombytes_switch_algorithm(c"system".as_ptr()) };
+            *lock(&STREAM) = None;
+        }
+    }
+
+    unsafe extern "C" fn randombytes(random_array: *mut u8, bytes_to_read: usize) {
+        // SAFETY: liboqs passes a buffer of at least `bytes_to_read` bytes.
+        let out = unsafe { std::slice::from_raw_parts_mut(random_array, bytes_to_read) };
+
+        match &mut *lock(&STREAM) {
+            Some((owner, stream)) if *owner == thread::current().id() => stream.fill(out),
+            _ => rand::rngs::OsRng.fill_bytes(out),
+        }
+    }
+}
+
+impl fmt::Display for SignatureAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The name is not one of [`SignatureAlgorithm::name`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownAlgorithm(pub String);
+
+impl fmt::Display for UnknownAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown signature algorithm {:?}", self.0)
+    }
+}
+
+impl std::error::Error for UnknownAlgorithm {}
+
+impl FromStr for SignatureAlgorithm {
+    type Err = UnknownAlgorithm;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|a| a.name() == s)
+            .ok_or_else(|| UnknownAlgorithm(s.to_owned()))
+    }
+}
+
+impl Serialize for SignatureAlgorithm {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SignatureAlgorithm {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Reasons a Base64 hash, key or signature can fail to parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    BadEncoding(base64ct::Error),
+    BadLength { expected: usize, actual: usize },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadEncoding(e) => write!(f, "bad encoding: {e}"),
+            Self::BadLength { expected, actual } => {
+                write!(f, "bad length: expected {expected} bytes, got {actual}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<base64ct::Error> for ParseError {
+    fn from(e: base64ct::Error) -> Self {
+        Self::BadEncoding(e)
+    }
+}
+
+/// Implements Base64 `Display`, `FromStr` and serde for a byte newtype.
+macro_rules! base64_newtype {
+    ($name:ident) => {
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&Base64::encode_string(self.as_ref()))
+            }
+        }
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, concat!(stringify!($name), "({})"), self)
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_str(self)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let s = String::deserialize(deserializer)?;
+                s.parse().map_err(de::Error::custom)
+            }
+        }
+    };
+}
+
+/// A transaction ID: the BLAKE3 hash of a signed transaction.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TxHash([u8; 32]);
+
+impl TxHash {
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for TxHash {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<blake3::Hash> for TxHash {
+    fn from(hash: blake3::Hash) -> Self {
+        Self(*hash.as_bytes())
+    }
+}
+
+impl FromStr for TxHash {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = Base64::decode_vec(s)?;
+        let bytes = bytes
+            .try_into()
+            .map_err(|b: Vec<u8>| ParseError::BadLength {
+                expected: 32,
+                actual: b.len(),
+            })?;
+
+        Ok(Self(bytes))
+    }
+}
+
+base64_newtype!(TxHash);
+
+/// A BLAKE3 hash committing to something other than a transaction, e.g. a state root.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Digest([u8; 32]);
+
+impl Digest {
+    pub const fn from_bytes(bytes: [u8; 32]) -> Self {
+        Self(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for Digest {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<blake3::Hash> for Digest {
+    fn from(hash: blake3::Hash) -> Self {
+        Self(*hash.as_bytes())
+    }
+}
+
+impl FromStr for Digest {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TxHash::from_str(s).map(|hash| Self(hash.0))
+    }
+}
+
+base64_newtype!(Digest);
+
+/// An account's public key. Equality is constant-time.
+#[derive(Clone)]
+pub struct PublicKey(Vec<u8>);
+
+impl PublicKey {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl AsRef<[u8]> for PublicKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<oqs::sig::PublicKey> for PublicKey {
+    fn from(pk: oqs::sig::PublicKey) -> Self {
+        Self(pk.into_vec())
+    }
+}
+
+impl PartialEq for PublicKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl Eq for PublicKey {}
+
+impl Hash for PublicKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+    }
+}
+
+impl FromStr for PublicKey {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Base64::decode_vec(s)?))
+    }
+}
+
+base64_newtype!(PublicKey);
+
+/// A signature over a transaction. Equality is constant-time.
+#[derive(Clone)]
+pub struct Signature(Vec<u8>);
+
+impl Signature {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+}
+
+impl AsRef<[u8]> for Signature {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<oqs::sig::Signature> for Signature {
+    fn from(signature: oqs::sig::Signature) -> Self {
+        Self(signature.into_vec())
+    }
+}
+
+impl PartialEq for Signature {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl Eq for Signature {}
+
+impl FromStr for Signature {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self(Base64::decode_vec(s)?))
+    }
+}
+
+base64_newtype!(Signature);
+
+/// A public key together with the signature algorithm it belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AccountKey {
+    algorithm: SignatureAlgorithm,
+    public_key: PublicKey,
+}
+
+impl AccountKey {
+    pub fn new(algorithm: SignatureAlgorithm, public_key: PublicKey) -> Self {
+        Self {
+            algorithm,
+            public_key,
+        }
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+}
+
+/// An account's secret key. Its bytes are never printed and are wiped when it is dropped.
+#[derive(Clone)]
+pub struct SecretKey(Vec<u8>);
+
+impl SecretKey {
+    pub fn from_bytes(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl AsRef<[u8]> for SecretKey {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<oqs::sig::SecretKey> for SecretKey {
+    fn from(sk: oqs::sig::SecretKey) -> Self {
+        Self(sk.into_vec())
+    }
+}
+
+impl Drop for SecretKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for SecretKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretKey({} bytes)", self.0.len())
+    }
+}
@@ -0,0 +1,336 @@
+//! In-memory DAG of signed transactions linked through their parents.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    fmt,
+};
+
+use crate::{crypto::TxHash, transaction::SignedTransaction};
+
+/// Default for [`Dag::with_max_orphans`].
+pub const DEFAULT_MAX_ORPHANS: usize = 10_000;
+
+/// What to do with a transaction whose parents are not all known yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OrphanPolicy {
+    /// Fail the insertion with [`DagError::UnknownParents`].
+    Reject,
+    /// Keep the transaction aside and connect it once its parents arrive. When too many are
+    /// kept, the oldest is dropped.
+    #[default]
+    Park,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DagError {
+    /// The transaction is already in the DAG or parked as an orphan.
+    Duplicate(TxHash),
+    /// Some parents are unknown and the DAG rejects orphans.
+    UnknownParents(Vec<TxHash>),
+}
+
+impl fmt::Display for DagError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Duplicate(hash) => write!(f, "duplicate transaction {hash}"),
+            Self::UnknownParents(parents) => write!(f, "{} unknown parents", parents.len()),
+        }
+    }
+}
+
+impl std::error::Error for DagError {}
+
+/// Outcome of a successful [`Dag::insert`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Insertion {
+    /// The transaction joined the DAG, followed by every orphan it unblocked, in the order they
+    /// were connected. Parents always come before their children.
+    Connected(Vec<TxHash>),
+    /// The transaction is parked until the listed parents arrive.
+    Orphaned(Vec<TxHash>),
+}
+
+#[derive(Debug, Clone)]
+pub struct Dag {
+    policy: OrphanPolicy,
+    max_orphans: usize,
+    transactions: HashMap<TxHash, SignedTransaction>,
+    children: HashMap<TxHash, BTreeSet<TxHash>>,
+    tips: BTreeSet<TxHash>,
+    /// Parked orphans, with when each was parked.
+    orphans: HashMap<TxHash, (u64, SignedTransaction)>,
+    /// Parked orphans by when they were parked.
+    parked: BTreeMap<u64, TxHash>,
+    next_parked: u64,
+    /// Orphans waiting on each missing parent.
+    waiting: HashMap<TxHash, BTreeSet<TxHash>>,
+    /// Transactions at the cut of a snapshot the DAG was started from. They count as known
+    /// parents, but are not held.
+    base: BTreeSet<TxHash>,
+}
+
+impl Default for Dag {
+    fn default() -> Self {
+        Self {
+            policy: OrphanPolicy::default(),
+            max_orphans: DEFAULT_MAX_ORPHANS,
+            transactions: HashMap::new(),
+            children: HashMap::new(),
+            tips: BTreeSet::new(),
+            orphans: HashMap::new(),
+            parked: BTreeMap::new(),
+            next_parked: 0,
+            waiting: HashMap::new(),
+            base: BTreeSet::new(),
+        }
+    }
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_orphan_policy(policy: OrphanPolicy) -> Self {
+        Self {
+            policy,
+            ..Self::default()
+        }
+    }
+
+    /// Creates a DAG continuing from the cut of a snapshot, whose transactions start out as tips.
+    pub fn with_base(cut: impl IntoIterator<Item = TxHash>) -> Self {
+        let base: BTreeSet<_> = cut.into_iter().collect();
+
+        Self {
+            tips: base.clone(),
+            base,
+            ..Self::default()
+        }
+    }
+
+    /// Keeps at most `max` orphans, dropping the oldest beyond that.
+    pub fn with_max_orphans(self, max: usize) -> Self {
+        Self {
+            max_orphans: max,
+            ..self
+        }
+    }
+
+    /// The snapshot cut the DAG was started from.
+    pub fn base(&self) -> impl Iterator<Item = &TxHash> {
+        self.base.iter()
+    }
+
+    /// Adds a transaction, connecting it if all of its parents are known.
+    pub fn insert(&mut self, tx: SignedTransaction) -> Result<Insertion, DagError> {
+        let hash = tx.hash();
+        if self.is_known(&hash) || self.orphans.contains_key(&hash) {
+            return Err(DagError::Duplicate(hash));
+        }
+
+        let missing = self.missing_parents_of(&tx);
+        if !missing.is_empty() {
+            if self.policy == OrphanPolicy::Reject {
+                return Err(DagError::UnknownParents(missing));
+            }
+
+            for parent in &missing {
+                self.waiting.entry(*parent).or_default().insert(hash);
+            }
+            self.park(hash, tx);
+
+            return Ok(Insertion::Orphaned(missing));
+        }
+
+        let mut connected = Vec::new();
+        let mut queue = VecDeque::from([(hash, tx)]);
+
+        while let Some((hash, tx)) = queue.pop_front() {
+            self.connect(hash, tx);
+            connected.push(hash);
+
+            for orphan in self.waiting.remove(&hash).unwrap_or_default() {
+                let ready = self.orphans[&orphan]
+                    .1
+                    .transaction()
+                    .parents()
+                    .iter()
+                    .all(|p| self.is_known(p));
+
+                if ready {
+                    let (parked, tx) = self.orphans.remove(&orphan).unwrap();
+                    self.parked.remove(&parked);
+                    queue.push_back((orphan, tx));
+                }
+            }
+        }
+
+        Ok(Insertion::Connected(connected))
+    }
+
+    /// Parks an orphan, then drops the oldest orphans beyond [`Dag::with_max_orphans`].
+    fn park(&mut self, hash: TxHash, tx: SignedTransaction) {
+        self.orphans.insert(hash, (self.next_parked, tx));
+        self.parked.insert(self.next_parked, hash);
+        self.next_parked += 1;
+
+        while self.orphans.len() > self.max_orphans {
+            let (_, oldest) = self.parked.pop_first().unwrap();
+            let (_, tx) = self.orphans.remove(&oldest).unwrap();
+
+            for parent in tx.transaction().parents() {
+                if let Some(waiting) = self.waiting.get_mut(parent) {
+                    waiting.remove(&oldest);
+                    if waiting.is_empty() {
+                        self.waiting.remove(parent);
+                    }
+                }
+            }
+        }
+    }
+
+    fn missing_parents_of(&self, tx: &SignedTransaction) -> Vec<TxHash> {
+        let parents: BTreeSet<_> = tx.transaction().parents().iter().copied().collect();
+
+        parents.into_iter().filter(|p| !self.is_known(p)).collect()
+    }
+
+    /// Whether a transaction is connected to the DAG or part of its base.
+    pub fn is_known(&self, hash: &TxHash) -> bool {
+        self.transactions.contains_key(hash) || self.base.contains(hash)
+    }
+
+    fn connect(&mut self, hash: TxHash, tx: SignedTransaction) {
+        for parent in tx.transaction().parents() {
+            self.children.entry(*parent).or_default().insert(hash);
+            self.tips.remove(parent);
+        }
+
+        self.children.entry(hash).or_default();
+        self.tips.insert(hash);
+        self.transactions.insert(hash, tx);
+    }
+
+    /// Returns a connected transaction.
+    pub fn get(&self, hash: &TxHash) -> Option<&SignedTransaction> {
+        self.transactions.get(hash)
+    }
+
+    /// Whether a transaction is connected to the DAG. Orphans are not.
+    pub fn contains(&self, hash: &TxHash) -> bool {
+        self.transactions.contains_key(hash)
+    }
+
+    pub fn is_orphan(&self, hash: &TxHash) -> bool {
+        self.orphans.contains_key(hash)
+    }
+
+    /// Returns a parked orphan.
+    pub fn get_orphan(&self, hash: &TxHash) -> Option<&SignedTransaction> {
+        self.orphans.get(hash).map(|(_, tx)| tx)
+    }
+
+    /// Number of connected transactions.
+    pub fn len(&self) -> usize {
+        self.transactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.transactions.is_empty()
+    }
+
+    /// All connected transactions, in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&TxHash, &SignedTransaction)> {
+        self.transactions.iter()
+    }
+
+    /// Parked transactions that are still missing parents.
+    pub fn orphans(&self) -> impl Iterator<Item = (&TxHash, &SignedTransaction)> {
+        self.orphans.iter().map(|(hash, (_, tx))| (hash, tx))
+    }
+
+    /// Unknown transactions that parked orphans are waiting on.
+    pub fn missing(&self) -> impl Iterator<Item = &TxHash> {
+        self.waiting.keys()
+    }
+
+    /// Connected transactions that no other transaction references yet, in hash order, plus any
+    /// such transactions of the [`Dag::base`].
+    pub fn tips(&self) -> impl Iterator<Item = &TxHash> {
+        self.tips.iter()
+    }
+
+    /// Direct children of a connected transaction, in hash order.
+    pub fn children(&self, hash: &TxHash) -> impl Iterator<Item = &TxHash> {
+        self.children.get(hash).into_iter().flatten()
+    }
+
+    /// Every transaction reachable by following parents, excluding `hash` itself.
+    pub fn ancestors(&self, hash: &TxHash) -> HashSet<TxHash> {
+        self.walk(hash, |tx| {
+            self.transactions
+                .get(tx)
+                .map(|t| t.transaction().parents().to_vec())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Every transaction reachable by following children, excluding `hash` itself.
+    pub fn descendants(&self, hash: &TxHash) -> HashSet<TxHash> {
+        self.walk(hash, |tx| self.children(tx).copied().collect())
+    }
+
+    fn walk(&self, start: &TxHash, next: impl Fn(&TxHash) -> Vec<TxHash>) -> HashSet<TxHash> {
+        let mut seen = HashSet::new();
+        let mut stack = next(start);
+
+        while let Some(hash) = stack.pop() {
+            if seen.insert(hash) {
+                stack.extend(next(&hash));
+            }
+        }
+
+        seen
+    }
+
+    /// All connected transactions ordered so that parents precede their children.
+    ///
+    /// Ties are broken by hash, so every node holding the same DAG computes the same order.
+    pub fn topological_order(&self) -> Vec<TxHash> {
+        let mut pending: HashMap<TxHash, usize> = self
+            .transactions
+            .iter()
+            .map(|(hash, tx)| {
+                let parents: HashSet<_> = tx
+                    .transaction()
+                    .parents()
+                    .iter()
+                    .filter(|p| self.contains(p))
+                    .collect();
+                (*hash, parents.len())
+            })
+            .collect();
+
+        let mut ready: BTreeSet<TxHash> = pending
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(hash, _)| *hash)
+            .collect();
+
+        let mut order = Vec::with_capacity(self.transactions.len());
+        while let Some(hash) = ready.pop_first() {
+            order.push(hash);
+
+            for child in self.children(&hash) {
+                let count = pending.get_mut(child).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.insert(*child);
+                }
+            }
+        }
+
+        order
+    }
+}
@@ -0,0 +1,72 @@
+//! Canonical byte encoding of transactions.
+//!
+//! Signatures and transaction IDs are computed over this encoding rather than over JSON, so that
+//! every implementation agrees on them byte for byte. All integers are big-endian.
+//!
+//! ```text
+//! bytes(b)    = u32(len(b)) || b
+//! key         = u8(algorithm) || bytes(public key)
+//! sender      = key
+//!             | u8(0x80) || u8(threshold) || u32(len(keys)) || key*
+//! body        = u8(network) || u32(len(parents)) || bytes(parent)* || sender
+//!               || u64(sequence) || u64(timestamp) || u64(amount) || bytes(receiver)
+//! message     = bytes("ucoin/tx/v1") || body
+//! witness     = u32(len(signatures)) || (u8(index) || bytes(signature))*
+//! id          = BLAKE3(bytes("ucoin/txid/v1") || body || witness)
+//! ```
+//!
+//! The network is [`crate::address::Network::id`], so a signature is only valid on one network,
+//! and the algorithm is [`crate::crypto::SignatureAlgorithm::id`]. Multisig keys are listed in
+//! the order of [`crate::multisig::MultisigPolicy::keys`], and signature indices refer to it.
+//! Parents, keys and signatures are encoded as raw bytes, not as their Base64 text, and the
+//! receiver in the binary form of [`crate::address::Address::to_bytes`].
+
+/// Domain separation tag prefixed to the message that is signed.
+pub const TX_TAG: &[u8] = b"ucoin/tx/v1";
+
+/// Domain separation tag prefixed to the preimage of a transaction ID.
+pub const TXID_TAG: &[u8] = b"ucoin/txid/v1";
+
+/// Append-only writer for the canonical encoding.
+#[derive(Debug, Default)]
+pub struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub fn u32(&mut self, value: u32) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    pub fn u64(&mut self, value: u64) -> &mut Self {
+        self.buf.extend_from_slice(&value.to_be_bytes());
+        self
+    }
+
+    /// Writes a length-prefixed byte string.
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.u32(value.len() as u32);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    /// Writes raw bytes without a length prefix.
+    pub fn raw(&mut self, value: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
@@ -0,0 +1,332 @@
+//! Password-encrypted key files.
+//!
+//! A keystore is a JSON document holding an account's algorithm and public key in the clear and
+//! its secret key encrypted with XChaCha20-Poly1305. The encryption key is derived from the
+//! password with Argon2id. The algorithm and public key are authenticated as associated data, so
+//! they cannot be swapped without detection.
+//!
+//! Besides the ciphertext, the file stores a check value computed from the derived key. A
+//! mismatching check value means the password is wrong; a matching check value with a ciphertext
+//! that fails to decrypt means the file was damaged or tampered with.
+
+use std::{fmt, fs, io, path::Path};
+
+use argon2::Argon2;
+use base64ct::{Base64, Encoding};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{
+    address::{Address, Network},
+    crypto::{AccountKey, PublicKey, SecretKey, SignatureAlgorithm},
+};
+
+/// Version of the keystore format written by [`Keystore::encrypt`].
+pub const VERSION: u32 = 1;
+
+/// Costliest [`KdfParams`] a keystore may use, so that a hostile file cannot make loading it
+/// allocate or compute without bound.
+pub const MAX_KDF_PARAMS: KdfParams = KdfParams {
+    memory: 1024 * 1024,
+    iterations: 32,
+    parallelism: 16,
+};
+
+/// Domain separation tag of the password check value.
+const CHECK_TAG: &[u8] = b"ucoin/keystore/check/v1";
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Reasons a keystore cannot be saved or loaded.
+#[derive(Debug)]
+pub enum KeystoreError {
+    Io(io::Error),
+    /// The file is not a keystore.
+    Malformed(serde_json::Error),
+    /// The file was written by an unknown version of the format.
+    UnsupportedVersion(u32),
+    /// The KDF parameters are out of range.
+    BadParams(argon2::Error),
+    /// The KDF parameters exceed [`MAX_KDF_PARAMS`].
+    CostTooHigh(KdfParams),
+    /// The password does not match the one the keystore was encrypted with.
+    WrongPassword,
+    /// The password is right, but the file was damaged or tampered with.
+    Corrupt,
+}
+
+impl fmt::Display for KeystoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "not a keystore: {e}"),
+            Self::UnsupportedVersion(v) => write!(f, "unsupported keystore version {v}"),
+            Self::BadParams(e) => write!(f, "bad key derivation parameters: {e}"),
+            Self::CostTooHigh(params) => write!(
+                f,
+                "key derivation too costly: {} KiB, {} passes, {} lanes",
+                params.memory, params.iterations, params.parallelism
+            ),
+            Self::WrongPassword => write!(f, "wrong password"),
+            Self::Corrupt => write!(f, "keystore is corrupt"),
+        }
+    }
+}
+
+impl std::error::Error for KeystoreError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Malformed(e) => Some(e),
+            Self::BadParams(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for KeystoreError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for KeystoreError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Malformed(e)
+    }
+}
+
+impl From<argon2::Error> for KeystoreError {
+    fn from(e: argon2::Error) -> Self {
+        Self::BadParams(e)
+    }
+}
+
+/// Argon2id cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory in KiB.
+    pub memory: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// 64 MiB and 3 passes, about a quarter of a second on a laptop.
+    fn default() -> Self {
+        Self {
+            memory: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive(&self, password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; 32]>, KeystoreError> {
+        if self.memory > MAX_KDF_PARAMS.memory
+            || self.iterations > MAX_KDF_PARAMS.iterations
+            || self.parallelism > MAX_KDF_PARAMS.parallelism
+        {
+            return Err(KeystoreError::CostTooHigh(*self));
+        }
+
+        let params = argon2::Params::new(self.memory, self.iterations, self.parallelism, None)?;
+        let argon2 = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+
+        let mut key = Zeroizing::new([0; 32]);
+        argon2.hash_password_into(password.as_bytes(), salt, key.as_mut())?;
+
+        Ok(key)
+    }
+}
+
+/// On-disk representation. Binary fields are Base64.
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    algorithm: SignatureAlgorithm,
+    public_key: PublicKey,
+    kdf: KdfParams,
+    salt: String,
+    nonce: String,
+    check: String,
+    ciphertext: String,
+}
+
+/// An account keypair that can be stored encrypted on disk.
+#[derive(Debug, Clone)]
+pub struct Keystore {
+    algorithm: SignatureAlgorithm,
+    public_key: PublicKey,
+    secret_key: SecretKey,
+}
+
+impl Keystore {
+    pub fn new(
+        algorithm: SignatureAlgorithm,
+        public_key: PublicKey,
+        secret_key: SecretKey,
+    ) -> Self {
+        Self {
+            algorithm,
+            public_key,
+            secret_key,
+        }
+    }
+
+    /// Generates a fresh keypair.
+    pub fn generate(algorithm: SignatureAlgorithm) -> oqs::Result<Self> {
+        let (public_key, secret_key) = algorithm.keypair()?;
+
+        Ok(Self::new(algorithm, public_key, secret_key))
+    }
+
+    pub fn algorithm(&self) -> SignatureAlgorithm {
+        self.algorithm
+    }
+
+    pub fn public_key(&self) -> &PublicKey {
+        &self.public_key
+    }
+
+    pub fn secret_key(&self) -> &SecretKey {
+        &self.secret_key
+    }
+
+    pub fn account_key(&self) -> AccountKey {
+        AccountKey::new(self.algorithm, self.public_key.clone())
+    }
+
+    pub fn address(&self, network: Network) -> Address {
+        Address::from_public_key(network, self.algorithm, &self.public_key)
+    }
+
+    /// Encrypts the keypair with `password` into the JSON keystore format.
+    pub fn encrypt(&self, password: &str, params: &KdfParams) -> Result<String, KeystoreError> {
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut salt);
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+
+        let key = params.derive(password, &salt)?;
+        let check = blake3::keyed_hash(&key, CHECK_TAG);
+
+        let ciphertext = XChaCha20Poly1305::new(key.as_ref().into())
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: self.secret_key.as_ref(),
+                    aad: &associated_data(self.algorithm, &self.public_key),
+                },
+            )
+            .expect("encrypting in memory cannot fail");
+
+        let file = KeystoreFile {
+            version: VERSION,
+            algorithm: self.algorithm,
+            public_key: self.public_key.clone(),
+            kdf: *params,
+            salt: Base64::encode_string(&salt),
+            nonce: Base64::encode_string(&nonce),
+            check: Base64::encode_string(check.as_bytes()),
+            ciphertext: Base64::encode_string(&ciphertext),
+        };
+
+        Ok(serde_json::to_string_pretty(&file)?)
+    }
+
+    /// Decrypts a JSON keystore.
+    pub fn decrypt(json: &str, password: &str) -> Result<Self, KeystoreError> {
+        let file: KeystoreFile = serde_json::from_str(json)?;
+        if file.version != VERSION {
+            return Err(KeystoreError::UnsupportedVersion(file.version));
+        }
+
+        let decode = |s: &str| Base64::decode_vec(s).map_err(|_| KeystoreError::Corrupt);
+        let salt = decode(&file.salt)?;
+        let nonce = decode(&file.nonce)?;
+        let check = decode(&file.check)?;
+        let ciphertext = decode(&file.ciphertext)?;
+
+        let key = file.kdf.derive(password, &salt)?;
+        let check: [u8; 32] = check.try_into().map_err(|_| KeystoreError::Corrupt)?;
+        if blake3::keyed_hash(&key, CHECK_TAG) != check {
+            return Err(KeystoreError::WrongPassword);
+        }
+
+        if nonce.len() != NONCE_LEN {
+            return Err(KeystoreError::Corrupt);
+        }
+        let secret_key = XChaCha20Poly1305::new(key.as_ref().into())
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: &associated_data(file.algorithm, &file.public_key),
+                },
+            )
+            .map_err(|_| KeystoreError::Corrupt)?;
+
+        Ok(Self::new(
+            file.algorithm,
+            file.public_key,
+            SecretKey::from_bytes(secret_key),
+        ))
+    }
+
+    /// Reads the account key of the keystore at `path`, which is stored unencrypted.
+    pub fn read_account_key(path: impl AsRef<Path>) -> Result<AccountKey, KeystoreError> {
+        let file: KeystoreFile = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if file.version != VERSION {
+            return Err(KeystoreError::UnsupportedVersion(file.version));
+        }
+
+        Ok(AccountKey::new(file.algorithm, file.public_key))
+    }
+
+    /// Encrypts the keypair and writes it to `path`, readable only by the owner on Unix.
+    ///
+    /// The file is written next to `path` first and renamed into place, so an interrupted save
+    /// never leaves a truncated keystore behind.
+    pub fn save(
+        &self,
+        path: impl AsRef<Path>,
+        password: &str,
+        params: &KdfParams,
+    ) -> Result<(), KeystoreError> {
+        let path = path.as_ref();
+        let json = self.encrypt(password, params)?;
+
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+        io::Write::write_all(&mut options.open(&tmp)?, json.as_bytes())?;
+        fs::rename(&tmp, path)?;
+
+        Ok(())
+    }
+
+    /// Reads and decrypts the keystore at `path`.
+    pub fn load(path: impl AsRef<Path>, password: &str) -> Result<Self, KeystoreError> {
+        Self::decrypt(&fs::read_to_string(path)?, password)
+    }
+}
+
+fn associated_data(algorithm: SignatureAlgorithm, public_key: &PublicKey) -> Vec<u8> {
+    let mut data = vec![algorithm.id()];
+    data.extend_from_slice(public_key.as_ref());
+
+    data
+}
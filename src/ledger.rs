@@ -0,0 +1,711 @@
+//! Ledger combining the transaction DAG with the world state it produces.
+//!
+//! Being consensus-free, ucoin has no leader to decide which of two conflicting transactions
+//! wins. Two transactions conflict when they come from the same sender, carry the same sequence
+//! number, and neither is an ancestor of the other. Conflicts are resolved by a deterministic
+//! rule so that every node holding the same DAG reaches the same state:
+//!
+//! 1. the transaction with the higher cumulative weight (itself plus all of its descendants)
+//!    wins;
+//! 2. on equal weight, the transaction with the lower hash wins.
+//!
+//! Losing transactions are rejected, and so is every transaction that descends from a rejected
+//! one.
+//!
+//! Transactions are replayed in a fixed topological order: among the transactions whose parents
+//! have all been replayed, the one with the lowest timestamp, then hash, comes next. When a
+//! transaction reuses the sequence number of a concurrent accepted one that ranks below it, that
+//! one is evicted and the replay goes back to it. The order does not depend on when transactions
+//! arrive, and a new transaction mostly lands near its end, so only the few transactions after it
+//! are replayed again.
+//!
+//! A transaction must descend from every accepted transaction of its sender, so each sender's
+//! accepted transactions form a single chain ordered by sequence number. Overspending along that
+//! chain is not a conflict: the transaction that overspends is simply rejected.
+//!
+//! Only a shared sequence number makes a conflict. This is intended: two spends of one sender with
+//! different sequence numbers, neither an ancestor of the other, are not resolved by weight even
+//! when the sender can afford both. The one replayed later does not build on the other and is
+//! rejected, so a sender orders its spends by chaining them.
+//!
+//! Transactions breaking the structural rules of [`crate::validate`] are refused before they
+//! reach the DAG. A transaction older than one of its parents is stored but rejected.
+
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::{
+    address::Address,
+    crypto::TxHash,
+    dag::{Dag, DagError, Insertion},
+    snapshot::{Snapshot, SnapshotError},
+    state::WorldState,
+    transaction::{self, SignedTransaction, VerifyError},
+    validate::{self, Rules, Violation},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LedgerError {
+    /// The transaction signature is invalid; it was not stored.
+    Verify(VerifyError),
+    /// The DAG refused the transaction.
+    Dag(DagError),
+    /// The transaction breaks structural rules; it was not stored.
+    Invalid(Vec<Violation>),
+}
+
+impl fmt::Display for LedgerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verify(e) => write!(f, "invalid transaction: {e}"),
+            Self::Dag(e) => write!(f, "{e}"),
+            Self::Invalid(violations) => {
+                let violations: Vec<_> = violations.iter().map(Violation::to_string).collect();
+                write!(f, "invalid transaction: {}", violations.join(", "))
+            }
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Verify(e) => Some(e),
+            Self::Dag(e) => Some(e),
+            Self::Invalid(violations) => violations.first().map(|v| v as _),
+        }
+    }
+}
+
+impl From<VerifyError> for LedgerError {
+    fn from(e: VerifyError) -> Self {
+        Self::Verify(e)
+    }
+}
+
+impl From<DagError> for LedgerError {
+    fn from(e: DagError) -> Self {
+        Self::Dag(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    /// Parked until its parents arrive.
+    Pending,
+    /// Applied to the world state.
+    Accepted,
+    /// Lost a conflict, cannot be applied, or descends from a rejected transaction.
+    Rejected,
+}
+
+/// Transactions from one sender that cannot all be accepted, ordered from winner to loser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub sender: Address,
+    pub transactions: Vec<TxHash>,
+}
+
+/// Status changes caused by a single [`Ledger::insert`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// Transactions that became accepted.
+    pub accepted: Vec<TxHash>,
+    /// Transactions that became rejected, including formerly accepted ones that lost a conflict.
+    pub rejected: Vec<TxHash>,
+    /// Transactions parked until their parents arrive.
+    pub pending: Vec<TxHash>,
+    /// Conflicts involving the inserted transactions.
+    pub conflicts: Vec<Conflict>,
+}
+
+/// Whether a transaction in [`Ledger::history`] was sent or received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    /// Sent, including to the account itself.
+    Out,
+}
+
+impl Direction {
+    /// Lowercase name, as serialized.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::In => "in",
+            Self::Out => "out",
+        }
+    }
+}
+
+/// An accepted transaction sent or received by an account.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HistoryEntry {
+    pub hash: TxHash,
+    pub direction: Direction,
+    /// The receiver of a sent transaction, or the sender of a received one.
+    pub counterparty: Address,
+    pub amount: u64,
+    pub sequence: u64,
+    pub timestamp: u64,
+}
+
+/// Outcome of trying to accept a single transaction on top of the current state.
+enum Attempt {
+    Accepted,
+    Rejected,
+    /// The sequence number is already used by the listed concurrent, accepted transaction.
+    Conflict(TxHash),
+}
+
+/// An accepted transaction that lost a conflict against a later one in the replay.
+#[derive(Debug, Clone)]
+struct Eviction {
+    evicted: TxHash,
+    by: TxHash,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ledger {
+    genesis: WorldState,
+    state: WorldState,
+    dag: Dag,
+    status: HashMap<TxHash, Status>,
+    /// Accepted transactions of each sender by sequence number, each descending from the one
+    /// before.
+    spends: HashMap<Address, BTreeMap<u64, TxHash>>,
+    /// Transactions each transaction has been found to conflict with.
+    conflicts: HashMap<TxHash, BTreeSet<TxHash>>,
+    /// Connected transactions in replay order.
+    order: Vec<TxHash>,
+    /// Index of each connected transaction in `order`.
+    position: HashMap<TxHash, usize>,
+    /// Cumulative weight of the transactions that have been found to conflict.
+    weights: HashMap<TxHash, usize>,
+    /// Transactions with a weight in `weights` that each transaction is or descends from.
+    contested: HashMap<TxHash, BTreeSet<TxHash>>,
+    /// Evictions in the order the replay decided them.
+    evictions: Vec<Eviction>,
+    rules: Rules,
+}
+
+impl Ledger {
+    /// Creates a ledger whose state starts from `genesis`.
+    pub fn new(genesis: WorldState) -> Self {
+        Self {
+            state: genesis.clone(),
+            genesis,
+            dag: Dag::new(),
+            status: HashMap::new(),
+            spends: HashMap::new(),
+            conflicts: HashMap::new(),
+            order: Vec::new(),
+            position: HashMap::new(),
+            weights: HashMap::new(),
+            contested: HashMap::new(),
+            evictions: Vec::new(),
+            rules: Rules::default(),
+        }
+    }
+
+    /// Replaces the default limits of the structural rules new transactions are checked against.
+    pub fn with_rules(self, rules: Rules) -> Self {
+        Self { rules, ..self }
+    }
+
+    /// Creates a ledger continuing from a snapshot, after checking its root.
+    ///
+    /// Transactions of the snapshot's cut count as accepted parents without being held, so the
+    /// ledger only knows the transactions inserted afterwards.
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, SnapshotError> {
+        snapshot.verify()?;
+
+        Ok(Self {
+            dag: Dag::with_base(snapshot.cut().iter().copied()),
+            ..Self::new(snapshot.state().clone())
+        })
+    }
+
+    /// Snapshot of the current state, at the cut formed by the accepted tips.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(self.accepted_tips(), self.state.clone())
+    }
+
+    /// Snapshot of the state produced by `cut` and its ancestors alone.
+    ///
+    /// The conflict rule is applied to those transactions only, as a node that had not seen the
+    /// rest of the DAG yet would have. Every transaction of the cut must be accepted, both here
+    /// and among those transactions, since a snapshot counts its cut as accepted.
+    pub fn snapshot_at(&self, cut: &[TxHash]) -> Result<Snapshot, SnapshotError> {
+        let mut below = HashSet::new();
+        for hash in cut {
+            if self.dag.base().any(|b| b == hash) {
+                continue;
+            }
+            if !self.dag.contains(hash) {
+                return Err(SnapshotError::UnknownTransaction(*hash));
+            }
+            if self.status(hash) != Some(Status::Accepted) {
+                return Err(SnapshotError::RejectedTransaction(*hash));
+            }
+
+            below.insert(*hash);
+            below.extend(self.dag.ancestors(hash));
+        }
+
+        let mut ledger = Self {
+            dag: Dag::with_base(self.dag.base().copied()),
+            rules: self.rules,
+            ..Self::new(self.genesis.clone())
+        };
+        for hash in self.dag.topological_order() {
+            if below.contains(&hash) {
+                ledger.restore(self.dag.get(&hash).unwrap().clone())?;
+            }
+        }
+
+        let rejected = cut
+            .iter()
+            .find(|hash| self.dag.contains(hash) && ledger.status(hash) != Some(Status::Accepted));
+        if let Some(hash) = rejected {
+            return Err(SnapshotError::RejectedTransaction(*hash));
+        }
+
+        Ok(Snapshot::new(cut.iter().copied(), ledger.state))
+    }
+
+    /// Accepted transactions without accepted children, together standing for every accepted
+    /// transaction. Transactions of the cut the ledger started from count as accepted.
+    fn accepted_tips(&self) -> Vec<TxHash> {
+        let is_accepted = |hash: &TxHash| {
+            self.status.get(hash) == Some(&Status::Accepted) || self.dag.base().any(|b| b == hash)
+        };
+
+        self.dag
+            .iter()
+            .map(|(hash, _)| hash)
+            .chain(self.dag.base())
+            .filter(|hash| is_accepted(hash))
+            .filter(|hash| !self.dag.children(hash).any(is_accepted))
+            .copied()
+            .collect()
+    }
+
+    pub fn state(&self) -> &WorldState {
+        &self.state
+    }
+
+    pub fn dag(&self) -> &Dag {
+        &self.dag
+    }
+
+    pub fn get(&self, hash: &TxHash) -> Option<&SignedTransaction> {
+        self.dag.get(hash)
+    }
+
+    pub fn status(&self, hash: &TxHash) -> Option<Status> {
+        if self.dag.is_orphan(hash) {
+            return Some(Status::Pending);
+        }
+
+        self.status.get(hash).copied()
+    }
+
+    /// Accepted transactions sent from `sender`, by sequence number.
+    pub fn spends(&self, sender: &Address) -> impl Iterator<Item = &TxHash> {
+        self.spends
+            .get(sender)
+            .into_iter()
+            .flat_map(BTreeMap::values)
+    }
+
+    /// Transactions that have been found to conflict with `hash`.
+    pub fn conflicts(&self, hash: &TxHash) -> impl Iterator<Item = &TxHash> {
+        self.conflicts.get(hash).into_iter().flatten()
+    }
+
+    /// Accepted transactions sent or received by `address`, in replay order, so parents come
+    /// first. Transactions below the snapshot the ledger started from are not held, and left out.
+    pub fn history(&self, address: &Address) -> Vec<HistoryEntry> {
+        let Some(wallet) = self.state.wallet(address) else {
+            return Vec::new();
+        };
+
+        let mut hashes: Vec<_> = wallet
+            .history()
+            .iter()
+            .filter(|hash| self.position.contains_key(hash))
+            .collect();
+        hashes.sort_by_key(|hash| self.position[hash]);
+
+        hashes
+            .into_iter()
+            .map(|hash| {
+                let tx = self.dag.get(hash).unwrap().transaction();
+                let sender = tx.sender_address();
+                let (direction, counterparty) = match sender == *address {
+                    true => (Direction::Out, *tx.receiver()),
+                    false => (Direction::In, sender),
+                };
+
+                HistoryEntry {
+                    hash: *hash,
+                    direction,
+                    counterparty,
+                    amount: tx.amount(),
+                    sequence: tx.sequence(),
+                    timestamp: tx.timestamp(),
+                }
+            })
+            .collect()
+    }
+
+    /// Verifies, validates and stores a transaction, then accepts or rejects everything it
+    /// connects.
+    pub fn insert(&mut self, tx: SignedTransaction) -> Result<Report, LedgerError> {
+        tx.verify()?;
+        self.rules
+            .validate(tx.transaction(), transaction::now())
+            .map_err(LedgerError::Invalid)?;
+
+        self.connect(tx)
+    }
+
+    /// Stores a transaction that was verified and validated before, such as one read back from
+    /// disk or copied from another ledger, without checking it again.
+    ///
+    /// Transactions that were valid under older, looser rules are restored all the same.
+    pub(crate) fn restore(&mut self, tx: SignedTransaction) -> Result<Report, LedgerError> {
+        self.connect(tx)
+    }
+
+    /// Stores a transaction, then accepts or rejects everything it connects.
+    fn connect(&mut self, tx: SignedTransaction) -> Result<Report, LedgerError> {
+        let hash = tx.hash();
+        let mut report = Report::default();
+
+        let connected = match self.dag.insert(tx)? {
+            Insertion::Orphaned(_) => {
+                report.pending.push(hash);
+                return Ok(report);
+            }
+            Insertion::Connected(connected) => connected,
+        };
+
+        // Parents come first, so each one is a tip of the DAG placed so far.
+        let mut from = usize::MAX;
+        for hash in &connected {
+            from = from.min(self.place(hash)).min(self.reweigh(hash));
+        }
+        self.replay(from, &mut report);
+
+        Ok(report)
+    }
+
+    /// Adds a newly connected transaction to the replay order and returns its position.
+    ///
+    /// The order is the one a topological sort takes when it always continues with the lowest
+    /// timestamp, then hash, among the transactions whose parents are done. A transaction without
+    /// children is taken right before the first transaction after its parents that sorts above it,
+    /// and the rest of the order stays the same, whatever order transactions are placed in.
+    fn place(&mut self, hash: &TxHash) -> usize {
+        let key = |hash: &TxHash| (self.dag.get(hash).unwrap().transaction().timestamp(), *hash);
+        let tx = self.dag.get(hash).unwrap().transaction();
+
+        let parents = tx.parents().iter().filter_map(|p| self.position.get(p));
+        let after = parents.max().map_or(0, |p| p + 1);
+        let at = (after..self.order.len())
+            .find(|i| key(&self.order[*i]) > key(hash))
+            .unwrap_or(self.order.len());
+
+        self.order.insert(at, *hash);
+        for (i, hash) in self.order.iter().enumerate().skip(at) {
+            self.position.insert(*hash, i);
+        }
+
+        at
+    }
+
+    /// Adds a newly connected transaction to the weight of the transactions in `weights` it
+    /// descends from, and returns the first position where a conflict decided before may now go
+    /// the other way.
+    fn reweigh(&mut self, hash: &TxHash) -> usize {
+        let tx = self.dag.get(hash).unwrap().transaction();
+        let above: BTreeSet<_> = tx
+            .parents()
+            .iter()
+            .filter_map(|p| self.contested.get(p))
+            .flatten()
+            .copied()
+            .collect();
+        if above.is_empty() {
+            return usize::MAX;
+        }
+
+        for contested in &above {
+            *self.weights.get_mut(contested).unwrap() += 1;
+        }
+
+        let old = |hash: &TxHash| {
+            let weight = self.weights[hash] - usize::from(above.contains(hash));
+            (Reverse(weight), *hash)
+        };
+        let mut from = usize::MAX;
+        for a in &above {
+            for b in &self.conflicts[a] {
+                if (old(a) < old(b)) != (self.rank(a) < self.rank(b)) {
+                    // Decided when the later of the two was replayed.
+                    from = from.min(self.position[a].max(self.position[b]));
+                }
+            }
+        }
+
+        self.contested.insert(*hash, above);
+        from
+    }
+
+    /// Starts tracking the weight of a transaction found to conflict: one plus its number of
+    /// descendants.
+    fn contest(&mut self, hash: &TxHash) {
+        if self.weights.contains_key(hash) {
+            return;
+        }
+
+        let descendants = self.dag.descendants(hash);
+        self.weights.insert(*hash, descendants.len() + 1);
+        for descendant in descendants.iter().chain([hash]) {
+            self.contested.entry(*descendant).or_default().insert(*hash);
+        }
+    }
+
+    /// Order of precedence in a conflict: heavier first, then lower hash.
+    fn rank(&self, hash: &TxHash) -> (Reverse<usize>, TxHash) {
+        (Reverse(self.weights[hash]), *hash)
+    }
+
+    /// Replays the transactions from position `from` on, reaching the outcome a replay from
+    /// genesis would.
+    ///
+    /// Evictions decided after an earlier replay first got past `from` depend on what changed, so
+    /// they are undone and the replay starts at the earliest transaction they evicted instead.
+    fn replay(&mut self, from: usize, report: &mut Report) {
+        if from >= self.order.len() {
+            return;
+        }
+
+        let undone = self
+            .evictions
+            .iter()
+            .position(|e| self.position[&e.by] >= from)
+            .unwrap_or(self.evictions.len());
+        let mut position = self
+            .evictions
+            .drain(undone..)
+            .map(|e| self.position[&e.evicted])
+            .fold(from, usize::min);
+        let mut evicted: HashSet<_> = self.evictions.iter().map(|e| e.evicted).collect();
+
+        let mut before = HashMap::new();
+        self.rewind(position, &mut before);
+
+        while let Some(hash) = self.order.get(position).copied() {
+            position += 1;
+            if evicted.contains(&hash) {
+                self.status.insert(hash, Status::Rejected);
+                continue;
+            }
+
+            let Attempt::Conflict(rival) = self.attempt(&hash) else {
+                continue;
+            };
+
+            let sender = self.dag.get(&hash).unwrap().transaction().sender_address();
+            self.contest(&rival);
+            self.contest(&hash);
+            let mut members = [rival, hash];
+            members.sort_by_key(|hash| self.rank(hash));
+            self.record_conflict(&mut report.conflicts, sender, &members);
+
+            if self.rank(&rival) > self.rank(&hash) {
+                self.evictions.push(Eviction {
+                    evicted: rival,
+                    by: hash,
+                });
+                evicted.insert(rival);
+                position = self.position[&rival];
+                self.rewind(position, &mut before);
+                continue;
+            }
+
+            self.status.insert(hash, Status::Rejected);
+        }
+
+        self.diff(&before, report);
+    }
+
+    /// Undoes the outcome of every transaction from position `to` on, latest first, keeping the
+    /// status each had before the first rewind in `before`.
+    fn rewind(&mut self, to: usize, before: &mut HashMap<TxHash, Option<Status>>) {
+        for hash in self.order[to..].iter().rev() {
+            let status = self.status.remove(hash);
+            before.entry(*hash).or_insert(status);
+            if status != Some(Status::Accepted) {
+                continue;
+            }
+
+            let tx = self.dag.get(hash).unwrap();
+            self.state
+                .revert(tx)
+                .expect("accepted transactions are reverted latest first");
+
+            let sender = tx.transaction().sender_address();
+            if let Some(spends) = self.spends.get_mut(&sender) {
+                spends.remove(&tx.transaction().sequence());
+                if spends.is_empty() {
+                    self.spends.remove(&sender);
+                }
+            }
+        }
+    }
+
+    /// Tries to apply a connected transaction on top of the current state and records the result,
+    /// unless it is a conflict.
+    fn attempt(&mut self, hash: &TxHash) -> Attempt {
+        let tx = self.dag.get(hash).unwrap();
+        let parents = tx.transaction().parents();
+
+        // Checked here rather than by the rules, so that it does not depend on whether the
+        // parents had arrived before the transaction.
+        if parents
+            .iter()
+            .any(|p| self.status.get(p) == Some(&Status::Rejected))
+            || validate::newer_parents(tx.transaction(), &self.dag)
+                .next()
+                .is_some()
+        {
+            self.status.insert(*hash, Status::Rejected);
+            return Attempt::Rejected;
+        }
+
+        let sender = tx.transaction().sender_address();
+        let sequence = tx.transaction().sequence();
+
+        // Building on the sender's last accepted transaction, this one may come next. Otherwise
+        // it conflicts with the accepted transaction of the same sequence number if it builds on
+        // the one before, and is invalid if not.
+        let attempt = match self.spends.get(&sender) {
+            Some(spends) if !self.descends_from(hash, spends.values().next_back().unwrap()) => {
+                let rival = spends.get(&sequence);
+                let previous = sequence.checked_sub(1).and_then(|s| spends.get(&s));
+
+                match rival {
+                    Some(rival)
+                        if !self.descends_from(hash, rival)
+                            && previous.is_none_or(|p| self.descends_from(hash, p)) =>
+                    {
+                        Attempt::Conflict(*rival)
+                    }
+                    _ => Attempt::Rejected,
+                }
+            }
+            _ => match self.state.transfer(tx) {
+                Ok(()) => Attempt::Accepted,
+                Err(_) => Attempt::Rejected,
+            },
+        };
+
+        match attempt {
+            Attempt::Accepted => {
+                self.status.insert(*hash, Status::Accepted);
+                self.spends
+                    .entry(sender)
+                    .or_default()
+                    .insert(sequence, *hash);
+            }
+            Attempt::Rejected => {
+                self.status.insert(*hash, Status::Rejected);
+            }
+            Attempt::Conflict(_) => {}
+        }
+
+        attempt
+    }
+
+    /// Whether `ancestor` is an ancestor of `hash`, whose parents are all accepted.
+    ///
+    /// Timestamps never decrease from an accepted transaction to its children, so the search
+    /// skips every transaction older than `ancestor` instead of walking all ancestors of `hash`.
+    fn descends_from(&self, hash: &TxHash, ancestor: &TxHash) -> bool {
+        let floor = self.dag.get(ancestor).unwrap().transaction().timestamp();
+        let mut stack = vec![*hash];
+        let mut seen = HashSet::new();
+
+        while let Some(hash) = stack.pop() {
+            let Some(tx) = self.dag.get(&hash) else {
+                continue;
+            };
+
+            for parent in tx.transaction().parents() {
+                if parent == ancestor {
+                    return true;
+                }
+
+                let recent = self
+                    .dag
+                    .get(parent)
+                    .is_some_and(|p| p.transaction().timestamp() >= floor);
+                if recent && seen.insert(*parent) {
+                    stack.push(*parent);
+                }
+            }
+        }
+
+        false
+    }
+
+    fn record_conflict(
+        &mut self,
+        conflicts: &mut Vec<Conflict>,
+        sender: Address,
+        members: &[TxHash],
+    ) {
+        for a in members {
+            for b in members {
+                if a != b {
+                    self.conflicts.entry(*a).or_default().insert(*b);
+                }
+            }
+        }
+
+        if !conflicts.iter().any(|c| c.transactions == members) {
+            conflicts.push(Conflict {
+                sender,
+                transactions: members.to_vec(),
+            });
+        }
+    }
+
+    /// Adds the transactions whose status differs from `before` to the report, parents first.
+    fn diff(&self, before: &HashMap<TxHash, Option<Status>>, report: &mut Report) {
+        let mut changed: Vec<_> = before
+            .iter()
+            .filter(|(hash, status)| self.status.get(*hash) != status.as_ref())
+            .map(|(hash, _)| (self.position[hash], *hash))
+            .collect();
+        changed.sort();
+
+        for (_, hash) in changed {
+            match self.status[&hash] {
+                Status::Accepted => report.accepted.push(hash),
+                Status::Rejected => report.rejected.push(hash),
+                Status::Pending => report.pending.push(hash),
+            }
+        }
+    }
+}
@@ -0,0 +1,42 @@
+//! Post-quantum DAG-based consensus-free blockchain.
+
+pub mod address;
+pub mod crypto;
+pub mod dag;
+pub mod encoding;
+pub mod keystore;
+pub mod ledger;
+pub mod merkle;
+pub mod multisig;
+pub mod node;
+pub mod protocol;
+pub mod rpc;
+pub mod seed;
+pub mod snapshot;
+pub mod state;
+pub mod storage;
+pub mod sync;
+pub mod tips;
+pub mod transaction;
+pub mod transport;
+pub mod validate;
+pub mod wire;
+
+pub use address::{Address, Network};
+pub use crypto::{AccountKey, Digest, PublicKey, SecretKey, Signature, SignatureAlgorithm, TxHash};
+pub use dag::Dag;
+pub use keystore::{KdfParams, Keystore, KeystoreError};
+pub use ledger::Ledger;
+pub use merkle::WalletProof;
+pub use multisig::{MultisigPolicy, PartiallySignedTransaction};
+pub use node::{Node, NodeError};
+pub use rpc::{RpcError, RpcServer};
+pub use seed::{Mnemonic, Seed};
+pub use snapshot::Snapshot;
+pub use state::{StateError, Wallet, WorldState};
+pub use storage::{StorageError, Store};
+pub use sync::{SyncError, SyncProgress};
+pub use tips::TipSelector;
+pub use transaction::{Sender, SignError, SignedTransaction, Transaction, VerifyError};
+pub use transport::{Session, TransportError};
+pub use validate::{Rules, Violation};
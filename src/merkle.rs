@@ -0,0 +1,115 @@
+//! Merkle commitments to the world state.
+//!
+//! The state root is the root of a binary Merkle tree over every wallet, sorted by address, built
+//! the same way as the tree of RFC 6962 (Certificate Transparency):
+//!
+//! ```text
+//! leaf       = BLAKE3(u8(0) || address[34] || u64(balance) || u64(sequence))
+//! node(l, r) = BLAKE3(u8(1) || l || r)
+//! root([])   = BLAKE3("")
+//! root([x])  = x
+//! root(xs)   = node(root(xs[..k]), root(xs[k..])), k the largest power of two below len(xs)
+//! ```
+//!
+//! The prefixes keep a leaf from being passed off as a node. Addresses are in the binary form of
+//! [`crate::address::Address::to_bytes`], and integers are big-endian. Wallet histories are not
+//! committed to.
+//!
+//! A [`WalletProof`] lets a light client that only knows the root check a single wallet's balance.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{address::Address, crypto::Digest, encoding::Encoder};
+
+/// Hash of a wallet entry.
+pub fn leaf(address: &Address, balance: u64, sequence: u64) -> Digest {
+    let mut encoder = Encoder::new();
+    encoder
+        .u8(0)
+        .raw(&address.to_bytes())
+        .u64(balance)
+        .u64(sequence);
+
+    blake3::hash(&encoder.finish()).into()
+}
+
+fn node(left: &Digest, right: &Digest) -> Digest {
+    let mut encoder = Encoder::new();
+    encoder.u8(1).raw(left.as_ref()).raw(right.as_ref());
+
+    blake3::hash(&encoder.finish()).into()
+}
+
+/// Size of the left subtree of a tree with `n` > 1 leaves.
+fn split(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// Root of the tree over `leaves`.
+pub fn root(leaves: &[Digest]) -> Digest {
+    match leaves {
+        [] => blake3::hash(b"").into(),
+        [leaf] => *leaf,
+        _ => {
+            let (left, right) = leaves.split_at(split(leaves.len()));
+            node(&root(left), &root(right))
+        }
+    }
+}
+
+/// Siblings on the way from leaf `index` up to the root, lowest first.
+pub fn path(leaves: &[Digest], index: usize) -> Vec<Digest> {
+    if leaves.len() <= 1 {
+        return Vec::new();
+    }
+
+    let (left, right) = leaves.split_at(split(leaves.len()));
+    let (mut path, sibling) = match index < left.len() {
+        true => (path(left, index), root(right)),
+        false => (path(right, index - left.len()), root(left)),
+    };
+    path.push(sibling);
+
+    path
+}
+
+/// Recomputes the root from a leaf and its path, or `None` if the path has the wrong length for
+/// a tree of `size` leaves.
+pub fn root_from_path(index: u64, size: u64, leaf: Digest, path: &[Digest]) -> Option<Digest> {
+    if index >= size {
+        return None;
+    }
+    if size == 1 {
+        return path.is_empty().then_some(leaf);
+    }
+
+    let (sibling, rest) = path.split_last()?;
+    let k = split(size.try_into().ok()?) as u64;
+
+    Some(match index < k {
+        true => node(&root_from_path(index, k, leaf, rest)?, sibling),
+        false => node(sibling, &root_from_path(index - k, size - k, leaf, rest)?),
+    })
+}
+
+/// Proof that a wallet with the given balance and sequence number is part of a state.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WalletProof {
+    pub address: Address,
+    pub balance: u64,
+    pub sequence: u64,
+    /// Position of the wallet among all wallets sorted by address.
+    pub index: u64,
+    /// Number of wallets in the state.
+    pub size: u64,
+    pub path: Vec<Digest>,
+}
+
+impl WalletProof {
+    /// Whether the proof holds for a state with this root.
+    pub fn verify(&self, root: &Digest) -> bool {
+        let leaf = leaf(&self.address, self.balance, self.sequence);
+
+        root_from_path(self.index, self.size, leaf, &self.path).as_ref() == Some(root)
+    }
+}
@@ -0,0 +1,269 @@
+//! Multi-signature (m-of-n) accounts.
+//!
+//! A multisig account is controlled by up to [`MAX_KEYS`] public keys, possibly of different
+//! algorithms, and a threshold m. Its address commits to the threshold and the keys, so changing
+//! either yields a different account. A transaction from it carries signatures from at least m of
+//! the keys, each tagged with the index of its key in [`MultisigPolicy::keys`].
+//!
+//! Cosigners pass a [`PartiallySignedTransaction`] around, e.g. as JSON, each adding their
+//! signature offline, until the threshold is met and it can be finalized.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::{AccountKey, SecretKey, Signature},
+    encoding::Encoder,
+    transaction::{self, Cosignature, SignError, SignedTransaction, Transaction, VerifyError},
+};
+
+/// Maximum number of keys in a multisig policy.
+pub const MAX_KEYS: usize = 16;
+
+/// Reasons a multisig policy is invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PolicyError {
+    /// The threshold is zero or larger than the number of keys.
+    BadThreshold { threshold: u8, keys: usize },
+    /// More than [`MAX_KEYS`] keys.
+    TooManyKeys(usize),
+    /// The same key is listed twice.
+    DuplicateKey,
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadThreshold { threshold, keys } => {
+                write!(f, "bad threshold {threshold} for {keys} keys")
+            }
+            Self::TooManyKeys(n) => write!(f, "{n} keys, at most {MAX_KEYS} allowed"),
+            Self::DuplicateKey => write!(f, "duplicate key"),
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+/// Reasons a cosigner cannot add to or finalize a [`PartiallySignedTransaction`].
+#[derive(Debug)]
+pub enum CosignError {
+    /// The sender has no key with this index.
+    UnknownSigner(u8),
+    /// The transactions being combined differ.
+    DifferentTransaction,
+    /// Fewer signatures than the sender's threshold.
+    Incomplete {
+        threshold: usize,
+        actual: usize,
+    },
+    Sign(SignError),
+    Verify(VerifyError),
+}
+
+impl fmt::Display for CosignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownSigner(index) => write!(f, "no key with index {index}"),
+            Self::DifferentTransaction => write!(f, "signatures are for a different transaction"),
+            Self::Incomplete { threshold, actual } => {
+                write!(f, "{actual} signatures, {threshold} required")
+            }
+            Self::Sign(e) => write!(f, "{e}"),
+            Self::Verify(e) => write!(f, "invalid signature: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CosignError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Sign(e) => Some(e),
+            Self::Verify(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<SignError> for CosignError {
+    fn from(e: SignError) -> Self {
+        Self::Sign(e)
+    }
+}
+
+impl From<VerifyError> for CosignError {
+    fn from(e: VerifyError) -> Self {
+        Self::Verify(e)
+    }
+}
+
+/// The keys and threshold of a multisig account.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "RawPolicy")]
+pub struct MultisigPolicy {
+    threshold: u8,
+    keys: Vec<AccountKey>,
+}
+
+#[derive(Deserialize)]
+struct RawPolicy {
+    threshold: u8,
+    keys: Vec<AccountKey>,
+}
+
+impl TryFrom<RawPolicy> for MultisigPolicy {
+    type Error = PolicyError;
+
+    fn try_from(raw: RawPolicy) -> Result<Self, Self::Error> {
+        Self::new(raw.threshold, raw.keys)
+    }
+}
+
+impl MultisigPolicy {
+    /// Creates a policy requiring `threshold` signatures from `keys`.
+    ///
+    /// Keys are sorted by algorithm id and key bytes, so every cosigner derives the same address
+    /// and key indices regardless of the order the keys were listed in.
+    pub fn new(threshold: u8, mut keys: Vec<AccountKey>) -> Result<Self, PolicyError> {
+        if keys.len() > MAX_KEYS {
+            return Err(PolicyError::TooManyKeys(keys.len()));
+        }
+        if threshold == 0 || threshold as usize > keys.len() {
+            return Err(PolicyError::BadThreshold {
+                threshold,
+                keys: keys.len(),
+            });
+        }
+
+        keys.sort_by(|a, b| {
+            (a.algorithm().id(), a.public_key().as_ref())
+                .cmp(&(b.algorithm().id(), b.public_key().as_ref()))
+        });
+        if keys.windows(2).any(|pair| pair[0] == pair[1]) {
+            return Err(PolicyError::DuplicateKey);
+        }
+
+        Ok(Self { threshold, keys })
+    }
+
+    pub fn threshold(&self) -> u8 {
+        self.threshold
+    }
+
+    pub fn keys(&self) -> &[AccountKey] {
+        &self.keys
+    }
+
+    /// Index of `key` in [`MultisigPolicy::keys`].
+    pub fn index_of(&self, key: &AccountKey) -> Option<u8> {
+        self.keys.iter().position(|k| k == key).map(|i| i as u8)
+    }
+
+    /// Appends the canonical encoding described in [`crate::encoding`], without the leading
+    /// multisig kind byte.
+    pub fn encode(&self, encoder: &mut Encoder) {
+        encoder.u8(self.threshold).u32(self.keys.len() as u32);
+        for key in &self.keys {
+            encoder
+                .u8(key.algorithm().id())
+                .bytes(key.public_key().as_ref());
+        }
+    }
+}
+
+/// A transaction collecting signatures from its sender's cosigners.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PartiallySignedTransaction {
+    transaction: Transaction,
+    signatures: BTreeMap<u8, Signature>,
+}
+
+impl PartiallySignedTransaction {
+    pub fn new(transaction: Transaction) -> Self {
+        Self {
+            transaction,
+            signatures: BTreeMap::new(),
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    /// Indices of the keys that have signed so far.
+    pub fn signers(&self) -> impl Iterator<Item = u8> + '_ {
+        self.signatures.keys().copied()
+    }
+
+    /// Signs with the key at `index` in [`crate::transaction::Sender::keys`].
+    pub fn sign(&mut self, index: u8, sk: &SecretKey) -> Result<(), CosignError> {
+        let key = self.key(index)?;
+        let message = self.transaction.signing_message();
+
+        let signature = transaction::sign_message(key.algorithm(), &message, sk)?;
+        transaction::verify_message(key, &message, &signature)?;
+
+        self.signatures.insert(index, signature);
+        Ok(())
+    }
+
+    /// Adds a signature made elsewhere by the key at `index`, after checking it.
+    pub fn add_signature(&mut self, index: u8, signature: Signature) -> Result<(), CosignError> {
+        let key = self.key(index)?;
+        transaction::verify_message(key, &self.transaction.signing_message(), &signature)?;
+
+        self.signatures.insert(index, signature);
+        Ok(())
+    }
+
+    /// Adds the signatures of another copy of the same transaction.
+    pub fn combine(&mut self, other: PartiallySignedTransaction) -> Result<(), CosignError> {
+        if other.transaction.signing_message() != self.transaction.signing_message() {
+            return Err(CosignError::DifferentTransaction);
+        }
+
+        for (index, signature) in other.signatures {
+            self.add_signature(index, signature)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether enough cosigners have signed to finalize.
+    pub fn is_complete(&self) -> bool {
+        self.signatures.len() >= self.transaction.sender().threshold()
+    }
+
+    /// Produces the signed transaction from the signatures of the lowest key indices, so that
+    /// every cosigner finalizing the same signatures gets the same transaction ID.
+    pub fn finalize(self) -> Result<SignedTransaction, CosignError> {
+        let threshold = self.transaction.sender().threshold();
+        if self.signatures.len() < threshold {
+            return Err(CosignError::Incomplete {
+                threshold,
+                actual: self.signatures.len(),
+            });
+        }
+
+        let signatures = self
+            .signatures
+            .into_iter()
+            .take(threshold)
+            .map(|(index, signature)| Cosignature { index, signature })
+            .collect();
+
+        let signed = SignedTransaction::from_parts(self.transaction, signatures);
+        signed.verify()?;
+
+        Ok(signed)
+    }
+
+    fn key(&self, index: u8) -> Result<&AccountKey, CosignError> {
+        self.transaction
+            .sender()
+            .keys()
+            .get(index as usize)
+            .ok_or(CosignError::UnknownSigner(index))
+    }
+}
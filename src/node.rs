@@ -0,0 +1,716 @@
+//! A peer-to-peer node that gossips transactions over TCP.
+//!
+//! Every transaction a node stores, whether submitted locally or received from a peer, is forwarded
+//! to all other peers. A node remembers the hashes of the transactions it stored most recently and
+//! drops repeats, so gossip stops once every node has a transaction. When a received transaction
+//! has parents the node does not know, it asks the peer that sent it for them by hash.
+//!
+//! A node that joins late or was offline catches up with [`Node::sync`]. Changes to the ledger,
+//! however they come about, are announced to [subscribers](Node::subscribe).
+//!
+//! See [`crate::protocol`] for the messages and the handshake, and [`crate::transport`] for the
+//! encryption of the connections.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt, io,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{tcp::OwnedReadHalf, TcpListener, TcpStream, ToSocketAddrs},
+    sync::{broadcast, mpsc, oneshot},
+    task::AbortHandle,
+};
+
+use crate::{
+    address::Network,
+    crypto::{AccountKey, TxHash},
+    dag::DagError,
+    keystore::Keystore,
+    ledger::{Ledger, LedgerError, Report},
+    protocol::{self, auth_message, Hello, Message, MAX_BATCH, MAX_FRAME_LEN, PROTOCOL_VERSION},
+    sync::{self, SyncError, SyncProgress},
+    transaction::{self, SignError, SignedTransaction, VerifyError},
+    transport::{self, KemKeypair, Opener, Sealer, Session, TransportError},
+    wire::WireError,
+};
+
+/// How long a peer has to complete the handshake.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long a peer has to answer a sync request.
+pub const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Events buffered for each subscriber. Subscribers that fall further behind miss events.
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// Messages queued for each peer. Gossip to a peer that falls further behind is dropped, and
+/// answers to its requests wait for room.
+pub const OUTBOX_CAPACITY: usize = 1024;
+
+/// Hashes of stored transactions a node remembers, to drop repeats without checking them again.
+pub const SEEN_CAPACITY: usize = 100_000;
+
+/// A change to the ledger of a node.
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// The transaction became accepted and was applied to the world state.
+    Accepted(SignedTransaction),
+    /// The transaction became rejected. If it was accepted before, it was reverted.
+    Rejected(SignedTransaction),
+}
+
+/// Reasons a connection to a peer fails.
+#[derive(Debug)]
+pub enum NodeError {
+    Io(io::Error),
+    /// The peer sent a message that does not decode.
+    Wire(WireError),
+    /// The peer speaks another protocol version.
+    WrongVersion(u64),
+    /// The peer is on another network.
+    WrongNetwork(Network),
+    /// The peer's handshake signature does not verify.
+    BadAuth(VerifyError),
+    /// The node key cannot sign the handshake.
+    Sign(SignError),
+    /// The key exchange failed, or a frame from the peer did not decrypt.
+    Transport(TransportError),
+    /// The peer sent a message out of turn.
+    UnexpectedMessage,
+    /// The peer is this node.
+    SelfConnection,
+    /// This node is already connected to the peer.
+    AlreadyConnected,
+    /// The peer is not connected, or disconnected before answering.
+    NotConnected,
+    /// The peer did not complete the handshake or answer a request in time.
+    Timeout,
+    /// The peer sent an unusable answer during a sync.
+    Sync(SyncError),
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Wire(e) => write!(f, "malformed message: {e}"),
+            Self::WrongVersion(v) => write!(f, "peer speaks protocol version {v}"),
+            Self::WrongNetwork(network) => write!(f, "peer is on {network}"),
+            Self::BadAuth(e) => write!(f, "peer failed to authenticate: {e}"),
+            Self::Sign(e) => write!(f, "cannot sign handshake: {e}"),
+            Self::Transport(e) => write!(f, "{e}"),
+            Self::UnexpectedMessage => write!(f, "unexpected message"),
+            Self::SelfConnection => write!(f, "connected to self"),
+            Self::AlreadyConnected => write!(f, "already connected to peer"),
+            Self::NotConnected => write!(f, "peer not connected"),
+            Self::Timeout => write!(f, "peer timed out"),
+            Self::Sync(e) => write!(f, "sync failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Wire(e) => Some(e),
+            Self::BadAuth(e) => Some(e),
+            Self::Sign(e) => Some(e),
+            Self::Transport(e) => Some(e),
+            Self::Sync(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for NodeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<WireError> for NodeError {
+    fn from(e: WireError) -> Self {
+        Self::Wire(e)
+    }
+}
+
+impl From<TransportError> for NodeError {
+    fn from(e: TransportError) -> Self {
+        Self::Transport(e)
+    }
+}
+
+impl From<SyncError> for NodeError {
+    fn from(e: SyncError) -> Self {
+        Self::Sync(e)
+    }
+}
+
+/// Sends one message, sealed for the peer.
+pub async fn send<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    sealer: &mut Sealer,
+    message: &Message,
+) -> io::Result<()> {
+    protocol::write_frame(writer, &sealer.seal(&message.to_bytes())).await
+}
+
+/// Receives and opens one sealed message.
+pub async fn receive<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    opener: &mut Opener,
+) -> Result<Message, NodeError> {
+    let frame = protocol::read_frame(reader).await?;
+
+    Ok(Message::from_bytes(&opener.open(&frame)?)?)
+}
+
+/// Sends one handshake message in the clear.
+async fn send_clear<W: AsyncWrite + Unpin>(writer: &mut W, message: &Message) -> io::Result<()> {
+    protocol::write_frame(writer, &message.to_bytes()).await
+}
+
+/// Receives one handshake message in the clear.
+async fn receive_clear<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Message, NodeError> {
+    let frame = protocol::read_frame(reader).await?;
+
+    Ok(Message::from_bytes(&frame)?)
+}
+
+/// Runs the handshake on a fresh connection as the node identified by `identity`, and returns
+/// the encrypted session with the authenticated peer.
+pub async fn handshake<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    network: Network,
+    identity: &Keystore,
+) -> Result<Session, NodeError> {
+    let run = async {
+        let kem = KemKeypair::generate()?;
+        let own = Hello {
+            version: PROTOCOL_VERSION,
+            network,
+            node_key: identity.account_key(),
+            nonce: rand::random(),
+            kem_key: kem.public_key().to_vec(),
+        };
+        send_clear(stream, &Message::Hello(own.clone())).await?;
+
+        let Message::Hello(peer) = receive_clear(stream).await? else {
+            return Err(NodeError::UnexpectedMessage);
+        };
+        if peer.version != PROTOCOL_VERSION {
+            return Err(NodeError::WrongVersion(peer.version));
+        }
+        if peer.network != network {
+            return Err(NodeError::WrongNetwork(peer.network));
+        }
+        if peer.node_key == own.node_key {
+            return Err(NodeError::SelfConnection);
+        }
+
+        let (ciphertext, sent_secret) = transport::encapsulate(&peer.kem_key)?;
+        let signature = transaction::sign_message(
+            identity.algorithm(),
+            &auth_message(&own, &peer, &ciphertext),
+            identity.secret_key(),
+        )
+        .map_err(NodeError::Sign)?;
+        send_clear(
+            stream,
+            &Message::Auth {
+                ciphertext,
+                signature,
+            },
+        )
+        .await?;
+
+        let Message::Auth {
+            ciphertext,
+            signature,
+        } = receive_clear(stream).await?
+        else {
+            return Err(NodeError::UnexpectedMessage);
+        };
+        let message = auth_message(&peer, &own, &ciphertext);
+        transaction::verify_message(&peer.node_key, &message, &signature)
+            .map_err(NodeError::BadAuth)?;
+        let received_secret = kem.decapsulate(&ciphertext)?;
+
+        Ok(Session::new(
+            peer.node_key.clone(),
+            &Message::Hello(own).to_bytes(),
+            &Message::Hello(peer).to_bytes(),
+            &sent_secret,
+            &received_secret,
+        ))
+    };
+
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, run)
+        .await
+        .map_err(|_| NodeError::Timeout)?
+}
+
+/// A connected, authenticated peer.
+struct Peer {
+    /// Tells this connection apart from later ones to the same peer.
+    id: u64,
+    addr: SocketAddr,
+    outbox: mpsc::Sender<Message>,
+    reader: AbortHandle,
+    /// Where to deliver the answer to the request in flight.
+    reply: Option<oneshot::Sender<Message>>,
+}
+
+struct Shared {
+    network: Network,
+    identity: Keystore,
+    local_addr: SocketAddr,
+    ledger: Mutex<Ledger>,
+    /// Transactions stored most recently.
+    seen: Mutex<Seen>,
+    peers: Mutex<HashMap<AccountKey, Peer>>,
+    events: broadcast::Sender<Event>,
+    next_id: AtomicU64,
+    listener: Mutex<Option<AbortHandle>>,
+}
+
+/// Handle to a running node. Clones share the same node.
+#[derive(Clone)]
+pub struct Node {
+    shared: Arc<Shared>,
+}
+
+impl Node {
+    /// Starts a node on `network` around `ledger`, listening on `addr`. Must be called from
+    /// within a Tokio runtime.
+    pub async fn start(
+        network: Network,
+        identity: Keystore,
+        ledger: Ledger,
+        addr: impl ToSocketAddrs,
+    ) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+
+        let node = Self {
+            shared: Arc::new(Shared {
+                network,
+                identity,
+                local_addr: listener.local_addr()?,
+                ledger: Mutex::new(ledger),
+                seen: Mutex::default(),
+                peers: Mutex::default(),
+                events: broadcast::channel(EVENT_CAPACITY).0,
+                next_id: AtomicU64::new(0),
+                listener: Mutex::default(),
+            }),
+        };
+
+        let accept = tokio::spawn(node.clone().accept(listener));
+        *node.shared.listener.lock().unwrap() = Some(accept.abort_handle());
+
+        Ok(node)
+    }
+
+    pub fn network(&self) -> Network {
+        self.shared.network
+    }
+
+    /// Key identifying this node to its peers.
+    pub fn key(&self) -> AccountKey {
+        self.shared.identity.account_key()
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.shared.local_addr
+    }
+
+    /// Keys and addresses of the connected peers.
+    pub fn peers(&self) -> Vec<(AccountKey, SocketAddr)> {
+        let peers = self.shared.peers.lock().unwrap();
+
+        peers
+            .iter()
+            .map(|(key, peer)| (key.clone(), peer.addr))
+            .collect()
+    }
+
+    /// Runs `f` on the ledger. The node cannot process messages meanwhile.
+    pub fn with_ledger<T>(&self, f: impl FnOnce(&Ledger) -> T) -> T {
+        f(&self.shared.ledger.lock().unwrap())
+    }
+
+    /// Connects to a peer and returns its key once the handshake succeeds.
+    pub async fn connect(&self, addr: impl ToSocketAddrs) -> Result<AccountKey, NodeError> {
+        let mut stream = TcpStream::connect(addr).await?;
+        let addr = stream.peer_addr()?;
+
+        let session = handshake(&mut stream, self.shared.network, &self.shared.identity).await?;
+        let key = session.peer().clone();
+        self.register(session, addr, stream)?;
+
+        Ok(key)
+    }
+
+    /// Stores a transaction and gossips it to every peer.
+    pub fn submit(&self, tx: SignedTransaction) -> Result<Report, LedgerError> {
+        let report = {
+            let mut ledger = self.shared.ledger.lock().unwrap();
+            let report = ledger.insert(tx.clone())?;
+            self.publish(&ledger, &report);
+            report
+        };
+        self.shared.seen.lock().unwrap().insert(tx.hash());
+        self.broadcast(&Message::Transaction(tx), None);
+
+        Ok(report)
+    }
+
+    /// Fetches every transaction `peer` has that this node lacks, and applies them in topological
+    /// order. Synced transactions are not gossiped, since the other peers most likely have them.
+    ///
+    /// On failure, `progress` keeps what was fetched, and passing it to another call resumes the
+    /// sync, possibly with another peer. Only one sync per peer may run at a time.
+    pub async fn sync(
+        &self,
+        peer: &AccountKey,
+        progress: &mut SyncProgress,
+    ) -> Result<Report, NodeError> {
+        progress.retry();
+
+        let Message::Tips(tips) = self.request(peer, Message::GetTips).await? else {
+            return Err(NodeError::UnexpectedMessage);
+        };
+        self.with_ledger(|ledger| {
+            progress.want(ledger, tips);
+            progress.want(ledger, ledger.dag().missing().copied());
+        });
+
+        let mut report = Report::default();
+        loop {
+            let batch = progress.next_batch(MAX_BATCH);
+            if batch.is_empty() {
+                break;
+            }
+
+            let Message::Batch {
+                transactions,
+                missing,
+            } = self.request(peer, Message::GetBatch(batch)).await?
+            else {
+                return Err(NodeError::UnexpectedMessage);
+            };
+            if transactions.is_empty() && missing.is_empty() {
+                progress.retry();
+                return Err(NodeError::UnexpectedMessage);
+            }
+
+            let applied = {
+                let mut ledger = self.shared.ledger.lock().unwrap();
+                progress.receive(&ledger, transactions, &missing)?;
+                let applied = progress.apply(&mut ledger)?;
+                self.publish(&ledger, &applied);
+                applied
+            };
+
+            let mut seen = self.shared.seen.lock().unwrap();
+            for hash in applied.accepted.iter().chain(&applied.rejected) {
+                seen.insert(*hash);
+            }
+            sync::merge(&mut report, applied);
+        }
+
+        match progress.unavailable().next() {
+            None => Ok(report),
+            Some(_) => {
+                let unavailable = progress.unavailable().copied().collect();
+                Err(SyncError::Unavailable(unavailable).into())
+            }
+        }
+    }
+
+    /// Receives an [`Event`] for every transaction accepted or rejected from now on, in the order
+    /// the ledger changed.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.shared.events.subscribe()
+    }
+
+    /// Stops listening and disconnects from every peer.
+    pub fn shutdown(&self) {
+        if let Some(listener) = self.shared.listener.lock().unwrap().take() {
+            listener.abort();
+        }
+
+        for (_, peer) in self.shared.peers.lock().unwrap().drain() {
+            peer.reader.abort();
+        }
+    }
+
+    async fn accept(self, listener: TcpListener) {
+        while let Ok((stream, addr)) = listener.accept().await {
+            let node = self.clone();
+            tokio::spawn(async move {
+                let mut stream = stream;
+                let network = node.shared.network;
+                if let Ok(session) = handshake(&mut stream, network, &node.shared.identity).await {
+                    let _ = node.register(session, addr, stream);
+                }
+            });
+        }
+    }
+
+    /// Sends a request to a peer and waits for the answer.
+    async fn request(&self, peer: &AccountKey, message: Message) -> Result<Message, NodeError> {
+        let (reply, answer) = oneshot::channel();
+        let outbox = {
+            let mut peers = self.shared.peers.lock().unwrap();
+            let peer = peers.get_mut(peer).ok_or(NodeError::NotConnected)?;
+            peer.reply = Some(reply);
+            peer.outbox.clone()
+        };
+        outbox
+            .send(message)
+            .await
+            .map_err(|_| NodeError::NotConnected)?;
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, answer).await {
+            Ok(Ok(message)) => Ok(message),
+            Ok(Err(_)) => Err(NodeError::NotConnected),
+            Err(_) => Err(NodeError::Timeout),
+        }
+    }
+
+    /// Starts exchanging messages with a peer that passed the handshake.
+    fn register(
+        &self,
+        session: Session,
+        addr: SocketAddr,
+        stream: TcpStream,
+    ) -> Result<(), NodeError> {
+        let (key, mut sealer, opener) = session.into_parts();
+        let mut peers = self.shared.peers.lock().unwrap();
+        if peers.contains_key(&key) {
+            return Err(NodeError::AlreadyConnected);
+        }
+
+        let id = self.shared.next_id.fetch_add(1, Ordering::Relaxed);
+        let (read, mut write) = stream.into_split();
+        let (outbox, mut rx) = mpsc::channel(OUTBOX_CAPACITY);
+
+        tokio::spawn(async move {
+            while let Some(message) = rx.recv().await {
+                if send(&mut write, &mut sealer, &message).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let reader = tokio::spawn(self.clone().read(key.clone(), id, read, opener));
+
+        peers.insert(
+            key,
+            Peer {
+                id,
+                addr,
+                outbox,
+                reader: reader.abort_handle(),
+                reply: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Handles messages from a peer until it disconnects or misbehaves.
+    async fn read(self, key: AccountKey, id: u64, mut read: OwnedReadHalf, mut opener: Opener) {
+        while let Ok(message) = receive(&mut read, &mut opener).await {
+            match message {
+                Message::Transaction(tx) => self.receive(tx, &key).await,
+                Message::GetTransactions(hashes) => {
+                    let found: Vec<_> = self.with_ledger(|ledger| {
+                        hashes
+                            .iter()
+                            .take(MAX_BATCH)
+                            .filter_map(|hash| ledger.get(hash).cloned())
+                            .collect()
+                    });
+                    for tx in found {
+                        self.send(&key, Message::Transaction(tx)).await;
+                    }
+                }
+                Message::GetTips => {
+                    let tips = self.with_ledger(|ledger| ledger.dag().tips().copied().collect());
+                    self.send(&key, Message::Tips(tips)).await;
+                }
+                Message::GetBatch(hashes) => {
+                    let batch = self.batch(&hashes);
+                    self.send(&key, batch).await;
+                }
+                Message::Tips(_) | Message::Batch { .. } => {
+                    let mut peers = self.shared.peers.lock().unwrap();
+                    let reply = peers.get_mut(&key).and_then(|peer| peer.reply.take());
+                    if let Some(reply) = reply {
+                        let _ = reply.send(message);
+                    }
+                }
+                Message::Hello(_) | Message::Auth { .. } => break,
+            }
+        }
+
+        let mut peers = self.shared.peers.lock().unwrap();
+        if peers.get(&key).is_some_and(|peer| peer.id == id) {
+            peers.remove(&key);
+        }
+    }
+
+    /// Answers a [`Message::GetBatch`] with as many of the transactions as fit in a frame.
+    fn batch(&self, hashes: &[TxHash]) -> Message {
+        let mut transactions = Vec::new();
+        let mut missing = Vec::new();
+        let mut size = 0;
+
+        self.with_ledger(|ledger| {
+            for hash in hashes.iter().take(MAX_BATCH) {
+                let Some(tx) = ledger.get(hash) else {
+                    missing.push(*hash);
+                    continue;
+                };
+
+                // Leave room for the missing hashes; what does not fit is asked for again.
+                size += tx.to_bytes().len() + 8;
+                if size > MAX_FRAME_LEN / 2 && !transactions.is_empty() {
+                    break;
+                }
+                transactions.push(tx.clone());
+            }
+        });
+
+        Message::Batch {
+            transactions,
+            missing,
+        }
+    }
+
+    /// Stores a transaction from a peer, gossips it on, and asks the peer for unknown parents.
+    ///
+    /// Only stored transactions are remembered as seen, so one refused for now, such as one
+    /// dated too far ahead of the local clock, is checked again when it comes back.
+    async fn receive(&self, tx: SignedTransaction, from: &AccountKey) {
+        let hash = tx.hash();
+        if self.shared.seen.lock().unwrap().touch(&hash) {
+            return;
+        }
+
+        let missing = {
+            let mut ledger = self.shared.ledger.lock().unwrap();
+            let report = match ledger.insert(tx.clone()) {
+                Ok(report) => report,
+                Err(LedgerError::Dag(DagError::Duplicate(_))) => {
+                    self.shared.seen.lock().unwrap().insert(hash);
+                    return;
+                }
+                Err(_) => return,
+            };
+            self.shared.seen.lock().unwrap().insert(hash);
+            self.publish(&ledger, &report);
+
+            let dag = ledger.dag();
+            let parents: BTreeSet<_> = tx.transaction().parents().iter().copied().collect();
+            parents
+                .into_iter()
+                .filter(|p| !dag.is_known(p) && !dag.is_orphan(p))
+                .collect::<Vec<_>>()
+        };
+
+        if !missing.is_empty() {
+            self.send(from, Message::GetTransactions(missing)).await;
+        }
+        self.broadcast(&Message::Transaction(tx), Some(from));
+    }
+
+    /// Announces the status changes of `report` to the subscribers.
+    fn publish(&self, ledger: &Ledger, report: &Report) {
+        let accepted = report.accepted.iter().filter_map(|hash| ledger.get(hash));
+        for tx in accepted {
+            let _ = self.shared.events.send(Event::Accepted(tx.clone()));
+        }
+
+        let rejected = report.rejected.iter().filter_map(|hash| ledger.get(hash));
+        for tx in rejected {
+            let _ = self.shared.events.send(Event::Rejected(tx.clone()));
+        }
+    }
+
+    /// Sends a message to a peer, waiting while its outbox is full.
+    async fn send(&self, to: &AccountKey, message: Message) {
+        let outbox = self
+            .shared
+            .peers
+            .lock()
+            .unwrap()
+            .get(to)
+            .map(|peer| peer.outbox.clone());
+        if let Some(outbox) = outbox {
+            let _ = outbox.send(message).await;
+        }
+    }
+
+    /// Gossips a message to every peer but `except`, skipping peers whose outbox is full.
+    fn broadcast(&self, message: &Message, except: Option<&AccountKey>) {
+        for (key, peer) in self.shared.peers.lock().unwrap().iter() {
+            if Some(key) != except {
+                let _ = peer.outbox.try_send(message.clone());
+            }
+        }
+    }
+}
+
+/// The [`SEEN_CAPACITY`] transaction hashes used most recently.
+#[derive(Default)]
+struct Seen {
+    /// When each hash was last used.
+    used: HashMap<TxHash, u64>,
+    /// Hashes by when they were last used.
+    order: BTreeMap<u64, TxHash>,
+    clock: u64,
+}
+
+impl Seen {
+    /// Marks `hash` as just used, and returns whether it was there.
+    fn touch(&mut self, hash: &TxHash) -> bool {
+        let Some(used) = self.used.get_mut(hash) else {
+            return false;
+        };
+
+        self.order.remove(used);
+        self.clock += 1;
+        *used = self.clock;
+        self.order.insert(self.clock, *hash);
+
+        true
+    }
+
+    /// Adds `hash`, forgetting the least recently used hash if full.
+    fn insert(&mut self, hash: TxHash) {
+        if self.touch(&hash) {
+            return;
+        }
+
+        if self.used.len() >= SEEN_CAPACITY {
+            if let Some((_, oldest)) = self.order.pop_first() {
+                self.used.remove(&oldest);
+            }
+        }
+
+        self.clock += 1;
+        self.used.insert(hash, self.clock);
+        self.order.insert(self.clock, hash);
+    }
+}
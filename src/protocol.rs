@@ -0,0 +1,255 @@
+//! Messages exchanged between peers.
+//!
+//! Peers talk over TCP in frames of `u32(len) || payload`, at most [`MAX_FRAME_LEN`] bytes each.
+//! The payload is a message in the clear during the handshake, and a sealed message afterwards.
+//! Messages use the conventions of the [wire format](crate::wire):
+//!
+//! ```text
+//! hello            = u8(0) || varint(version) || u8(network) || u8(algorithm)
+//!                    || varint(len(node key)) || node key || nonce[32]
+//!                    || varint(len(kem key)) || kem key
+//! auth             = u8(1) || varint(len(ciphertext)) || ciphertext
+//!                    || varint(len(signature)) || signature
+//! transaction      = u8(2) || varint(len(tx)) || wire transaction
+//! get transactions = u8(3) || varint(len(hashes)) || hash[32]*
+//! get tips         = u8(4)
+//! tips             = u8(5) || varint(len(hashes)) || hash[32]*
+//! get batch        = u8(6) || varint(len(hashes)) || hash[32]*
+//! batch            = u8(7) || varint(len(txs)) || (varint(len(tx)) || wire transaction)*
+//!                    || varint(len(missing)) || hash[32]*
+//! ```
+//!
+//! A connection opens with a handshake: each side sends a hello carrying a fresh KEM public key,
+//! then an auth message with a secret encapsulated to the peer's KEM key and a signature of
+//! [`auth_message`] by its node key. The signature proves the node holds the key and binds it to
+//! both hellos and the ciphertext of this connection. Every later frame is encrypted with the keys
+//! of the [session](crate::transport).
+
+use std::io;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::{
+    address::Network,
+    crypto::{AccountKey, PublicKey, Signature, SignatureAlgorithm, TxHash},
+    transaction::SignedTransaction,
+    wire::{Reader, WireError, Writer},
+};
+
+/// Version of the protocol spoken by this node. Peers must speak the same version.
+pub const PROTOCOL_VERSION: u64 = 2;
+
+/// Largest frame accepted from a peer.
+pub const MAX_FRAME_LEN: usize = 4 << 20;
+
+/// Most transactions asked for in one [`Message::GetBatch`] or [`Message::GetTransactions`].
+pub const MAX_BATCH: usize = 256;
+
+/// Domain separation tag of the handshake signatures.
+pub const HANDSHAKE_TAG: &[u8] = b"ucoin/handshake/v2";
+
+/// First message on a connection, sent by both sides.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u64,
+    pub network: Network,
+    /// Long-term key identifying the node.
+    pub node_key: AccountKey,
+    /// Fresh random value the peer must sign over.
+    pub nonce: [u8; 32],
+    /// Ephemeral ML-KEM public key the peer encapsulates its share of the session keys to.
+    pub kem_key: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Hello(Hello),
+    Auth {
+        /// Secret encapsulated to the peer's [`Hello::kem_key`].
+        ciphertext: Vec<u8>,
+        /// Signature of [`auth_message`] by the sender's node key.
+        signature: Signature,
+    },
+    /// A transaction the sender has stored, or one that was asked for.
+    Transaction(SignedTransaction),
+    /// Asks the peer to send these transactions, typically missing parents. Only the first
+    /// [`MAX_BATCH`] are sent.
+    GetTransactions(Vec<TxHash>),
+    /// Asks the peer for its DAG tips, to start a sync.
+    GetTips,
+    /// Answer to [`Message::GetTips`].
+    Tips(Vec<TxHash>),
+    /// Asks the peer for up to [`MAX_BATCH`] transactions during a sync.
+    GetBatch(Vec<TxHash>),
+    /// Answer to [`Message::GetBatch`]. Requested transactions that are in neither list did not
+    /// fit in the frame and should be asked for again.
+    Batch {
+        transactions: Vec<SignedTransaction>,
+        /// Requested transactions the peer does not have.
+        missing: Vec<TxHash>,
+    },
+}
+
+impl Message {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = Writer::new();
+
+        match self {
+            Self::Hello(hello) => {
+                writer
+                    .u8(0)
+                    .varint(hello.version)
+                    .u8(hello.network.id())
+                    .u8(hello.node_key.algorithm().id())
+                    .bytes(hello.node_key.public_key().as_ref())
+                    .raw(&hello.nonce)
+                    .bytes(&hello.kem_key);
+            }
+            Self::Auth {
+                ciphertext,
+                signature,
+            } => {
+                writer.u8(1).bytes(ciphertext).bytes(signature.as_ref());
+            }
+            Self::Transaction(tx) => {
+                writer.u8(2).bytes(&tx.to_bytes());
+            }
+            Self::GetTransactions(hashes) => {
+                write_hashes(writer.u8(3), hashes);
+            }
+            Self::GetTips => {
+                writer.u8(4);
+            }
+            Self::Tips(hashes) => {
+                write_hashes(writer.u8(5), hashes);
+            }
+            Self::GetBatch(hashes) => {
+                write_hashes(writer.u8(6), hashes);
+            }
+            Self::Batch {
+                transactions,
+                missing,
+            } => {
+                writer.u8(7).varint(transactions.len() as u64);
+                for tx in transactions {
+                    writer.bytes(&tx.to_bytes());
+                }
+                write_hashes(&mut writer, missing);
+            }
+        }
+
+        writer.finish()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut reader = Reader::new(bytes);
+
+        let message = match reader.u8()? {
+            0 => {
+                let version = reader.varint()?;
+                let network = reader.u8()?;
+                let network =
+                    Network::from_id(network).ok_or(WireError::UnknownNetwork(network))?;
+                let algorithm = reader.u8()?;
+                let algorithm = SignatureAlgorithm::from_id(algorithm)
+                    .ok_or(WireError::UnknownAlgorithm(algorithm))?;
+                let node_key = AccountKey::new(algorithm, PublicKey::from_bytes(reader.bytes()?));
+                let nonce = reader.raw(32)?.try_into().unwrap();
+                let kem_key = reader.bytes()?.to_vec();
+
+                Self::Hello(Hello {
+                    version,
+                    network,
+                    node_key,
+                    nonce,
+                    kem_key,
+                })
+            }
+            1 => Self::Auth {
+                ciphertext: reader.bytes()?.to_vec(),
+                signature: Signature::from_bytes(reader.bytes()?),
+            },
+            2 => Self::Transaction(SignedTransaction::from_bytes(reader.bytes()?)?),
+            3 => Self::GetTransactions(read_hashes(&mut reader)?),
+            4 => Self::GetTips,
+            5 => Self::Tips(read_hashes(&mut reader)?),
+            6 => Self::GetBatch(read_hashes(&mut reader)?),
+            7 => {
+                let count = reader.varint()?;
+                let mut transactions = Vec::new();
+                for _ in 0..count {
+                    transactions.push(SignedTransaction::from_bytes(reader.bytes()?)?);
+                }
+
+                Self::Batch {
+                    transactions,
+                    missing: read_hashes(&mut reader)?,
+                }
+            }
+            kind => return Err(WireError::UnknownMessage(kind)),
+        };
+        reader.finish()?;
+
+        Ok(message)
+    }
+}
+
+fn write_hashes(writer: &mut Writer, hashes: &[TxHash]) {
+    writer.varint(hashes.len() as u64);
+    for hash in hashes {
+        writer.raw(hash.as_bytes());
+    }
+}
+
+fn read_hashes(reader: &mut Reader) -> Result<Vec<TxHash>, WireError> {
+    let count = reader.varint()?;
+    let mut hashes = Vec::new();
+    for _ in 0..count {
+        hashes.push(TxHash::from_bytes(reader.raw(32)?.try_into().unwrap()));
+    }
+
+    Ok(hashes)
+}
+
+/// What a node signs during the handshake: its own hello, the peer's, and the ciphertext it sends
+/// the peer.
+pub fn auth_message(own: &Hello, peer: &Hello, ciphertext: &[u8]) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer
+        .raw(HANDSHAKE_TAG)
+        .bytes(&Message::Hello(own.clone()).to_bytes())
+        .bytes(&Message::Hello(peer.clone()).to_bytes())
+        .bytes(ciphertext);
+
+    writer.finish()
+}
+
+/// Reads one frame. Fails with [`io::ErrorKind::InvalidData`] if it is larger than
+/// [`MAX_FRAME_LEN`].
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("frame of {len} bytes"),
+        ));
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame).await?;
+
+    Ok(frame)
+}
+
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, frame: &[u8]) -> io::Result<()> {
+    if frame.len() > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("frame of {} bytes", frame.len()),
+        ));
+    }
+
+    writer.write_u32(frame.len() as u32).await?;
+    writer.write_all(frame).await?;
+    writer.flush().await
+}
@@ -0,0 +1,561 @@
+//! JSON-RPC 2.0 API of a node, served over HTTP.
+//!
+//! The server answers `POST /` with a JSON-RPC request, or a batch of them, in the body. Each
+//! connection carries a single request. Transactions use the JSON representation of
+//! [`SignedTransaction`]; hashes and addresses use their string forms. Parameters are given by
+//! position or by name:
+//!
+//! | method              | params          | result                                                     |
+//! |---------------------|-----------------|------------------------------------------------------------|
+//! | `submitTransaction` | `[transaction]` | `{hash, status}`                                           |
+//! | `getTransaction`    | `[hash]`        | `{transaction, status}`                                    |
+//! | `getBalance`        | `[address]`     | `{address, balance, sequence}`                             |
+//! | `getHistory`        | `[address]`     | accepted transactions sent or received, parents first      |
+//! | `getTips`           | `[]`            | hashes of the DAG tips                                     |
+//! | `getStatus`         | `[]`            | `{network, key, peers, transactions, orphans, tips, root}` |
+//!
+//! Failures are JSON-RPC errors with the standard codes, or the ucoin-specific ones in [`code`].
+//!
+//! `GET /events` subscribes to changes as [server-sent events]. The query string selects them:
+//!
+//! - `sender=<address>` and `receiver=<address>` stream a `transaction` event, holding `{hash,
+//!   transaction}`, for every accepted transaction from or to one of the addresses. Without either,
+//!   every accepted transaction is streamed, unless `wallet` is given.
+//! - `wallet=<address>` streams a `balance` event, holding `{address, balance, sequence}`, with the
+//!   current values on subscribing and then whenever they change.
+//!
+//! Each parameter may be repeated. A subscriber that falls too far behind receives a `lagged`
+//! event and is disconnected, and should subscribe again.
+//!
+//! [server-sent events]: https://html.spec.whatwg.org/multipage/server-sent-events.html
+
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, io,
+    net::SocketAddr,
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    sync::broadcast::error::RecvError,
+    task::AbortHandle,
+};
+
+use crate::{
+    address::{Address, Network},
+    crypto::TxHash,
+    dag::DagError,
+    ledger::LedgerError,
+    node::{Event, Node},
+    transaction::{SignedTransaction, Transaction},
+};
+
+/// Largest request body accepted.
+pub const MAX_BODY_LEN: usize = 4 << 20;
+
+/// Largest request line and headers accepted.
+pub const MAX_HEAD_LEN: usize = 16 << 10;
+
+/// How long a client has to send its request.
+pub const READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often an idle event stream sends a comment, to notice subscribers that went away.
+pub const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Error codes. Those from -32000 down are specific to ucoin.
+pub mod code {
+    pub const PARSE_ERROR: i64 = -32700;
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The transaction signature does not verify, or the transaction breaks a structural rule.
+    pub const INVALID_TRANSACTION: i64 = -32001;
+    /// The node already has the transaction.
+    pub const DUPLICATE_TRANSACTION: i64 = -32002;
+    /// The DAG refused the transaction for another reason.
+    pub const REFUSED_TRANSACTION: i64 = -32003;
+    /// The transaction is not known to the node.
+    pub const NOT_FOUND: i64 = -32004;
+    /// The address or transaction belongs to another network than the node.
+    pub const WRONG_NETWORK: i64 = -32005;
+}
+
+/// Error object of a JSON-RPC response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.message, self.code)
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+impl From<LedgerError> for RpcError {
+    fn from(e: LedgerError) -> Self {
+        let code = match &e {
+            LedgerError::Verify(_) | LedgerError::Invalid(_) => code::INVALID_TRANSACTION,
+            LedgerError::Dag(DagError::Duplicate(_)) => code::DUPLICATE_TRANSACTION,
+            LedgerError::Dag(_) => code::REFUSED_TRANSACTION,
+        };
+
+        Self::new(code, e.to_string())
+    }
+}
+
+/// Handle to a running API server.
+pub struct RpcServer {
+    local_addr: SocketAddr,
+    listener: AbortHandle,
+}
+
+impl RpcServer {
+    /// Serves the API of `node` on `addr`. Must be called from within a Tokio runtime.
+    pub async fn start(node: Node, addr: impl ToSocketAddrs) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr).await?;
+        let local_addr = listener.local_addr()?;
+
+        let accept = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(node.clone(), stream));
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            listener: accept.abort_handle(),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stops accepting connections.
+    pub fn shutdown(&self) {
+        self.listener.abort();
+    }
+}
+
+/// An HTTP request.
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: Vec<u8>,
+}
+
+/// Answers the single request of a connection.
+async fn serve(node: Node, mut stream: TcpStream) -> io::Result<()> {
+    let (read, mut write) = stream.split();
+    let mut reader = BufReader::new(read);
+
+    let request = match tokio::time::timeout(READ_TIMEOUT, read_request(&mut reader)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(e)) if e.kind() == io::ErrorKind::InvalidData => {
+            return respond(&mut write, "400 Bad Request", b"").await;
+        }
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return respond(&mut write, "408 Request Timeout", b"").await,
+    };
+
+    match (request.method.as_str(), request.path.as_str()) {
+        ("POST", "/") => match handle(&node, &request.body) {
+            Some(response) => respond(&mut write, "200 OK", response.to_string().as_bytes()).await,
+            None => respond(&mut write, "204 No Content", b"").await,
+        },
+        ("GET", "/events") => stream_events(&node, &request.query, &mut write).await,
+        (_, "/" | "/events") => respond(&mut write, "405 Method Not Allowed", b"").await,
+        _ => respond(&mut write, "404 Not Found", b"").await,
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads an HTTP/1.1 request. Fails with [`io::ErrorKind::InvalidData`] if it is malformed or
+/// too large.
+async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> io::Result<Request> {
+    let mut head = String::new();
+    let mut limited = (&mut *reader).take(MAX_HEAD_LEN as u64);
+    while !head.ends_with("\r\n\r\n") && !head.ends_with("\n\n") {
+        if limited.read_line(&mut head).await? == 0 {
+            return Err(invalid_data("incomplete request head"));
+        }
+    }
+
+    let mut lines = head.lines();
+    let mut request_line = lines.next().unwrap_or_default().split_whitespace();
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(invalid_data("malformed request line"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    let mut len = 0;
+    for line in lines {
+        let Some((name, value)) = line.split_once(':') else {
+            continue;
+        };
+        if name.trim().eq_ignore_ascii_case("content-length") {
+            len = value
+                .trim()
+                .parse()
+                .map_err(|_| invalid_data("malformed content length"))?;
+        }
+    }
+    if len > MAX_BODY_LEN {
+        return Err(invalid_data("request body too large"));
+    }
+
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body).await?;
+
+    Ok(Request {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        query: query.to_owned(),
+        body,
+    })
+}
+
+async fn respond<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    status: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    let mut head = format!("HTTP/1.1 {status}\r\nConnection: close\r\n");
+    if !body.is_empty() {
+        head.push_str("Content-Type: application/json\r\n");
+    }
+    head.push_str(&format!("Content-Length: {}\r\n\r\n", body.len()));
+
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(body).await?;
+    writer.flush().await
+}
+
+/// What an event stream carries, from the query string of `GET /events`.
+#[derive(Debug, Default)]
+struct Filter {
+    senders: HashSet<Address>,
+    receivers: HashSet<Address>,
+    wallets: HashSet<Address>,
+}
+
+impl Filter {
+    fn parse(query: &str, network: Network) -> Result<Self, String> {
+        let mut filter = Self::default();
+
+        for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let addresses = match name {
+                "sender" => &mut filter.senders,
+                "receiver" => &mut filter.receivers,
+                "wallet" => &mut filter.wallets,
+                _ => return Err(format!("unknown parameter {name}")),
+            };
+
+            let address: Address = value.parse().map_err(|e| format!("bad {name}: {e}"))?;
+            if address.network() != network {
+                return Err(format!("{address} is not a {network} address"));
+            }
+            addresses.insert(address);
+        }
+
+        Ok(filter)
+    }
+
+    /// Whether an accepted transaction is streamed.
+    fn matches(&self, tx: &Transaction) -> bool {
+        if self.senders.is_empty() && self.receivers.is_empty() {
+            return self.wallets.is_empty();
+        }
+
+        self.senders.contains(&tx.sender_address()) || self.receivers.contains(tx.receiver())
+    }
+}
+
+/// State of one event stream.
+struct Subscription {
+    filter: Filter,
+    /// Balance and sequence number last sent for each wallet.
+    balances: HashMap<Address, (u64, u64)>,
+}
+
+impl Subscription {
+    /// Server-sent events announcing a change to the ledger.
+    fn events(&mut self, node: &Node, event: &Event) -> Vec<String> {
+        let (tx, accepted) = match event {
+            Event::Accepted(tx) => (tx, true),
+            Event::Rejected(tx) => (tx, false),
+        };
+        let transaction = tx.transaction();
+
+        let mut events = Vec::new();
+        if accepted && self.filter.matches(transaction) {
+            let data = json!({ "hash": tx.hash(), "transaction": tx });
+            events.push(server_event("transaction", &data));
+        }
+        events.extend(self.balances(
+            node,
+            &[transaction.sender_address(), *transaction.receiver()],
+        ));
+
+        events
+    }
+
+    /// Balance events of the subscribed wallets among `wallets` whose balance or sequence number
+    /// changed since last sent.
+    fn balances(&mut self, node: &Node, wallets: &[Address]) -> Vec<String> {
+        let wallets: Vec<_> = wallets
+            .iter()
+            .filter(|address| self.filter.wallets.contains(address))
+            .collect();
+        if wallets.is_empty() {
+            return Vec::new();
+        }
+
+        node.with_ledger(|ledger| {
+            let state = ledger.state();
+            wallets
+                .into_iter()
+                .filter_map(|address| {
+                    let current = (state.balance(address), state.sequence(address));
+                    if self.balances.insert(*address, current) == Some(current) {
+                        return None;
+                    }
+
+                    let data = json!({
+                        "address": address,
+                        "balance": current.0,
+                        "sequence": current.1,
+                    });
+                    Some(server_event("balance", &data))
+                })
+                .collect()
+        })
+    }
+}
+
+fn server_event(name: &str, data: &Value) -> String {
+    format!("event: {name}\ndata: {data}\n\n")
+}
+
+/// Streams the events selected by `query` until the subscriber goes away.
+async fn stream_events<W: AsyncWrite + Unpin>(
+    node: &Node,
+    query: &str,
+    writer: &mut W,
+) -> io::Result<()> {
+    let filter = match Filter::parse(query, node.network()) {
+        Ok(filter) => filter,
+        Err(e) => {
+            let body = json!({ "error": e }).to_string();
+            return respond(writer, "400 Bad Request", body.as_bytes()).await;
+        }
+    };
+
+    // Subscribe before reading the balances, so that no change goes unnoticed.
+    let mut events = node.subscribe();
+    let wallets: Vec<_> = filter.wallets.iter().copied().collect();
+    let mut subscription = Subscription {
+        filter,
+        balances: HashMap::new(),
+    };
+
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\n\
+                Connection: close\r\n\r\n";
+    writer.write_all(head.as_bytes()).await?;
+    let mut messages = subscription.balances(node, &wallets);
+
+    loop {
+        for message in messages {
+            writer.write_all(message.as_bytes()).await?;
+        }
+        writer.flush().await?;
+
+        messages = match tokio::time::timeout(KEEPALIVE_INTERVAL, events.recv()).await {
+            Ok(Ok(event)) => subscription.events(node, &event),
+            Ok(Err(RecvError::Lagged(missed))) => {
+                let lagged = server_event("lagged", &json!({ "missed": missed }));
+                writer.write_all(lagged.as_bytes()).await?;
+                return writer.flush().await;
+            }
+            Ok(Err(RecvError::Closed)) => return Ok(()),
+            Err(_) => vec![": keepalive\n\n".to_owned()],
+        };
+    }
+}
+
+/// Answers the JSON-RPC request or batch in `body`. Returns `None` if it only holds
+/// notifications, which get no response.
+pub fn handle(node: &Node, body: &[u8]) -> Option<Value> {
+    let request: Value = match serde_json::from_slice(body) {
+        Ok(request) => request,
+        Err(e) => {
+            let error = RpcError::new(code::PARSE_ERROR, e.to_string());
+            return Some(response(Value::Null, Err(error)));
+        }
+    };
+
+    match request {
+        Value::Array(batch) if !batch.is_empty() => {
+            let responses: Vec<_> = batch.into_iter().filter_map(|r| call(node, r)).collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => call(node, request),
+    }
+}
+
+/// Runs a single request. Returns `None` for a notification.
+fn call(node: &Node, request: Value) -> Option<Value> {
+    let Value::Object(mut request) = request else {
+        let error = RpcError::new(code::INVALID_REQUEST, "request is not an object");
+        return Some(response(Value::Null, Err(error)));
+    };
+
+    let id = request.remove("id");
+    let version = request.remove("jsonrpc");
+    let method = match (
+        version.as_ref().and_then(Value::as_str),
+        request.remove("method"),
+    ) {
+        (Some("2.0"), Some(Value::String(method))) => method,
+        _ => {
+            let error = RpcError::new(code::INVALID_REQUEST, "not a JSON-RPC 2.0 request");
+            return Some(response(id.unwrap_or_default(), Err(error)));
+        }
+    };
+    let params = request.remove("params").unwrap_or_default();
+
+    let result = dispatch(node, &method, &params);
+
+    id.map(|id| response(id, result))
+}
+
+fn response(id: Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+        Err(error) => json!({ "jsonrpc": "2.0", "error": error, "id": id }),
+    }
+}
+
+/// Parameter `index` of a call by position, or `name` of a call by name.
+fn param<T: DeserializeOwned>(params: &Value, index: usize, name: &str) -> Result<T, RpcError> {
+    let value = match params {
+        Value::Array(params) => params.get(index),
+        Value::Object(params) => params.get(name),
+        _ => None,
+    };
+    let value = value
+        .ok_or_else(|| RpcError::new(code::INVALID_PARAMS, format!("missing parameter {name}")))?;
+
+    T::deserialize(value)
+        .map_err(|e| RpcError::new(code::INVALID_PARAMS, format!("bad parameter {name}: {e}")))
+}
+
+fn dispatch(node: &Node, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "submitTransaction" => submit_transaction(node, param(params, 0, "transaction")?),
+        "getTransaction" => get_transaction(node, &param(params, 0, "hash")?),
+        "getBalance" => get_balance(node, &param(params, 0, "address")?),
+        "getHistory" => get_history(node, &param(params, 0, "address")?),
+        "getTips" => Ok(node.with_ledger(|ledger| json!(ledger.dag().tips().collect::<Vec<_>>()))),
+        "getStatus" => Ok(get_status(node)),
+        _ => Err(RpcError::new(
+            code::METHOD_NOT_FOUND,
+            format!("unknown method {method}"),
+        )),
+    }
+}
+
+fn check_network(node: &Node, address: &Address) -> Result<(), RpcError> {
+    match address.network() == node.network() {
+        true => Ok(()),
+        false => Err(RpcError::new(
+            code::WRONG_NETWORK,
+            format!("{address} is not a {} address", node.network()),
+        )),
+    }
+}
+
+fn submit_transaction(node: &Node, tx: SignedTransaction) -> Result<Value, RpcError> {
+    let network = tx.transaction().network();
+    if network != node.network() {
+        return Err(RpcError::new(
+            code::WRONG_NETWORK,
+            format!("transaction is on {network}"),
+        ));
+    }
+
+    let hash = tx.hash();
+    node.submit(tx)?;
+    let status = node.with_ledger(|ledger| ledger.status(&hash));
+
+    Ok(json!({ "hash": hash, "status": status }))
+}
+
+fn get_transaction(node: &Node, hash: &TxHash) -> Result<Value, RpcError> {
+    node.with_ledger(|ledger| {
+        let tx = ledger
+            .get(hash)
+            .or_else(|| ledger.dag().get_orphan(hash))
+            .ok_or_else(|| RpcError::new(code::NOT_FOUND, format!("unknown transaction {hash}")))?;
+
+        Ok(json!({ "transaction": tx, "status": ledger.status(hash) }))
+    })
+}
+
+fn get_balance(node: &Node, address: &Address) -> Result<Value, RpcError> {
+    check_network(node, address)?;
+
+    Ok(node.with_ledger(|ledger| {
+        let state = ledger.state();
+        json!({
+            "address": address,
+            "balance": state.balance(address),
+            "sequence": state.sequence(address),
+        })
+    }))
+}
+
+fn get_history(node: &Node, address: &Address) -> Result<Value, RpcError> {
+    check_network(node, address)?;
+
+    Ok(json!(node.with_ledger(|ledger| ledger.history(address))))
+}
+
+fn get_status(node: &Node) -> Value {
+    let peers = node.peers().len();
+
+    node.with_ledger(|ledger| {
+        let dag = ledger.dag();
+        json!({
+            "network": node.network(),
+            "key": node.key(),
+            "peers": peers,
+            "transactions": dag.len(),
+            "orphans": dag.orphans().count(),
+            "tips": dag.tips().count(),
+            "root": ledger.state().root(),
+        })
+    })
+}
@@ -0,0 +1,93 @@
+//! Seed phrases and deterministic key derivation.
+//!
+//! A wallet is backed up as a BIP-39 mnemonic. The mnemonic and an optional passphrase are
+//! stretched into a 64-byte [`Seed`] exactly as in BIP-39, and every account keypair is derived
+//! from the seed, the account's algorithm and an index, so the phrase restores all accounts.
+//!
+//! ```text
+//! key seed = BLAKE3-derive_key("ucoin/keygen/v1", seed || u8(algorithm) || u32(index))
+//! keypair  = SignatureAlgorithm::keypair_from_seed(key seed)
+//! ```
+
+use std::fmt;
+
+pub use bip39::{Error as MnemonicError, Mnemonic};
+use rand::RngCore;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::{
+    crypto::{PublicKey, SecretKey, SignatureAlgorithm},
+    encoding::Encoder,
+    keystore::Keystore,
+};
+
+/// BLAKE3 key derivation context of account key seeds.
+pub const KEYGEN_CONTEXT: &str = "ucoin/keygen/v1";
+
+/// Generates a random English mnemonic of 12, 15, 18, 21 or 24 words.
+pub fn generate_mnemonic(words: usize) -> Result<Mnemonic, MnemonicError> {
+    let mut entropy = Zeroizing::new(vec![0; words / 3 * 4]);
+    rand::rngs::OsRng.fill_bytes(&mut entropy);
+
+    let mnemonic = Mnemonic::from_entropy(&entropy)?;
+    if mnemonic.word_count() != words {
+        return Err(MnemonicError::BadWordCount(words));
+    }
+
+    Ok(mnemonic)
+}
+
+/// The master secret of a wallet. Its bytes are never printed and are wiped when it is dropped.
+pub struct Seed([u8; 64]);
+
+impl Seed {
+    pub fn from_mnemonic(mnemonic: &Mnemonic, passphrase: &str) -> Self {
+        Self(mnemonic.to_seed(passphrase))
+    }
+
+    /// Parses and checks a space-separated English phrase.
+    pub fn from_phrase(phrase: &str, passphrase: &str) -> Result<Self, MnemonicError> {
+        Ok(Self::from_mnemonic(&Mnemonic::parse(phrase)?, passphrase))
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 64] {
+        &self.0
+    }
+
+    /// The key seed of account `index` of `algorithm`.
+    pub fn key_seed(&self, algorithm: SignatureAlgorithm, index: u32) -> Zeroizing<[u8; 32]> {
+        let mut material = Encoder::new();
+        material.raw(&self.0).u8(algorithm.id()).u32(index);
+        let material = Zeroizing::new(material.finish());
+
+        Zeroizing::new(blake3::derive_key(KEYGEN_CONTEXT, &material))
+    }
+
+    /// Derives the keypair of account `index` of `algorithm`.
+    pub fn keypair(
+        &self,
+        algorithm: SignatureAlgorithm,
+        index: u32,
+    ) -> oqs::Result<(PublicKey, SecretKey)> {
+        algorithm.keypair_from_seed(&self.key_seed(algorithm, index))
+    }
+
+    /// Derives account `index` of `algorithm`, ready to be saved.
+    pub fn keystore(&self, algorithm: SignatureAlgorithm, index: u32) -> oqs::Result<Keystore> {
+        let (public_key, secret_key) = self.keypair(algorithm, index)?;
+
+        Ok(Keystore::new(algorithm, public_key, secret_key))
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Seed(..)")
+    }
+}
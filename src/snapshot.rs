@@ -0,0 +1,98 @@
+//! Snapshots of the world state at a cut of the DAG.
+//!
+//! A cut is a set of transactions standing for themselves and all of their ancestors. A snapshot
+//! holds the world state those transactions produce and its Merkle root, so a node can start
+//! from it instead of replaying the whole DAG, and two nodes can compare roots to check that they
+//! agree on every balance.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::{Digest, TxHash},
+    ledger::LedgerError,
+    state::WorldState,
+};
+
+/// Reasons a snapshot cannot be taken or imported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// A transaction of the cut is not in the DAG.
+    UnknownTransaction(TxHash),
+    /// A transaction of the cut is not accepted, so the state cannot include it.
+    RejectedTransaction(TxHash),
+    /// The state does not match the root.
+    RootMismatch,
+    /// Replaying the transactions below the cut failed.
+    Ledger(LedgerError),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownTransaction(hash) => write!(f, "unknown transaction {hash}"),
+            Self::RejectedTransaction(hash) => write!(f, "transaction {hash} is not accepted"),
+            Self::RootMismatch => write!(f, "state does not match the root"),
+            Self::Ledger(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Ledger(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LedgerError> for SnapshotError {
+    fn from(e: LedgerError) -> Self {
+        Self::Ledger(e)
+    }
+}
+
+/// The world state at a cut of the DAG, committed to by its Merkle root.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    cut: Vec<TxHash>,
+    root: Digest,
+    state: WorldState,
+}
+
+impl Snapshot {
+    pub fn new(cut: impl IntoIterator<Item = TxHash>, state: WorldState) -> Self {
+        let mut cut: Vec<_> = cut.into_iter().collect();
+        cut.sort();
+        cut.dedup();
+
+        Self {
+            cut,
+            root: state.root(),
+            state,
+        }
+    }
+
+    /// Transactions of the cut, in hash order.
+    pub fn cut(&self) -> &[TxHash] {
+        &self.cut
+    }
+
+    pub fn root(&self) -> &Digest {
+        &self.root
+    }
+
+    pub fn state(&self) -> &WorldState {
+        &self.state
+    }
+
+    /// Checks that the state matches the root, e.g. after importing a snapshot from a file.
+    pub fn verify(&self) -> Result<(), SnapshotError> {
+        match self.state.root() == self.root {
+            true => Ok(()),
+            false => Err(SnapshotError::RootMismatch),
+        }
+    }
+}
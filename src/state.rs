@@ -0,0 +1,305 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::{Address, Network},
+    crypto::{Digest, TxHash},
+    merkle::{self, WalletProof},
+    transaction::{SignedTransaction, VerifyError},
+};
+
+/// Reasons a transaction cannot be applied to or reverted from a [`WorldState`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StateError {
+    /// The transaction signature is invalid.
+    Verify(VerifyError),
+    /// The transaction or its receiver belongs to another network.
+    WrongNetwork(Network),
+    /// The sequence number is not the sender's next one: a replay, or a gap in its payments.
+    BadSequence { expected: u64, actual: u64 },
+    /// The sender cannot cover the amount.
+    InsufficientBalance { available: u64, required: u64 },
+    /// A balance would exceed `u64::MAX`.
+    Overflow,
+    /// The transaction has already been applied.
+    AlreadyApplied(TxHash),
+    /// The transaction cannot be reverted because it was never applied.
+    NotApplied(TxHash),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Verify(e) => write!(f, "invalid transaction: {e}"),
+            Self::WrongNetwork(network) => write!(f, "transaction is for {network}"),
+            Self::BadSequence { expected, actual } => {
+                write!(f, "bad sequence: expected {expected}, got {actual}")
+            }
+            Self::InsufficientBalance {
+                available,
+                required,
+            } => write!(
+                f,
+                "insufficient balance: {available} available, {required} required"
+            ),
+            Self::Overflow => write!(f, "balance overflow"),
+            Self::AlreadyApplied(hash) => write!(f, "transaction {hash} already applied"),
+            Self::NotApplied(hash) => write!(f, "transaction {hash} was not applied"),
+        }
+    }
+}
+
+impl std::error::Error for StateError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Verify(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<VerifyError> for StateError {
+    fn from(e: VerifyError) -> Self {
+        Self::Verify(e)
+    }
+}
+
+/// Balance and transaction history of a single account.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Wallet {
+    balance: u64,
+    sequence: u64,
+    history: HashSet<TxHash>,
+}
+
+impl Wallet {
+    pub fn balance(&self) -> u64 {
+        self.balance
+    }
+
+    /// Sequence number expected on the next transaction sent from this wallet.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Hashes of every transaction that touched this wallet.
+    pub fn history(&self) -> &HashSet<TxHash> {
+        &self.history
+    }
+
+    fn is_empty(&self) -> bool {
+        self.balance == 0 && self.sequence == 0 && self.history.is_empty()
+    }
+}
+
+/// The set of all wallets on a network, keyed by address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorldState {
+    network: Network,
+    wallets: HashMap<Address, Wallet>,
+}
+
+impl WorldState {
+    pub fn new(network: Network) -> Self {
+        Self {
+            network,
+            wallets: HashMap::new(),
+        }
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn wallet(&self, address: &Address) -> Option<&Wallet> {
+        self.wallets.get(address)
+    }
+
+    pub fn wallets(&self) -> impl Iterator<Item = (&Address, &Wallet)> {
+        self.wallets.iter()
+    }
+
+    pub fn balance(&self, address: &Address) -> u64 {
+        self.wallet(address).map_or(0, Wallet::balance)
+    }
+
+    /// Sequence number expected on the next transaction sent from `address`.
+    pub fn sequence(&self, address: &Address) -> u64 {
+        self.wallet(address).map_or(0, Wallet::sequence)
+    }
+
+    /// Merkle root committing to the balance and sequence number of every wallet, as described in
+    /// [`crate::merkle`].
+    pub fn root(&self) -> Digest {
+        let (_, leaves) = self.leaves();
+
+        merkle::root(&leaves)
+    }
+
+    /// Proof of the balance of `address` against [`WorldState::root`], if it has a wallet.
+    pub fn prove(&self, address: &Address) -> Option<WalletProof> {
+        let wallet = self.wallet(address)?;
+        let (addresses, leaves) = self.leaves();
+        let index = addresses
+            .binary_search_by_key(&address.to_bytes(), |a| a.to_bytes())
+            .ok()?;
+
+        Some(WalletProof {
+            address: *address,
+            balance: wallet.balance,
+            sequence: wallet.sequence,
+            index: index as u64,
+            size: leaves.len() as u64,
+            path: merkle::path(&leaves, index),
+        })
+    }
+
+    /// Addresses sorted by their binary form, and the Merkle leaf of each.
+    fn leaves(&self) -> (Vec<&Address>, Vec<Digest>) {
+        let mut wallets: Vec<_> = self.wallets.iter().collect();
+        wallets.sort_by_key(|(address, _)| address.to_bytes());
+
+        wallets
+            .into_iter()
+            .map(|(address, w)| (address, merkle::leaf(address, w.balance, w.sequence)))
+            .unzip()
+    }
+
+    /// Mints `amount` into a wallet, e.g. for genesis allocations.
+    pub fn credit(&mut self, address: Address, amount: u64) -> Result<(), StateError> {
+        let wallet = self.wallets.entry(address).or_default();
+        wallet.balance = wallet
+            .balance
+            .checked_add(amount)
+            .ok_or(StateError::Overflow)?;
+
+        Ok(())
+    }
+
+    /// Verifies a transaction and moves its amount from the sender to the receiver.
+    ///
+    /// The state is left untouched if any check fails.
+    pub fn apply(&mut self, tx: &SignedTransaction) -> Result<(), StateError> {
+        tx.verify()?;
+
+        self.transfer(tx)
+    }
+
+    /// [`WorldState::apply`] for a transaction whose signature is already verified.
+    pub(crate) fn transfer(&mut self, tx: &SignedTransaction) -> Result<(), StateError> {
+        let hash = tx.hash();
+        let transaction = tx.transaction();
+        let amount = transaction.amount();
+
+        if transaction.network() != self.network {
+            return Err(StateError::WrongNetwork(transaction.network()));
+        }
+
+        let receiver = *transaction.receiver();
+        if receiver.network() != self.network {
+            return Err(StateError::WrongNetwork(receiver.network()));
+        }
+
+        let sender = transaction.sender_address();
+        let sender_wallet = self.wallets.get(&sender);
+        if sender_wallet.is_some_and(|w| w.history.contains(&hash)) {
+            return Err(StateError::AlreadyApplied(hash));
+        }
+
+        let expected = sender_wallet.map_or(0, Wallet::sequence);
+        if transaction.sequence() != expected {
+            return Err(StateError::BadSequence {
+                expected,
+                actual: transaction.sequence(),
+            });
+        }
+
+        let available = sender_wallet.map_or(0, Wallet::balance);
+        if available < amount {
+            return Err(StateError::InsufficientBalance {
+                available,
+                required: amount,
+            });
+        }
+
+        if sender != receiver {
+            self.balance(&receiver)
+                .checked_add(amount)
+                .ok_or(StateError::Overflow)?;
+        }
+
+        let sender_wallet = self.wallets.entry(sender).or_default();
+        sender_wallet.balance -= amount;
+        sender_wallet.sequence += 1;
+        sender_wallet.history.insert(hash);
+
+        let receiver_wallet = self.wallets.entry(receiver).or_default();
+        receiver_wallet.balance += amount;
+        receiver_wallet.history.insert(hash);
+
+        Ok(())
+    }
+
+    /// Undoes a previously applied transaction, e.g. when its DAG branch is discarded.
+    ///
+    /// Transactions must be reverted in the reverse order they were applied, otherwise the
+    /// receiver may no longer hold the amount being returned.
+    pub fn revert(&mut self, tx: &SignedTransaction) -> Result<(), StateError> {
+        let hash = tx.hash();
+        let transaction = tx.transaction();
+        let amount = transaction.amount();
+
+        let receiver = *transaction.receiver();
+        let sender = transaction.sender_address();
+        if !self
+            .wallets
+            .get(&sender)
+            .is_some_and(|w| w.history.contains(&hash))
+        {
+            return Err(StateError::NotApplied(hash));
+        }
+
+        let expected = self.sequence(&sender) - 1;
+        if transaction.sequence() != expected {
+            return Err(StateError::BadSequence {
+                expected,
+                actual: transaction.sequence(),
+            });
+        }
+
+        if sender != receiver {
+            let available = self.balance(&receiver);
+            if available < amount {
+                return Err(StateError::InsufficientBalance {
+                    available,
+                    required: amount,
+                });
+            }
+
+            self.balance(&sender)
+                .checked_add(amount)
+                .ok_or(StateError::Overflow)?;
+        }
+
+        let receiver_wallet = self.wallets.get_mut(&receiver).unwrap();
+        receiver_wallet.balance -= amount;
+        receiver_wallet.history.remove(&hash);
+
+        let sender_wallet = self.wallets.get_mut(&sender).unwrap();
+        sender_wallet.balance += amount;
+        sender_wallet.sequence -= 1;
+        sender_wallet.history.remove(&hash);
+
+        for address in [sender, receiver] {
+            if self.wallets.get(&address).is_some_and(Wallet::is_empty) {
+                self.wallets.remove(&address);
+            }
+        }
+
+        Ok(())
+    }
+}
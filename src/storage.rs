@@ -0,0 +1,417 @@
+//! On-disk storage of the ledger.
+//!
+//! A store is a directory with four files:
+//!
+//! - `genesis.json`, the [`WorldState`] the ledger starts from;
+//! - `transactions.log`, the write-ahead log: every stored transaction in insertion order, as
+//!   `u32(len) || wire bytes || BLAKE3(wire bytes)`;
+//! - `transactions.idx`, one `hash[32] || u64(offset)` entry per log record, to look up
+//!   transactions by hash without reading the log;
+//! - `checkpoint.json`, the DAG tips and wallets after the first `records` transactions.
+//!
+//! Transactions are appended to the log and synced before anything else refers to them, so the log
+//! is the source of truth: the DAG edges are the parents of its transactions, and the ledger is
+//! rebuilt by replaying it on open. The replay trusts what was checked before the transactions were
+//! stored: records up to the checkpoint are not verified again, later ones only have their
+//! signatures verified, and none is held to the current [`Rules`](crate::validate::Rules), so a
+//! store stays readable when the rules get stricter. A crash can leave a torn record at the end of
+//! the log or an index missing its last entries; both are repaired when the store is opened. Damage
+//! anywhere else, or a replay that does not reproduce the checkpoint, is reported as an error.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::TxHash,
+    ledger::{Ledger, LedgerError},
+    state::WorldState,
+    transaction::SignedTransaction,
+};
+
+const GENESIS: &str = "genesis.json";
+const LOG: &str = "transactions.log";
+const INDEX: &str = "transactions.idx";
+const CHECKPOINT: &str = "checkpoint.json";
+
+const CHECKSUM_LEN: usize = 32;
+const INDEX_ENTRY_LEN: u64 = 40;
+
+/// Reasons a store cannot be opened, read or written.
+#[derive(Debug)]
+pub enum StorageError {
+    Io(io::Error),
+    /// The genesis or checkpoint file cannot be parsed.
+    Malformed(serde_json::Error),
+    /// The log record at this offset fails its checksum or does not decode.
+    Corrupt {
+        offset: u64,
+    },
+    /// The index refers to records that are not in the log.
+    BadIndex,
+    /// Replaying the log does not reproduce the checkpoint.
+    CheckpointMismatch,
+    /// The ledger refused a transaction from the log.
+    Ledger(LedgerError),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "{e}"),
+            Self::Malformed(e) => write!(f, "malformed store file: {e}"),
+            Self::Corrupt { offset } => write!(f, "corrupt log record at offset {offset}"),
+            Self::BadIndex => write!(f, "index does not match the log"),
+            Self::CheckpointMismatch => write!(f, "log does not reproduce the checkpoint"),
+            Self::Ledger(e) => write!(f, "stored transaction refused: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Malformed(e) => Some(e),
+            Self::Ledger(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for StorageError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for StorageError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Malformed(e)
+    }
+}
+
+impl From<LedgerError> for StorageError {
+    fn from(e: LedgerError) -> Self {
+        Self::Ledger(e)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Checkpoint {
+    /// Number of log records the checkpoint covers.
+    records: u64,
+    tips: Vec<TxHash>,
+    state: WorldState,
+}
+
+/// A ledger persisted in a directory.
+#[derive(Debug)]
+pub struct Store {
+    dir: PathBuf,
+    log: File,
+    index_file: File,
+    /// Hash and log offset of every record, in log order.
+    index: Vec<(TxHash, u64)>,
+    positions: HashMap<TxHash, usize>,
+    log_len: u64,
+}
+
+impl Store {
+    /// Opens the store in `dir`, creating it with `genesis` if it does not exist yet.
+    ///
+    /// An existing store keeps its own genesis state.
+    pub fn open(dir: impl AsRef<Path>, genesis: &WorldState) -> Result<Self, StorageError> {
+        let dir = dir.as_ref().to_owned();
+        fs::create_dir_all(&dir)?;
+        if !dir.join(GENESIS).exists() {
+            write_atomic(&dir, GENESIS, &serde_json::to_vec_pretty(genesis)?)?;
+        }
+
+        let open = |name| {
+            fs::OpenOptions::new()
+                .read(true)
+                .append(true)
+                .create(true)
+                .open(dir.join(name))
+        };
+        let mut store = Self {
+            log: open(LOG)?,
+            index_file: open(INDEX)?,
+            dir,
+            index: Vec::new(),
+            positions: HashMap::new(),
+            log_len: 0,
+        };
+        store.recover()?;
+
+        Ok(store)
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// The state the ledger starts from.
+    pub fn genesis(&self) -> Result<WorldState, StorageError> {
+        Ok(serde_json::from_slice(&fs::read(self.dir.join(GENESIS))?)?)
+    }
+
+    /// Number of stored transactions.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn contains(&self, hash: &TxHash) -> bool {
+        self.positions.contains_key(hash)
+    }
+
+    /// Hashes of the stored transactions, in the order they were appended.
+    pub fn hashes(&self) -> impl Iterator<Item = &TxHash> {
+        self.index.iter().map(|(hash, _)| hash)
+    }
+
+    /// Reads a transaction, checking it against its checksum and hash.
+    pub fn get(&self, hash: &TxHash) -> Result<Option<SignedTransaction>, StorageError> {
+        let Some(&position) = self.positions.get(hash) else {
+            return Ok(None);
+        };
+        let (_, offset) = self.index[position];
+
+        let tx = self.read(offset)?.ok_or(StorageError::BadIndex)?;
+        if tx.hash() != *hash {
+            return Err(StorageError::BadIndex);
+        }
+
+        Ok(Some(tx))
+    }
+
+    /// Appends a transaction to the log and syncs it to disk. Returns `false` if it was already
+    /// stored.
+    ///
+    /// Only transactions the ledger accepted into its DAG should be appended, or replaying the
+    /// log fails.
+    pub fn append(&mut self, tx: &SignedTransaction) -> Result<bool, StorageError> {
+        let hash = tx.hash();
+        if self.contains(&hash) {
+            return Ok(false);
+        }
+
+        let bytes = tx.to_bytes();
+        let mut record = Vec::with_capacity(4 + bytes.len() + CHECKSUM_LEN);
+        record.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+        record.extend_from_slice(&bytes);
+        record.extend_from_slice(blake3::hash(&bytes).as_bytes());
+
+        let offset = self.log_len;
+        self.log.write_all(&record)?;
+        self.log.sync_data()?;
+        self.log_len += record.len() as u64;
+
+        self.write_index_entry(&hash, offset)?;
+        self.index_file.sync_data()?;
+
+        Ok(true)
+    }
+
+    /// Rebuilds the ledger by replaying the log on top of the genesis state, checking the result
+    /// against the checkpoint. Signatures are only verified past the checkpoint.
+    pub fn load(&self) -> Result<Ledger, StorageError> {
+        let checkpoint = self.checkpoint_file()?;
+        let check = |ledger: &Ledger, replayed: usize| match &checkpoint {
+            Some(checkpoint) if checkpoint.records == replayed as u64 => {
+                self.check(ledger, checkpoint)
+            }
+            _ => Ok(()),
+        };
+
+        let checked = checkpoint.as_ref().map_or(0, |c| c.records as usize);
+
+        let mut ledger = Ledger::new(self.genesis()?);
+        check(&ledger, 0)?;
+        for (i, &(hash, offset)) in self.index.iter().enumerate() {
+            let tx = self.read(offset)?.ok_or(StorageError::BadIndex)?;
+            if tx.hash() != hash {
+                return Err(StorageError::BadIndex);
+            }
+
+            if i >= checked {
+                tx.verify().map_err(LedgerError::Verify)?;
+            }
+            ledger.restore(tx)?;
+            check(&ledger, i + 1)?;
+        }
+
+        Ok(ledger)
+    }
+
+    /// Records the tips and wallets of `ledger`, which must have been loaded from this store and
+    /// hold exactly its transactions.
+    pub fn checkpoint(&self, ledger: &Ledger) -> Result<(), StorageError> {
+        let checkpoint = Checkpoint {
+            records: self.index.len() as u64,
+            tips: ledger.dag().tips().copied().collect(),
+            state: ledger.state().clone(),
+        };
+
+        write_atomic(&self.dir, CHECKPOINT, &serde_json::to_vec(&checkpoint)?)
+    }
+
+    fn check(&self, ledger: &Ledger, checkpoint: &Checkpoint) -> Result<(), StorageError> {
+        if !ledger.dag().tips().eq(checkpoint.tips.iter()) || *ledger.state() != checkpoint.state {
+            return Err(StorageError::CheckpointMismatch);
+        }
+
+        Ok(())
+    }
+
+    fn checkpoint_file(&self) -> Result<Option<Checkpoint>, StorageError> {
+        let checkpoint: Checkpoint = match fs::read(self.dir.join(CHECKPOINT)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if checkpoint.records > self.index.len() as u64 {
+            return Err(StorageError::CheckpointMismatch);
+        }
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Loads the index and repairs what a crash in the middle of [`Store::append`] leaves behind.
+    fn recover(&mut self) -> Result<(), StorageError> {
+        self.log_len = self.log.metadata()?.len();
+
+        let mut entries = Vec::new();
+        (&self.index_file).seek(SeekFrom::Start(0))?;
+        (&self.index_file).read_to_end(&mut entries)?;
+
+        // An entry cut short by a crash is dropped; its record is indexed again below.
+        let complete = entries.len() as u64 / INDEX_ENTRY_LEN * INDEX_ENTRY_LEN;
+        if complete != entries.len() as u64 {
+            self.index_file.set_len(complete)?;
+        }
+
+        let mut next = 0;
+        for entry in entries.chunks_exact(INDEX_ENTRY_LEN as usize) {
+            let hash = TxHash::from_bytes(entry[..32].try_into().unwrap());
+            let offset = u64::from_be_bytes(entry[32..].try_into().unwrap());
+            if offset != next || self.positions.contains_key(&hash) {
+                return Err(StorageError::BadIndex);
+            }
+
+            next = self.record_end(offset)?.ok_or(StorageError::BadIndex)?;
+            self.positions.insert(hash, self.index.len());
+            self.index.push((hash, offset));
+        }
+
+        // Records appended after the last index entry was written.
+        while next < self.log_len {
+            let Some(tx) = self.read_tail(next)? else {
+                break;
+            };
+
+            let hash = tx.hash();
+            if self.contains(&hash) {
+                return Err(StorageError::Corrupt { offset: next });
+            }
+            self.write_index_entry(&hash, next)?;
+            next = self.record_end(next)?.unwrap();
+        }
+        self.index_file.sync_data()?;
+
+        if next < self.log_len {
+            self.log.set_len(next)?;
+            self.log.sync_data()?;
+            self.log_len = next;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a record that may be the torn last one: `None` if it is incomplete, or is the last
+    /// record and fails its checksum.
+    fn read_tail(&self, offset: u64) -> Result<Option<SignedTransaction>, StorageError> {
+        let Some(end) = self.record_end(offset)? else {
+            return Ok(None);
+        };
+
+        match self.read(offset) {
+            Err(StorageError::Corrupt { .. }) if end == self.log_len => Ok(None),
+            result => result,
+        }
+    }
+
+    /// End offset of the record at `offset`, or `None` if it runs past the end of the log.
+    fn record_end(&self, offset: u64) -> Result<Option<u64>, StorageError> {
+        if offset + 4 > self.log_len {
+            return Ok(None);
+        }
+
+        let mut len = [0; 4];
+        (&self.log).seek(SeekFrom::Start(offset))?;
+        (&self.log).read_exact(&mut len)?;
+
+        let end = offset + 4 + u32::from_be_bytes(len) as u64 + CHECKSUM_LEN as u64;
+        Ok((end <= self.log_len).then_some(end))
+    }
+
+    /// Reads and checks the record at `offset`, or `None` if it runs past the end of the log.
+    fn read(&self, offset: u64) -> Result<Option<SignedTransaction>, StorageError> {
+        let Some(end) = self.record_end(offset)? else {
+            return Ok(None);
+        };
+
+        let mut record = vec![0; (end - offset - 4) as usize];
+        (&self.log).seek(SeekFrom::Start(offset + 4))?;
+        (&self.log).read_exact(&mut record)?;
+
+        let (bytes, checksum) = record.split_at(record.len() - CHECKSUM_LEN);
+        if blake3::hash(bytes).as_bytes() != checksum {
+            return Err(StorageError::Corrupt { offset });
+        }
+
+        SignedTransaction::from_bytes(bytes)
+            .map(Some)
+            .map_err(|_| StorageError::Corrupt { offset })
+    }
+
+    fn write_index_entry(&mut self, hash: &TxHash, offset: u64) -> Result<(), StorageError> {
+        let mut entry = [0; INDEX_ENTRY_LEN as usize];
+        entry[..32].copy_from_slice(hash.as_ref());
+        entry[32..].copy_from_slice(&offset.to_be_bytes());
+        self.index_file.write_all(&entry)?;
+
+        self.positions.insert(*hash, self.index.len());
+        self.index.push((*hash, offset));
+
+        Ok(())
+    }
+}
+
+/// Replaces `dir/name` with `contents` so that a crash leaves either the old or the new file.
+fn write_atomic(dir: &Path, name: &str, contents: &[u8]) -> Result<(), StorageError> {
+    let tmp = dir.join(format!("{name}.tmp"));
+
+    let mut file = File::create(&tmp)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    fs::rename(&tmp, dir.join(name))?;
+
+    // Persist the rename itself. Directories cannot be opened as files on every platform.
+    if let Ok(dir) = File::open(dir) {
+        let _ = dir.sync_all();
+    }
+
+    Ok(())
+}
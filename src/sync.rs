@@ -0,0 +1,244 @@
+//! Catching up with a peer after joining the network or coming back online.
+//!
+//! A syncing node asks a peer for its tips, then walks back through the parents of every
+//! transaction it lacks, fetching them in batches, until it reaches transactions it already has.
+//! Fetched transactions are checked as they arrive but only applied to the ledger in topological
+//! order, once all of their parents are in the DAG.
+//!
+//! [`SyncProgress`] holds what has been fetched and what is still wanted. It survives a failed
+//! sync, so the node can resume with another peer, and it can be serialized to resume after a
+//! restart.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+    fmt,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    crypto::TxHash,
+    dag::DagError,
+    ledger::{Ledger, LedgerError, Report},
+    transaction::{SignedTransaction, VerifyError},
+};
+
+/// Reasons a sync fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyncError {
+    /// The peer sent a transaction that was not asked for.
+    Unrequested(TxHash),
+    /// The peer sent a transaction with an invalid signature.
+    Invalid(TxHash, VerifyError),
+    /// No peer has had these transactions so far.
+    Unavailable(Vec<TxHash>),
+    /// Applying a fetched transaction failed.
+    Ledger(LedgerError),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unrequested(hash) => write!(f, "unrequested transaction {hash}"),
+            Self::Invalid(hash, e) => write!(f, "invalid transaction {hash}: {e}"),
+            Self::Unavailable(hashes) => write!(f, "{} transactions unavailable", hashes.len()),
+            Self::Ledger(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for SyncError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Invalid(_, e) => Some(e),
+            Self::Ledger(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<LedgerError> for SyncError {
+    fn from(e: LedgerError) -> Self {
+        Self::Ledger(e)
+    }
+}
+
+/// State of an ongoing sync.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SyncProgress {
+    /// Transactions to ask for.
+    wanted: BTreeSet<TxHash>,
+    /// Transactions asked for whose answer has not arrived.
+    requested: BTreeSet<TxHash>,
+    /// Transactions the current peer does not have.
+    unavailable: BTreeSet<TxHash>,
+    /// Transactions fetched but not applied yet, because some parents are still missing.
+    fetched: BTreeMap<TxHash, SignedTransaction>,
+}
+
+impl SyncProgress {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether nothing is left to fetch or apply.
+    pub fn is_complete(&self) -> bool {
+        self.wanted.is_empty()
+            && self.requested.is_empty()
+            && self.unavailable.is_empty()
+            && self.fetched.is_empty()
+    }
+
+    /// Number of fetched transactions waiting on their parents.
+    pub fn pending(&self) -> usize {
+        self.fetched.len()
+    }
+
+    /// Transactions the current peer does not have.
+    pub fn unavailable(&self) -> impl Iterator<Item = &TxHash> {
+        self.unavailable.iter()
+    }
+
+    /// Prepares to continue with another peer: everything not fetched yet is wanted again.
+    pub fn retry(&mut self) {
+        self.wanted.append(&mut self.requested);
+        self.wanted.append(&mut self.unavailable);
+    }
+
+    /// Adds the transactions among `hashes` that neither the ledger nor this sync has.
+    pub fn want(&mut self, ledger: &Ledger, hashes: impl IntoIterator<Item = TxHash>) {
+        for hash in hashes {
+            let dag = ledger.dag();
+            let known = dag.is_known(&hash)
+                || dag.is_orphan(&hash)
+                || self.fetched.contains_key(&hash)
+                || self.requested.contains(&hash)
+                || self.unavailable.contains(&hash);
+
+            if !known {
+                self.wanted.insert(hash);
+            }
+        }
+    }
+
+    /// Takes up to `max` wanted transactions to ask for.
+    pub fn next_batch(&mut self, max: usize) -> Vec<TxHash> {
+        let batch: Vec<_> = self.wanted.iter().take(max).copied().collect();
+        for hash in &batch {
+            self.wanted.remove(hash);
+            self.requested.insert(*hash);
+        }
+
+        batch
+    }
+
+    /// Records the answer to the last batch: the transactions the peer sent, and those it says it
+    /// does not have. Requested transactions missing from both are wanted again.
+    ///
+    /// Every transaction is checked before anything is recorded, so a bad answer changes nothing.
+    pub fn receive(
+        &mut self,
+        ledger: &Ledger,
+        transactions: Vec<SignedTransaction>,
+        missing: &[TxHash],
+    ) -> Result<(), SyncError> {
+        let mut batch = Vec::new();
+        for tx in transactions {
+            let hash = tx.hash();
+            if !self.requested.contains(&hash) {
+                return Err(SyncError::Unrequested(hash));
+            }
+            tx.verify().map_err(|e| SyncError::Invalid(hash, e))?;
+
+            batch.push((hash, tx));
+        }
+
+        for (hash, tx) in batch {
+            self.requested.remove(&hash);
+            self.want(ledger, tx.transaction().parents().iter().copied());
+            self.fetched.insert(hash, tx);
+        }
+        for hash in missing {
+            if self.requested.remove(hash) {
+                self.unavailable.insert(*hash);
+            }
+        }
+        self.wanted.append(&mut self.requested);
+
+        Ok(())
+    }
+
+    /// Applies every fetched transaction whose parents are all in the DAG, parents first.
+    pub fn apply(&mut self, ledger: &mut Ledger) -> Result<Report, SyncError> {
+        let mut report = Report::default();
+
+        // Applying a transaction can connect orphans parked in the ledger, which can make other
+        // fetched transactions ready, so go on until nothing more is applied.
+        loop {
+            let mut children: HashMap<TxHash, Vec<TxHash>> = HashMap::new();
+            for (hash, tx) in &self.fetched {
+                for parent in tx.transaction().parents() {
+                    children.entry(*parent).or_default().push(*hash);
+                }
+            }
+
+            let is_ready = |ledger: &Ledger, tx: &SignedTransaction| {
+                let parents = tx.transaction().parents();
+                parents.iter().all(|p| ledger.dag().is_known(p))
+            };
+            let mut ready: VecDeque<_> = self
+                .fetched
+                .iter()
+                .filter(|(_, tx)| is_ready(ledger, tx))
+                .map(|(hash, _)| *hash)
+                .collect();
+            if ready.is_empty() {
+                return Ok(report);
+            }
+
+            while let Some(hash) = ready.pop_front() {
+                let Some(tx) = self.fetched.remove(&hash) else {
+                    continue;
+                };
+
+                match ledger.insert(tx) {
+                    Ok(applied) => merge(&mut report, applied),
+                    // It arrived some other way in the meantime.
+                    Err(LedgerError::Dag(DagError::Duplicate(_))) => {}
+                    // The peer stored it under looser rules or another clock. Its fetched
+                    // descendants can never connect here, so they are dropped with it.
+                    Err(LedgerError::Invalid(_)) => {
+                        let mut refused = vec![hash];
+                        while let Some(hash) = refused.pop() {
+                            for child in children.get(&hash).into_iter().flatten() {
+                                if self.fetched.remove(child).is_some() {
+                                    refused.push(*child);
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+
+                for child in children.get(&hash).into_iter().flatten() {
+                    if self
+                        .fetched
+                        .get(child)
+                        .is_some_and(|tx| is_ready(ledger, tx))
+                    {
+                        ready.push_back(*child);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Adds the status changes of `other` to `report`.
+pub(crate) fn merge(report: &mut Report, other: Report) {
+    report.accepted.extend(other.accepted);
+    report.rejected.extend(other.rejected);
+    report.pending.extend(other.pending);
+    report.conflicts.extend(other.conflicts);
+}
@@ -0,0 +1,268 @@
+//! Choosing the parents of new transactions.
+//!
+//! A new transaction approves tips of the DAG: accepted transactions that no accepted transaction
+//! approves yet. Only accepted transactions are candidates, since a transaction descending from a
+//! rejected one is rejected too. Accepted transactions never conflict with each other, so any set
+//! of them is a valid choice of parents.
+//!
+//! Transactions dated after the local clock are left out, since a new transaction older than its
+//! parent is rejected. Their accepted descendants are never older than them, so they are left out
+//! too.
+//!
+//! A sender must also build on its own latest accepted transaction, otherwise its new transaction
+//! is rejected. Given the sender, [`TipSelector::select`] makes sure one of the chosen tips
+//! descends from it.
+
+use std::collections::HashSet;
+
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    seq::SliceRandom,
+    Rng,
+};
+
+use crate::{
+    address::Address,
+    crypto::TxHash,
+    ledger::{Ledger, Status},
+    transaction,
+};
+
+/// Default for [`TipSelector::min_parents`].
+pub const DEFAULT_MIN_PARENTS: usize = 2;
+
+/// Default for [`TipSelector::max_parents`].
+pub const DEFAULT_MAX_PARENTS: usize = 4;
+
+/// How tips are picked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Strategy {
+    /// Every tip is equally likely.
+    Uniform,
+    /// One random walk per parent, each starting `depth` approvals below a random tip and moving
+    /// up to a child with odds proportional to `exp(alpha * weight)`, where `weight` is the
+    /// child's cumulative weight. Walks favor the heavier branches of the DAG, so tips left
+    /// behind on a light branch are less likely to be approved. With `alpha` at 0 every child is
+    /// equally likely, and so it is when `alpha` is not finite or the odds overflow.
+    RandomWalk { depth: usize, alpha: f64 },
+    /// The tips with the lowest hashes, so that tests are reproducible.
+    Deterministic,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Self::RandomWalk {
+            depth: 8,
+            alpha: 0.5,
+        }
+    }
+}
+
+/// Picks the parents of new transactions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TipSelector {
+    pub strategy: Strategy,
+    /// Fewest parents returned, as long as the ledger holds that many accepted transactions.
+    /// When there are fewer tips, the most recent other accepted transactions make up for them.
+    pub min_parents: usize,
+    /// Most parents returned. At least one parent is returned if there is any tip.
+    pub max_parents: usize,
+}
+
+impl Default for TipSelector {
+    fn default() -> Self {
+        Self::new(Strategy::default())
+    }
+}
+
+impl TipSelector {
+    pub fn new(strategy: Strategy) -> Self {
+        Self {
+            strategy,
+            min_parents: DEFAULT_MIN_PARENTS,
+            max_parents: DEFAULT_MAX_PARENTS,
+        }
+    }
+
+    /// Chooses parents for a new transaction of `sender`, in hash order. Returns no parents only
+    /// when the ledger has no accepted transaction dated up to now to approve.
+    pub fn select<R: Rng + ?Sized>(
+        &self,
+        ledger: &Ledger,
+        sender: Option<&Address>,
+        rng: &mut R,
+    ) -> Vec<TxHash> {
+        let view = View::new(ledger, transaction::now());
+        let tips = view.tips();
+        if tips.is_empty() {
+            return Vec::new();
+        }
+
+        let max = self.max_parents.max(1);
+        let min = self.min_parents.min(max);
+
+        let mut selected: Vec<TxHash> = match self.strategy {
+            Strategy::Uniform => tips.choose_multiple(rng, max).copied().collect(),
+            Strategy::RandomWalk { depth, alpha } => {
+                let mut selected = Vec::new();
+                for _ in 0..max {
+                    let tip = view.walk(&tips, depth, alpha, rng);
+                    if !selected.contains(&tip) {
+                        selected.push(tip);
+                    }
+                }
+                selected
+            }
+            Strategy::Deterministic => tips.iter().take(max).copied().collect(),
+        };
+
+        if let Some(last) = sender.and_then(|sender| view.last_spend(sender)) {
+            let descendants = ledger.dag().descendants(&last);
+            let covered = |hash: &TxHash| *hash == last || descendants.contains(hash);
+
+            if !selected.iter().any(covered) {
+                // Some tip descends from every accepted transaction, unless it is a tip itself.
+                let tip = tips.iter().copied().find(covered).unwrap_or(last);
+                if selected.len() == max {
+                    selected.pop();
+                }
+                selected.push(tip);
+            }
+        }
+
+        if selected.len() < min {
+            for hash in view.recent() {
+                if selected.len() == min {
+                    break;
+                }
+                if !selected.contains(&hash) {
+                    selected.push(hash);
+                }
+            }
+        }
+
+        selected.sort();
+        selected
+    }
+}
+
+/// The accepted part of a ledger, as of `now`.
+struct View<'a> {
+    ledger: &'a Ledger,
+    /// Cut of the snapshot the ledger started from, which counts as accepted.
+    base: HashSet<TxHash>,
+    /// Milliseconds since the Unix epoch.
+    now: u64,
+}
+
+impl<'a> View<'a> {
+    fn new(ledger: &'a Ledger, now: u64) -> Self {
+        Self {
+            ledger,
+            base: ledger.dag().base().copied().collect(),
+            now,
+        }
+    }
+
+    fn is_accepted(&self, hash: &TxHash) -> bool {
+        if self.base.contains(hash) {
+            return true;
+        }
+
+        self.ledger.status(hash) == Some(Status::Accepted)
+            && self
+                .ledger
+                .get(hash)
+                .is_some_and(|tx| tx.transaction().timestamp() <= self.now)
+    }
+
+    /// Accepted transactions without accepted children, in hash order.
+    fn tips(&self) -> Vec<TxHash> {
+        let dag = self.ledger.dag();
+        let connected = dag.iter().map(|(hash, _)| hash);
+
+        let mut tips: Vec<_> = connected
+            .chain(&self.base)
+            .filter(|hash| self.is_accepted(hash))
+            .filter(|hash| !dag.children(hash).any(|child| self.is_accepted(child)))
+            .copied()
+            .collect();
+        tips.sort();
+        tips.dedup();
+
+        tips
+    }
+
+    /// Accepted transactions, children first.
+    fn recent(&self) -> impl Iterator<Item = TxHash> + '_ {
+        let order = self.ledger.dag().topological_order();
+        order
+            .into_iter()
+            .rev()
+            .filter(move |hash| self.is_accepted(hash))
+    }
+
+    /// The accepted transaction of `sender` with the highest sequence number.
+    fn last_spend(&self, sender: &Address) -> Option<TxHash> {
+        let sequence = |hash: &&TxHash| {
+            let tx = self.ledger.get(hash);
+            tx.map(|tx| tx.transaction().sequence())
+        };
+
+        self.ledger.spends(sender).max_by_key(sequence).copied()
+    }
+
+    /// The transaction itself plus its accepted descendants.
+    fn weight(&self, hash: &TxHash) -> usize {
+        let descendants = self.ledger.dag().descendants(hash);
+        1 + descendants.iter().filter(|h| self.is_accepted(h)).count()
+    }
+
+    fn walk<R: Rng + ?Sized>(
+        &self,
+        tips: &[TxHash],
+        depth: usize,
+        alpha: f64,
+        rng: &mut R,
+    ) -> TxHash {
+        let dag = self.ledger.dag();
+
+        let mut current = *tips.choose(rng).unwrap();
+        for _ in 0..depth {
+            let parents: Vec<_> = dag.get(&current).map_or_else(Vec::new, |tx| {
+                let parents = tx.transaction().parents().iter();
+                parents.filter(|p| self.is_accepted(p)).collect()
+            });
+            match parents.choose(rng) {
+                Some(parent) => current = **parent,
+                None => break,
+            }
+        }
+
+        loop {
+            let children: Vec<_> = dag
+                .children(&current)
+                .filter(|child| self.is_accepted(child))
+                .collect();
+            if children.is_empty() {
+                return current;
+            }
+
+            // Relative to the heaviest child, so that large weights do not overflow.
+            let weights: Vec<_> = children.iter().map(|c| self.weight(c) as f64).collect();
+            let heaviest = weights.iter().copied().fold(0.0, f64::max);
+            let odds: Vec<_> = weights
+                .iter()
+                .map(|w| (alpha * (w - heaviest)).exp())
+                .collect();
+
+            // A NaN or infinite alpha, or a negative one that overflows, gives no usable odds.
+            let usable = odds.iter().all(|o| o.is_finite());
+            let index = usable.then(|| WeightedIndex::new(&odds).ok()).flatten();
+            current = match index {
+                Some(index) => *children[index.sample(rng)],
+                None => **children.choose(rng).unwrap(),
+            };
+        }
+    }
+}
@@ -0,0 +1,590 @@
+use std::{fmt, time::UNIX_EPOCH};
+
+use ed25519_dalek::Signer;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    address::{Address, AddressKind, Network, ADDRESS_LEN},
+    crypto::{
+        AccountKey, PublicKey, SecretKey, Signature, SignatureAlgorithm, TxHash,
+        ED25519_PUBLIC_KEY_LEN, ED25519_SECRET_KEY_LEN, ED25519_SIGNATURE_LEN,
+    },
+    encoding::{Encoder, TXID_TAG, TX_TAG},
+    multisig::{MultisigPolicy, PolicyError, MAX_KEYS},
+    wire::{self, Reader, WireError, Writer},
+};
+
+/// Reasons a [`SignedTransaction`] can fail verification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// The sender key does not have the length required by the algorithm.
+    BadKeyLength { expected: usize, actual: usize },
+    /// The signature is longer than the algorithm allows.
+    BadSignatureLength { max: usize, actual: usize },
+    /// The signature does not match the transaction and sender key.
+    SignatureMismatch,
+    /// The transaction's algorithm is not enabled in this build.
+    UnsupportedAlgorithm(SignatureAlgorithm),
+    /// Fewer signatures than the sender's threshold.
+    NotEnoughSignatures { threshold: usize, actual: usize },
+    /// A signature's key index is out of range, repeated or out of order.
+    BadSignerIndex(u8),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadKeyLength { expected, actual } => {
+                write!(f, "bad key length: expected {expected} bytes, got {actual}")
+            }
+            Self::BadSignatureLength { max, actual } => {
+                write!(f, "bad signature length: at most {max} bytes, got {actual}")
+            }
+            Self::SignatureMismatch => write!(f, "signature mismatch"),
+            Self::UnsupportedAlgorithm(a) => write!(f, "unsupported algorithm {a}"),
+            Self::NotEnoughSignatures { threshold, actual } => {
+                write!(f, "{actual} signatures, {threshold} required")
+            }
+            Self::BadSignerIndex(index) => write!(f, "bad signer index {index}"),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Reasons [`Transaction::sign`] can fail.
+#[derive(Debug)]
+pub enum SignError {
+    /// The secret key does not have the length required by the algorithm.
+    BadKeyLength { expected: usize, actual: usize },
+    /// The transaction's algorithm is not enabled in this build.
+    UnsupportedAlgorithm(SignatureAlgorithm),
+    /// liboqs failed to produce a signature.
+    Signing(oqs::Error),
+    /// The sender is a multisig account, whose cosigners sign through a
+    /// [`PartiallySignedTransaction`](crate::multisig::PartiallySignedTransaction).
+    Multisig,
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadKeyLength { expected, actual } => {
+                write!(f, "bad key length: expected {expected} bytes, got {actual}")
+            }
+            Self::UnsupportedAlgorithm(a) => write!(f, "unsupported algorithm {a}"),
+            Self::Signing(e) => write!(f, "signing failed: {e}"),
+            Self::Multisig => write!(
+                f,
+                "multisig transactions need a partially-signed transaction"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SignError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Signing(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<oqs::Error> for SignError {
+    fn from(e: oqs::Error) -> Self {
+        Self::Signing(e)
+    }
+}
+
+/// The account a transaction spends from.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Sender {
+    /// A single key.
+    Key(AccountKey),
+    /// An m-of-n multisig account.
+    Multisig(MultisigPolicy),
+}
+
+impl Sender {
+    /// The sender's address on `network`.
+    pub fn address(&self, network: Network) -> Address {
+        match self {
+            Self::Key(key) => Address::from_public_key(network, key.algorithm(), key.public_key()),
+            Self::Multisig(policy) => Address::from_multisig(network, policy),
+        }
+    }
+
+    /// Keys that may sign for the sender, indexed by [`Cosignature::index`].
+    pub fn keys(&self) -> &[AccountKey] {
+        match self {
+            Self::Key(key) => std::slice::from_ref(key),
+            Self::Multisig(policy) => policy.keys(),
+        }
+    }
+
+    /// Number of signatures a transaction needs.
+    pub fn threshold(&self) -> usize {
+        match self {
+            Self::Key(_) => 1,
+            Self::Multisig(policy) => policy.threshold() as usize,
+        }
+    }
+
+    /// Appends the canonical encoding described in [`crate::encoding`].
+    pub fn encode(&self, encoder: &mut Encoder) {
+        match self {
+            Self::Key(key) => {
+                encoder
+                    .u8(key.algorithm().id())
+                    .bytes(key.public_key().as_ref());
+            }
+            Self::Multisig(policy) => {
+                encoder.u8(AddressKind::Multisig.id());
+                policy.encode(encoder);
+            }
+        }
+    }
+
+    fn write(&self, writer: &mut Writer) {
+        match self {
+            Self::Key(key) => {
+                writer
+                    .u8(key.algorithm().id())
+                    .bytes(key.public_key().as_ref());
+            }
+            Self::Multisig(policy) => {
+                writer
+                    .u8(AddressKind::Multisig.id())
+                    .u8(policy.threshold())
+                    .varint(policy.keys().len() as u64);
+                for key in policy.keys() {
+                    writer
+                        .u8(key.algorithm().id())
+                        .bytes(key.public_key().as_ref());
+                }
+            }
+        }
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self, WireError> {
+        fn key(reader: &mut Reader, algorithm: u8) -> Result<AccountKey, WireError> {
+            let algorithm = SignatureAlgorithm::from_id(algorithm)
+                .ok_or(WireError::UnknownAlgorithm(algorithm))?;
+
+            Ok(AccountKey::new(
+                algorithm,
+                PublicKey::from_bytes(reader.bytes()?),
+            ))
+        }
+
+        let kind = reader.u8()?;
+        if kind != AddressKind::Multisig.id() {
+            return Ok(Self::Key(key(reader, kind)?));
+        }
+
+        let threshold = reader.u8()?;
+        let count = reader.varint()?;
+        if count > MAX_KEYS as u64 {
+            let count = usize::try_from(count).unwrap_or(usize::MAX);
+            return Err(PolicyError::TooManyKeys(count).into());
+        }
+
+        let mut keys = Vec::new();
+        for _ in 0..count {
+            let algorithm = reader.u8()?;
+            keys.push(key(reader, algorithm)?);
+        }
+
+        Ok(Self::Multisig(MultisigPolicy::new(threshold, keys)?))
+    }
+}
+
+impl From<AccountKey> for Sender {
+    fn from(key: AccountKey) -> Self {
+        Self::Key(key)
+    }
+}
+
+impl From<MultisigPolicy> for Sender {
+    fn from(policy: MultisigPolicy) -> Self {
+        Self::Multisig(policy)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    network: Network,
+    parents: Vec<TxHash>,
+    sender: Sender,
+    sequence: u64,
+    timestamp: u64,
+    amount: u64,
+    receiver: Address,
+}
+
+impl Transaction {
+    /// Creates a payment from `sender` to `receiver`.
+    ///
+    /// `sequence` must equal the number of transactions the sender has already sent. Senders
+    /// should include their previous transaction in `parents`, so that their payments are
+    /// applied in order.
+    pub fn new(
+        network: Network,
+        parents: &[TxHash],
+        sender: &Sender,
+        sequence: u64,
+        amount: u64,
+        receiver: &Address,
+    ) -> Self {
+        Self {
+            network,
+            parents: parents.to_vec(),
+            sender: sender.clone(),
+            sequence,
+            timestamp: now(),
+            amount,
+            receiver: *receiver,
+        }
+    }
+
+    /// The network the transaction is valid on.
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    pub fn parents(&self) -> &[TxHash] {
+        &self.parents
+    }
+
+    pub fn sender(&self) -> &Sender {
+        &self.sender
+    }
+
+    /// The address of the sender on the transaction's network.
+    pub fn sender_address(&self) -> Address {
+        self.sender.address(self.network)
+    }
+
+    /// Number of transactions the sender sent before this one.
+    pub fn sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// Creation time, in milliseconds since the Unix epoch.
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn amount(&self) -> u64 {
+        self.amount
+    }
+
+    pub fn receiver(&self) -> &Address {
+        &self.receiver
+    }
+
+    /// Canonical encoding of the transaction fields, without a domain tag.
+    ///
+    /// See [`crate::encoding`] for the exact layout.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+
+        encoder.u8(self.network.id()).u32(self.parents.len() as u32);
+        for parent in &self.parents {
+            encoder.bytes(parent.as_ref());
+        }
+
+        self.sender.encode(&mut encoder);
+
+        encoder
+            .u64(self.sequence)
+            .u64(self.timestamp)
+            .u64(self.amount)
+            .bytes(&self.receiver.to_bytes());
+
+        encoder.finish()
+    }
+
+    /// The message that is signed by the sender.
+    pub fn signing_message(&self) -> Vec<u8> {
+        let mut encoder = Encoder::new();
+        encoder.bytes(TX_TAG).raw(&self.encode());
+
+        encoder.finish()
+    }
+
+    /// Signs a single-key transaction with the sender's secret key.
+    ///
+    /// Hybrid algorithms produce an Ed25519 and a Falcon signature over the same message.
+    pub fn sign(self, sk: &SecretKey) -> Result<SignedTransaction, SignError> {
+        let Sender::Key(key) = &self.sender else {
+            return Err(SignError::Multisig);
+        };
+
+        let signature = sign_message(key.algorithm(), &self.signing_message(), sk)?;
+
+        Ok(SignedTransaction {
+            transaction: self,
+            signatures: vec![Cosignature {
+                index: 0,
+                signature,
+            }],
+        })
+    }
+}
+
+/// Milliseconds since the Unix epoch, as in [`Transaction::timestamp`].
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Signs `message` with a secret key of `algorithm`.
+pub(crate) fn sign_message(
+    algorithm: SignatureAlgorithm,
+    message: &[u8],
+    sk: &SecretKey,
+) -> Result<Signature, SignError> {
+    let sig = algorithm
+        .sig()
+        .map_err(|_| SignError::UnsupportedAlgorithm(algorithm))?;
+
+    let classical_len = match algorithm.is_hybrid() {
+        true => ED25519_SECRET_KEY_LEN,
+        false => 0,
+    };
+    let expected = classical_len + sig.length_secret_key();
+    if sk.len() != expected {
+        return Err(SignError::BadKeyLength {
+            expected,
+            actual: sk.len(),
+        });
+    }
+    let (classical, sk) = sk.as_ref().split_at(classical_len);
+
+    let mut signature = Vec::new();
+
+    if algorithm.is_hybrid() {
+        let key = ed25519_dalek::SigningKey::from_bytes(classical.try_into().unwrap());
+        signature.extend_from_slice(&key.sign(message).to_bytes());
+    }
+
+    let sk = sig.secret_key_from_bytes(sk).unwrap();
+    signature.extend_from_slice(sig.sign(message, sk)?.as_ref());
+
+    Ok(Signature::from_bytes(signature))
+}
+
+/// Checks a signature over `message` by `key`. Never panics.
+pub(crate) fn verify_message(
+    key: &AccountKey,
+    message: &[u8],
+    signature: &Signature,
+) -> Result<(), VerifyError> {
+    let algorithm = key.algorithm();
+    let sig = algorithm
+        .sig()
+        .map_err(|_| VerifyError::UnsupportedAlgorithm(algorithm))?;
+
+    let (pk_len, signature_len) = match algorithm.is_hybrid() {
+        true => (ED25519_PUBLIC_KEY_LEN, ED25519_SIGNATURE_LEN),
+        false => (0, 0),
+    };
+
+    let pk = key.public_key().as_ref();
+    let bad_key = VerifyError::BadKeyLength {
+        expected: pk_len + sig.length_public_key(),
+        actual: pk.len(),
+    };
+    let (classical_pk, pk) = pk.split_at_checked(pk_len).ok_or(bad_key.clone())?;
+    let pk = sig.public_key_from_bytes(pk).ok_or(bad_key)?;
+
+    let signature = signature.as_ref();
+    let bad_signature = VerifyError::BadSignatureLength {
+        max: signature_len + sig.length_signature(),
+        actual: signature.len(),
+    };
+    let (classical_signature, signature) = signature
+        .split_at_checked(signature_len)
+        .ok_or(bad_signature.clone())?;
+    let signature = sig.signature_from_bytes(signature).ok_or(bad_signature)?;
+
+    if algorithm.is_hybrid() {
+        let key = ed25519_dalek::VerifyingKey::from_bytes(classical_pk.try_into().unwrap())
+            .map_err(|_| VerifyError::SignatureMismatch)?;
+        let classical_signature =
+            ed25519_dalek::Signature::from_bytes(classical_signature.try_into().unwrap());
+
+        key.verify_strict(message, &classical_signature)
+            .map_err(|_| VerifyError::SignatureMismatch)?;
+    }
+
+    sig.verify(message, signature, pk)
+        .map_err(|_| VerifyError::SignatureMismatch)
+}
+
+/// A signature by one of the sender's keys.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cosignature {
+    /// Index of the signing key in [`Sender::keys`].
+    pub index: u8,
+    pub signature: Signature,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    //#[serde(flatten)]
+    transaction: Transaction,
+    signatures: Vec<Cosignature>,
+}
+
+impl SignedTransaction {
+    /// Assembles a signed transaction without checking the signatures.
+    pub(crate) fn from_parts(transaction: Transaction, signatures: Vec<Cosignature>) -> Self {
+        Self {
+            transaction,
+            signatures,
+        }
+    }
+
+    pub fn transaction(&self) -> &Transaction {
+        &self.transaction
+    }
+
+    /// Signatures ordered by key index.
+    pub fn signatures(&self) -> &[Cosignature] {
+        &self.signatures
+    }
+
+    /// The transaction ID: a BLAKE3 hash of the canonical encoding and the signatures.
+    pub fn hash(&self) -> TxHash {
+        let mut encoder = Encoder::new();
+        encoder
+            .bytes(TXID_TAG)
+            .raw(&self.transaction.encode())
+            .u32(self.signatures.len() as u32);
+        for cosignature in &self.signatures {
+            encoder
+                .u8(cosignature.index)
+                .bytes(cosignature.signature.as_ref());
+        }
+
+        blake3::hash(&encoder.finish()).into()
+    }
+
+    /// Encodes the transaction in the compact binary format described in [`crate::wire`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let transaction = &self.transaction;
+        let mut writer = Writer::new();
+
+        writer
+            .u8(wire::VERSION)
+            .u8(transaction.network.id())
+            .varint(transaction.parents.len() as u64);
+        for parent in &transaction.parents {
+            writer.raw(parent.as_ref());
+        }
+
+        transaction.sender.write(&mut writer);
+
+        writer
+            .varint(transaction.sequence)
+            .varint(transaction.timestamp)
+            .varint(transaction.amount)
+            .raw(&transaction.receiver.to_bytes())
+            .varint(self.signatures.len() as u64);
+        for cosignature in &self.signatures {
+            writer
+                .u8(cosignature.index)
+                .bytes(cosignature.signature.as_ref());
+        }
+
+        writer.finish()
+    }
+
+    /// Decodes a transaction from the compact binary format described in [`crate::wire`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WireError> {
+        let mut reader = Reader::new(bytes);
+
+        let version = reader.u8()?;
+        if version != wire::VERSION {
+            return Err(WireError::UnknownVersion(version));
+        }
+
+        let network = reader.u8()?;
+        let network = Network::from_id(network).ok_or(WireError::UnknownNetwork(network))?;
+
+        let count = reader.varint()?;
+        let mut parents = Vec::new();
+        for _ in 0..count {
+            parents.push(TxHash::from_bytes(reader.raw(32)?.try_into().unwrap()));
+        }
+
+        let sender = Sender::read(&mut reader)?;
+        let sequence = reader.varint()?;
+        let timestamp = reader.varint()?;
+        let amount = reader.varint()?;
+        let receiver = Address::from_bytes(reader.raw(ADDRESS_LEN)?)?;
+
+        let count = reader.varint()?;
+        let mut signatures = Vec::new();
+        for _ in 0..count {
+            signatures.push(Cosignature {
+                index: reader.u8()?,
+                signature: Signature::from_bytes(reader.bytes()?),
+            });
+        }
+
+        reader.finish()?;
+
+        Ok(Self {
+            transaction: Transaction {
+                network,
+                parents,
+                sender,
+                sequence,
+                timestamp,
+                amount,
+                receiver,
+            },
+            signatures,
+        })
+    }
+
+    /// Checks the signatures against the sender's keys.
+    ///
+    /// At least the sender's threshold of signatures is required, ordered by key index, and every
+    /// one of them must be valid. Hybrid keys must provide a valid Ed25519 and a valid Falcon
+    /// signature. Never panics: malformed or hostile transactions are reported as a
+    /// [`VerifyError`].
+    pub fn verify(&self) -> Result<(), VerifyError> {
+        let sender = &self.transaction.sender;
+        let keys = sender.keys();
+
+        if self.signatures.len() < sender.threshold() {
+            return Err(VerifyError::NotEnoughSignatures {
+                threshold: sender.threshold(),
+                actual: self.signatures.len(),
+            });
+        }
+
+        let message = self.transaction.signing_message();
+
+        let mut previous = None;
+        for cosignature in &self.signatures {
+            let index = cosignature.index;
+            if index as usize >= keys.len() || previous.is_some_and(|p| p >= index) {
+                return Err(VerifyError::BadSignerIndex(index));
+            }
+            previous = Some(index);
+
+            verify_message(&keys[index as usize], &message, &cosignature.signature)?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,225 @@
+//! Encrypted sessions between peers.
+//!
+//! During the handshake each side sends a fresh ML-KEM-768 public key in its hello, and
+//! encapsulates a secret to the key of the other side. Both secrets and both hellos go into the
+//! key of each direction, so the keys stay secret as long as either side drew fresh randomness,
+//! and traffic recorded today cannot be decrypted later with a quantum computer. The handshake
+//! signatures cover the hellos and the ciphertexts, which authenticates the key exchange with the
+//! node keys.
+//!
+//! Every frame after the handshake is a message sealed with ChaCha20-Poly1305. The nonce is the
+//! number of frames sent before in the same direction, so dropped, replayed or reordered frames
+//! fail to decrypt.
+
+use std::fmt;
+
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    ChaCha20Poly1305, Nonce,
+};
+use oqs::kem::{self, Kem};
+use zeroize::Zeroizing;
+
+use crate::crypto::AccountKey;
+
+/// Key encapsulation mechanism of the handshake.
+pub const KEM: kem::Algorithm = kem::Algorithm::MlKem768;
+
+/// Context of the BLAKE3 key derivation of the session keys.
+const KEY_CONTEXT: &str = "ucoin/transport/v1 session key";
+
+/// Reasons a session cannot be set up or a frame cannot be opened.
+#[derive(Debug)]
+pub enum TransportError {
+    /// liboqs failed, e.g. because ML-KEM is disabled in this build.
+    Kem(oqs::Error),
+    /// The peer's KEM public key has the wrong length.
+    BadKemKey,
+    /// The peer's KEM ciphertext has the wrong length.
+    BadCiphertext,
+    /// A frame failed to decrypt: it was tampered with, replayed or reordered.
+    Decrypt,
+}
+
+impl fmt::Display for TransportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Kem(e) => write!(f, "key encapsulation failed: {e}"),
+            Self::BadKemKey => write!(f, "malformed KEM public key"),
+            Self::BadCiphertext => write!(f, "malformed KEM ciphertext"),
+            Self::Decrypt => write!(f, "frame failed to decrypt"),
+        }
+    }
+}
+
+impl std::error::Error for TransportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Kem(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<oqs::Error> for TransportError {
+    fn from(e: oqs::Error) -> Self {
+        Self::Kem(e)
+    }
+}
+
+/// Ephemeral KEM keypair of one handshake.
+pub struct KemKeypair {
+    public: Vec<u8>,
+    secret: kem::SecretKey,
+}
+
+impl KemKeypair {
+    pub fn generate() -> Result<Self, TransportError> {
+        let (public, secret) = Kem::new(KEM)?.keypair()?;
+
+        Ok(Self {
+            public: public.as_ref().to_vec(),
+            secret,
+        })
+    }
+
+    pub fn public_key(&self) -> &[u8] {
+        &self.public
+    }
+
+    /// Recovers the secret the peer encapsulated in `ciphertext`.
+    pub fn decapsulate(&self, ciphertext: &[u8]) -> Result<Zeroizing<Vec<u8>>, TransportError> {
+        let kem = Kem::new(KEM)?;
+        let ciphertext = kem
+            .ciphertext_from_bytes(ciphertext)
+            .ok_or(TransportError::BadCiphertext)?;
+        let secret = kem.decapsulate(&self.secret, ciphertext)?;
+
+        Ok(Zeroizing::new(secret.as_ref().to_vec()))
+    }
+}
+
+/// Encapsulates a fresh secret to the peer's KEM key. Returns the ciphertext and the secret.
+pub fn encapsulate(public_key: &[u8]) -> Result<(Vec<u8>, Zeroizing<Vec<u8>>), TransportError> {
+    let kem = Kem::new(KEM)?;
+    let public_key = kem
+        .public_key_from_bytes(public_key)
+        .ok_or(TransportError::BadKemKey)?;
+    let (ciphertext, secret) = kem.encapsulate(public_key)?;
+
+    Ok((
+        ciphertext.as_ref().to_vec(),
+        Zeroizing::new(secret.as_ref().to_vec()),
+    ))
+}
+
+/// Key of the direction from the node that sent `from_hello` to the one that sent `to_hello`.
+/// `from_secret` is the secret the sending node encapsulated, `to_secret` the one it received.
+fn direction_key(
+    from_hello: &[u8],
+    to_hello: &[u8],
+    from_secret: &[u8],
+    to_secret: &[u8],
+) -> Zeroizing<[u8; 32]> {
+    let mut hasher = blake3::Hasher::new_derive_key(KEY_CONTEXT);
+    for part in [from_hello, to_hello, from_secret, to_secret] {
+        hasher.update(&(part.len() as u64).to_le_bytes());
+        hasher.update(part);
+    }
+
+    Zeroizing::new(*hasher.finalize().as_bytes())
+}
+
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = Nonce::default();
+    nonce[..8].copy_from_slice(&counter.to_le_bytes());
+
+    nonce
+}
+
+/// Encrypts the frames going to the peer.
+pub struct Sealer {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl Sealer {
+    pub fn seal(&mut self, plaintext: &[u8]) -> Vec<u8> {
+        let nonce = nonce(self.counter);
+        self.counter += 1;
+
+        self.cipher
+            .encrypt(&nonce, plaintext)
+            .expect("encrypting in memory cannot fail")
+    }
+}
+
+/// Decrypts the frames coming from the peer.
+pub struct Opener {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl Opener {
+    pub fn open(&mut self, frame: &[u8]) -> Result<Vec<u8>, TransportError> {
+        let plaintext = self
+            .cipher
+            .decrypt(&nonce(self.counter), frame)
+            .map_err(|_| TransportError::Decrypt)?;
+        self.counter += 1;
+
+        Ok(plaintext)
+    }
+}
+
+/// An authenticated, encrypted session with a peer.
+pub struct Session {
+    peer: AccountKey,
+    sealer: Sealer,
+    opener: Opener,
+}
+
+impl Session {
+    /// Derives the session keys from the encoded hellos of both sides, the secret this node
+    /// encapsulated to the peer and the one the peer encapsulated to this node.
+    pub fn new(
+        peer: AccountKey,
+        own_hello: &[u8],
+        peer_hello: &[u8],
+        sent_secret: &[u8],
+        received_secret: &[u8],
+    ) -> Self {
+        let outgoing = direction_key(own_hello, peer_hello, sent_secret, received_secret);
+        let incoming = direction_key(peer_hello, own_hello, received_secret, sent_secret);
+
+        Self {
+            peer,
+            sealer: Sealer {
+                cipher: ChaCha20Poly1305::new(outgoing.as_ref().into()),
+                counter: 0,
+            },
+            opener: Opener {
+                cipher: ChaCha20Poly1305::new(incoming.as_ref().into()),
+                counter: 0,
+            },
+        }
+    }
+
+    /// Authenticated key of the peer.
+    pub fn peer(&self) -> &AccountKey {
+        &self.peer
+    }
+
+    pub fn sealer(&mut self) -> &mut Sealer {
+        &mut self.sealer
+    }
+
+    pub fn opener(&mut self) -> &mut Opener {
+        &mut self.opener
+    }
+
+    /// Splits the session to send and receive from separate tasks.
+    pub fn into_parts(self) -> (AccountKey, Sealer, Opener) {
+        (self.peer, self.sealer, self.opener)
+    }
+}
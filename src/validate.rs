@@ -0,0 +1,135 @@
+//! Structural rules a transaction must follow before it enters the DAG.
+//!
+//! These rules only look at the transaction itself, not at the DAG or the world state, so a
+//! violation means the transaction can never be accepted. [`Rules::validate`] runs every rule
+//! and reports all violations at once.
+//!
+//! Whether a transaction is older than one of its parents depends on which parents a node holds
+//! when it arrives, so refusing it would leave nodes with different DAGs. The
+//! [`Ledger`](crate::ledger::Ledger) stores such a transaction and rejects it once its parents
+//! connect it.
+
+use std::{collections::HashSet, fmt, time::Duration};
+
+use crate::{crypto::TxHash, dag::Dag, transaction::Transaction};
+
+/// Default for [`Rules::max_parents`]. Leaves room above the tips picked by a
+/// [`TipSelector`](crate::tips::TipSelector).
+pub const DEFAULT_MAX_PARENTS: usize = 8;
+
+/// Default for [`Rules::max_clock_drift`].
+pub const DEFAULT_MAX_CLOCK_DRIFT: Duration = Duration::from_secs(5 * 60);
+
+/// A broken rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Violation {
+    /// More parents than [`Rules::max_parents`].
+    TooManyParents { max: usize, actual: usize },
+    /// The parent is listed more than once.
+    DuplicateParent(TxHash),
+    /// The sender pays itself.
+    SelfTransfer,
+    /// The transaction moves nothing.
+    ZeroAmount,
+    /// The timestamp is ahead of the local clock by more than [`Rules::max_clock_drift`].
+    FutureTimestamp { timestamp: u64, now: u64 },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyParents { max, actual } => {
+                write!(f, "{actual} parents, at most {max} allowed")
+            }
+            Self::DuplicateParent(hash) => write!(f, "duplicate parent {hash}"),
+            Self::SelfTransfer => write!(f, "sender and receiver are the same"),
+            Self::ZeroAmount => write!(f, "zero amount"),
+            Self::FutureTimestamp { timestamp, now } => {
+                write!(f, "timestamp {timestamp} is too far ahead of {now}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// Configurable limits of the structural rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rules {
+    /// Most parents a transaction may reference.
+    pub max_parents: usize,
+    /// How far a timestamp may be ahead of the local clock, to allow for clock skew between
+    /// nodes.
+    pub max_clock_drift: Duration,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            max_parents: DEFAULT_MAX_PARENTS,
+            max_clock_drift: DEFAULT_MAX_CLOCK_DRIFT,
+        }
+    }
+}
+
+impl Rules {
+    /// Checks `tx` against every rule, with `now` as the local time in milliseconds since the
+    /// Unix epoch.
+    pub fn validate(&self, tx: &Transaction, now: u64) -> Result<(), Vec<Violation>> {
+        let mut violations = Vec::new();
+
+        let parents = tx.parents();
+        if parents.len() > self.max_parents {
+            violations.push(Violation::TooManyParents {
+                max: self.max_parents,
+                actual: parents.len(),
+            });
+        }
+
+        let mut seen = HashSet::new();
+        let mut duplicates = HashSet::new();
+        for parent in parents {
+            if !seen.insert(parent) && duplicates.insert(parent) {
+                violations.push(Violation::DuplicateParent(*parent));
+            }
+        }
+
+        if tx.sender_address() == *tx.receiver() {
+            violations.push(Violation::SelfTransfer);
+        }
+
+        if tx.amount() == 0 {
+            violations.push(Violation::ZeroAmount);
+        }
+
+        let drift = self.max_clock_drift.as_millis() as u64;
+        if tx.timestamp() > now.saturating_add(drift) {
+            violations.push(Violation::FutureTimestamp {
+                timestamp: tx.timestamp(),
+                now,
+            });
+        }
+
+        match violations.is_empty() {
+            true => Ok(()),
+            false => Err(violations),
+        }
+    }
+}
+
+/// Connected parents of `tx` with a later timestamp, each listed once.
+pub(crate) fn newer_parents<'a>(
+    tx: &'a Transaction,
+    dag: &'a Dag,
+) -> impl Iterator<Item = TxHash> + 'a {
+    let mut seen = HashSet::new();
+
+    tx.parents()
+        .iter()
+        .filter(move |parent| seen.insert(**parent))
+        .filter(move |parent| {
+            dag.get(parent)
+                .is_some_and(|p| p.transaction().timestamp() > tx.timestamp())
+        })
+        .copied()
+}
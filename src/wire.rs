@@ -0,0 +1,184 @@
+//! Compact binary wire format for transactions.
+//!
+//! Unlike JSON, keys, hashes and signatures travel as raw bytes and integers as LEB128 varints:
+//!
+//! ```text
+//! u8(VERSION) || u8(network)
+//! varint(len(parents)) || parent[32]*
+//! sender
+//! varint(sequence) || varint(timestamp) || varint(amount)
+//! receiver[34]
+//! varint(len(signatures)) || (u8(index) || varint(len(signature)) || signature)*
+//! ```
+//!
+//! where the sender is either a single key or a multisig policy:
+//!
+//! ```text
+//! u8(algorithm) || varint(len(public key)) || public key
+//! u8(0x80) || u8(threshold) || varint(len(keys)) || (u8(algorithm) || varint(len(key)) || key)*
+//! ```
+
+use std::fmt;
+
+use crate::{address::AddressError, multisig::PolicyError};
+
+/// Version byte that starts every encoded transaction.
+pub const VERSION: u8 = 1;
+
+/// Reasons a wire-format transaction can fail to encode or decode.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WireError {
+    /// The receiver address is malformed.
+    BadAddress(AddressError),
+    /// The input ended in the middle of a field.
+    UnexpectedEof,
+    /// A varint does not fit in 64 bits.
+    VarintOverflow,
+    /// The input continues after the transaction.
+    TrailingBytes(usize),
+    /// The version byte is not [`VERSION`].
+    UnknownVersion(u8),
+    UnknownNetwork(u8),
+    UnknownAlgorithm(u8),
+    /// The sender's multisig policy is invalid.
+    BadPolicy(PolicyError),
+    /// A peer message has an unknown type byte.
+    UnknownMessage(u8),
+}
+
+impl fmt::Display for WireError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadAddress(e) => write!(f, "bad address: {e}"),
+            Self::UnexpectedEof => write!(f, "unexpected end of input"),
+            Self::VarintOverflow => write!(f, "varint overflows 64 bits"),
+            Self::TrailingBytes(n) => write!(f, "{n} trailing bytes"),
+            Self::UnknownVersion(v) => write!(f, "unknown version {v}"),
+            Self::UnknownNetwork(id) => write!(f, "unknown network {id}"),
+            Self::UnknownAlgorithm(id) => write!(f, "unknown signature algorithm {id}"),
+            Self::BadPolicy(e) => write!(f, "bad multisig policy: {e}"),
+            Self::UnknownMessage(kind) => write!(f, "unknown message type {kind}"),
+        }
+    }
+}
+
+impl std::error::Error for WireError {}
+
+impl From<AddressError> for WireError {
+    fn from(e: AddressError) -> Self {
+        Self::BadAddress(e)
+    }
+}
+
+impl From<PolicyError> for WireError {
+    fn from(e: PolicyError) -> Self {
+        Self::BadPolicy(e)
+    }
+}
+
+/// Append-only writer for the wire format.
+#[derive(Debug, Default)]
+pub struct Writer {
+    buf: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn u8(&mut self, value: u8) -> &mut Self {
+        self.buf.push(value);
+        self
+    }
+
+    pub fn varint(&mut self, mut value: u64) -> &mut Self {
+        while value >= 0x80 {
+            self.buf.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+        self
+    }
+
+    /// Writes a varint length-prefixed byte string.
+    pub fn bytes(&mut self, value: &[u8]) -> &mut Self {
+        self.varint(value.len() as u64);
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    /// Writes raw bytes without a length prefix.
+    pub fn raw(&mut self, value: &[u8]) -> &mut Self {
+        self.buf.extend_from_slice(value);
+        self
+    }
+
+    pub fn finish(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+/// Cursor over a wire-format buffer.
+#[derive(Debug)]
+pub struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        Self { buf }
+    }
+
+    pub fn u8(&mut self) -> Result<u8, WireError> {
+        Ok(self.raw(1)?[0])
+    }
+
+    pub fn varint(&mut self) -> Result<u64, WireError> {
+        let mut value = 0u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            let bits = (byte & 0x7f) as u64;
+
+            if shift == 63 && bits > 1 {
+                return Err(WireError::VarintOverflow);
+            }
+
+            value |= bits << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        Err(WireError::VarintOverflow)
+    }
+
+    /// Reads a varint length-prefixed byte string.
+    pub fn bytes(&mut self) -> Result<&'a [u8], WireError> {
+        let len = self.varint()?;
+        let len = usize::try_from(len).map_err(|_| WireError::UnexpectedEof)?;
+
+        self.raw(len)
+    }
+
+    /// Reads exactly `len` raw bytes.
+    pub fn raw(&mut self, len: usize) -> Result<&'a [u8], WireError> {
+        if self.buf.len() < len {
+            return Err(WireError::UnexpectedEof);
+        }
+
+        let (head, tail) = self.buf.split_at(len);
+        self.buf = tail;
+
+        Ok(head)
+    }
+
+    /// Fails if any input is left unread.
+    pub fn finish(self) -> Result<(), WireError> {
+        match self.buf.len() {
+            0 => Ok(()),
+            n => Err(WireError::TrailingBytes(n)),
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Parsing addresses, and catching typos and addresses of another network.
+
+use ucoin::{
+    address::{AddressError, AddressKind, UnknownNetwork},
+    Address, Keystore, Network, SignatureAlgorithm,
+};
+
+mod common;
+
+use common::ALGORITHM;
+
+const BASE58: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+fn address(network: Network) -> Address {
+    Keystore::generate(ALGORITHM).unwrap().address(network)
+}
+
+#[test]
+fn addresses_parse_back() {
+    for network in [Network::Mainnet, Network::Testnet] {
+        for algorithm in [
+            ALGORITHM,
+            SignatureAlgorithm::Falcon1024,
+            SignatureAlgorithm::Ed25519Falcon512,
+        ] {
+            let address = Keystore::generate(algorithm).unwrap().address(network);
+            let text = address.to_string();
+            assert!(text.starts_with(&format!("{}_", network.prefix())));
+            assert_eq!(address.kind(), AddressKind::Key(algorithm));
+
+            assert_eq!(text.parse::<Address>(), Ok(address));
+            assert_eq!(Address::from_bytes(&address.to_bytes()), Ok(address));
+            let json = serde_json::to_string(&address).unwrap();
+            assert_eq!(json, format!("\"{text}\""));
+            assert_eq!(serde_json::from_str::<Address>(&json).unwrap(), address);
+        }
+    }
+}
+
+#[test]
+fn addresses_commit_to_the_algorithm() {
+    let (public_key, _) = ALGORITHM.keypair().unwrap();
+
+    let falcon512 = Address::from_public_key(Network::Mainnet, ALGORITHM, &public_key);
+    let falcon1024 = Address::from_public_key(
+        Network::Mainnet,
+        SignatureAlgorithm::Falcon1024,
+        &public_key,
+    );
+    assert_ne!(falcon512.hash(), falcon1024.hash());
+    assert_ne!(falcon512.to_string(), falcon1024.to_string());
+}
+
+#[test]
+fn mistyped_characters_are_detected() {
+    let text = address(Network::Mainnet).to_string();
+    let start = text.find('_').unwrap() + 1;
+
+    for i in start..text.len() {
+        for c in BASE58.chars().filter(|c| !text[i..].starts_with(*c)) {
+            let typo = format!("{}{c}{}", &text[..i], &text[i + 1..]);
+            assert!(
+                matches!(
+                    typo.parse::<Address>(),
+                    Err(AddressError::BadChecksum | AddressError::BadLength(_))
+                ),
+                "{typo}"
+            );
+        }
+    }
+}
+
+#[test]
+fn swapped_characters_are_detected() {
+    let text = address(Network::Mainnet).to_string();
+    let start = text.find('_').unwrap() + 1;
+    let bytes = text.as_bytes();
+
+    for i in start..text.len() - 1 {
+        if bytes[i] == bytes[i + 1] {
+            continue;
+        }
+
+        let mut swapped = bytes.to_vec();
+        swapped.swap(i, i + 1);
+        let swapped = String::from_utf8(swapped).unwrap();
+        assert!(
+            matches!(
+                swapped.parse::<Address>(),
+                Err(AddressError::BadChecksum | AddressError::BadLength(_))
+            ),
+            "{swapped}"
+        );
+    }
+}
+
+#[test]
+fn addresses_of_another_network_are_detected() {
+    let mainnet = address(Network::Mainnet).to_string();
+    let testnet = address(Network::Testnet).to_string();
+
+    let moved = mainnet.replacen("uc_", "tuc_", 1);
+    assert_eq!(moved.parse::<Address>(), Err(AddressError::BadChecksum));
+    let moved = testnet.replacen("tuc_", "uc_", 1);
+    assert_eq!(moved.parse::<Address>(), Err(AddressError::BadChecksum));
+
+    for unknown in [
+        mainnet.replacen("uc_", "xuc_", 1),
+        mainnet.replacen("uc_", "UC_", 1),
+        mainnet.replacen('_', "", 1),
+    ] {
+        assert_eq!(
+            unknown.parse::<Address>(),
+            Err(AddressError::UnknownPrefix),
+            "{unknown}"
+        );
+    }
+}
+
+#[test]
+fn malformed_addresses_are_refused() {
+    let text = address(Network::Mainnet).to_string();
+
+    // `0` is not a Base58 character.
+    let bad = format!("{}0", &text[..text.len() - 1]);
+    assert!(matches!(
+        bad.parse::<Address>(),
+        Err(AddressError::BadEncoding(_))
+    ));
+
+    assert!(matches!(
+        text[..text.len() - 2].parse::<Address>(),
+        Err(AddressError::BadLength(_))
+    ));
+    assert!(matches!(
+        format!("{text}zzzz").parse::<Address>(),
+        Err(AddressError::BadLength(_))
+    ));
+
+    let mut bytes = address(Network::Mainnet).to_bytes();
+    bytes[1] = 0x7f;
+    assert_eq!(
+        Address::from_bytes(&bytes),
+        Err(AddressError::UnknownKind(0x7f))
+    );
+    bytes[0] = 9;
+    assert_eq!(
+        Address::from_bytes(&bytes),
+        Err(AddressError::UnknownNetwork(9))
+    );
+}
+
+#[test]
+fn networks_parse_by_name() {
+    for network in [Network::Mainnet, Network::Testnet] {
+        assert_eq!(network.to_string(), network.name());
+        assert_eq!(network.name().parse(), Ok(network));
+    }
+
+    assert_eq!(
+        "Mainnet".parse::<Network>(),
+        Err(UnknownNetwork("Mainnet".to_owned()))
+    );
+}
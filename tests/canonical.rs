@@ -0,0 +1,43 @@
+//! Golden vectors for the canonical transaction encoding.
+//!
+//! Any change to these values is a consensus-breaking change to signatures and transaction IDs.
+
+use serde::Deserialize;
+use ucoin::SignedTransaction;
+
+#[derive(Deserialize)]
+struct Vector {
+    name: String,
+    transaction: SignedTransaction,
+    signing_message: String,
+    hash: String,
+}
+
+fn vectors() -> Vec<Vector> {
+    serde_json::from_str(include_str!("vectors/canonical.json")).unwrap()
+}
+
+#[test]
+fn signing_message_matches_vectors() {
+    for vector in vectors() {
+        let message = vector.transaction.transaction().signing_message();
+        assert_eq!(
+            hex::encode(message),
+            vector.signing_message,
+            "{}",
+            vector.name
+        );
+    }
+}
+
+#[test]
+fn hash_matches_vectors() {
+    for vector in vectors() {
+        assert_eq!(
+            vector.transaction.hash().to_string(),
+            vector.hash,
+            "{}",
+            vector.name
+        );
+    }
+}
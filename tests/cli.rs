@@ -0,0 +1,205 @@
+//! The `ucoin` command-line wallet, run as a separate process.
+
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use serde_json::Value;
+use ucoin::{Keystore, Network, Transaction, WorldState};
+
+mod common;
+
+use common::{TempDir, ALGORITHM};
+
+const NETWORK: &str = "testnet";
+
+/// What a `ucoin` run printed.
+struct Output {
+    success: bool,
+    /// Standard output, parsed as JSON.
+    json: Value,
+    stderr: String,
+}
+
+/// Runs `ucoin --json` on the state directory `dir`, writing `stdin` to its standard input.
+fn ucoin(dir: &Path, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_ucoin"))
+        .args(["--json", "--network", NETWORK, "--dir"])
+        .arg(dir)
+        .args(args)
+        .env("UCOIN_PASSWORD", "correct horse battery staple")
+        .env_remove("UCOIN_DIR")
+        .env_remove("UCOIN_KEYSTORE")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    Output {
+        success: output.status.success(),
+        json: serde_json::from_str(&stdout).unwrap_or(Value::Null),
+        stderr: String::from_utf8(output.stderr).unwrap(),
+    }
+}
+
+/// Creates a keystore in `dir` with cheap key derivation, and returns its output.
+fn keygen(dir: &Path, args: &[&str]) -> Output {
+    let cheap = ["keygen", "--kdf-memory", "8", "--kdf-iterations", "1"];
+    let output = ucoin(dir, &[&cheap, args].concat(), "");
+    assert!(output.success, "{}", output.stderr);
+
+    output
+}
+
+fn address(output: &Output) -> String {
+    output.json["address"].as_str().unwrap().to_owned()
+}
+
+#[test]
+fn keystores_are_created_once() {
+    let dir = TempDir::new();
+    let created = keygen(dir.path(), &[]);
+    assert_eq!(created.json["mnemonic"], Value::Null);
+    assert!(address(&created).starts_with("tuc_"));
+
+    let shown = ucoin(dir.path(), &["address"], "");
+    assert_eq!(shown.json["address"], created.json["address"]);
+
+    let again = ucoin(dir.path(), &["keygen"], "");
+    assert!(!again.success);
+    assert!(again.json["error"].as_str().unwrap().contains("--force"));
+
+    let replaced = keygen(dir.path(), &["--force"]);
+    assert_ne!(address(&replaced), address(&created));
+}
+
+#[test]
+fn seed_phrases_stay_off_standard_output_unless_asked() {
+    let dir = TempDir::new();
+    let created = keygen(dir.path(), &["--mnemonic", "--words", "12"]);
+    assert_eq!(created.json["mnemonic"], Value::Null);
+    let phrase = created.stderr.lines().last().unwrap().to_owned();
+    assert_eq!(phrase.split(' ').count(), 12);
+
+    let restored = TempDir::new();
+    let cheap = [
+        "keygen",
+        "--restore",
+        "--kdf-memory",
+        "8",
+        "--kdf-iterations",
+        "1",
+    ];
+    let output = ucoin(restored.path(), &cheap, &format!("{phrase}\n"));
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(address(&output), address(&created));
+
+    let printed = TempDir::new();
+    let output = keygen(printed.path(), &["--mnemonic", "--print-mnemonic"]);
+    let phrase = output.json["mnemonic"].as_str().unwrap();
+    assert_eq!(phrase.split(' ').count(), 24);
+    assert!(!output.stderr.contains(phrase));
+
+    let output = ucoin(TempDir::new().path(), &["keygen", "--print-mnemonic"], "");
+    assert!(!output.success);
+}
+
+#[test]
+fn payments_show_in_balances_and_history() {
+    let dir = TempDir::new();
+    let address = address(&keygen(dir.path(), &[]));
+
+    let mut genesis = WorldState::new(Network::Testnet);
+    genesis.credit(address.parse().unwrap(), 100).unwrap();
+    fs::write(
+        dir.path().join("genesis.json"),
+        serde_json::to_string(&genesis).unwrap(),
+    )
+    .unwrap();
+
+    let receiver = Keystore::generate(ALGORITHM).unwrap();
+    let to = receiver.address(Network::Testnet).to_string();
+    let sent = ucoin(dir.path(), &["send", &to, "10"], "");
+    assert!(sent.success, "{}", sent.stderr);
+    assert_eq!(sent.json["status"], "accepted");
+
+    let balance = ucoin(dir.path(), &["balance"], "");
+    assert_eq!(balance.json["balance"], 90);
+    assert_eq!(balance.json["sequence"], 1);
+    let balance = ucoin(dir.path(), &["balance", &to], "");
+    assert_eq!(balance.json["balance"], 10);
+
+    let history = ucoin(dir.path(), &["history"], "");
+    assert_eq!(history.json.as_array().unwrap().len(), 1);
+    assert_eq!(history.json[0]["hash"], sent.json["hash"]);
+    assert_eq!(history.json[0]["direction"], "out");
+    assert_eq!(history.json[0]["counterparty"], to.as_str());
+    assert_eq!(history.json[0]["amount"], 10);
+    let history = ucoin(dir.path(), &["history", &to], "");
+    assert_eq!(history.json[0]["direction"], "in");
+    assert_eq!(history.json[0]["counterparty"], address.as_str());
+
+    let overdraft = ucoin(dir.path(), &["send", &to, "1000"], "");
+    assert!(!overdraft.success);
+
+    let mainnet = receiver.address(Network::Mainnet).to_string();
+    let elsewhere = ucoin(dir.path(), &["send", &mainnet, "1"], "");
+    assert!(!elsewhere.success);
+    assert_eq!(ucoin(dir.path(), &["balance"], "").json["balance"], 90);
+}
+
+#[test]
+fn transactions_are_signed_and_verified_offline() {
+    let dir = TempDir::new();
+    keygen(dir.path(), &[]);
+    let key = Keystore::read_account_key(dir.path().join("keystore.json")).unwrap();
+
+    let receiver = Keystore::generate(ALGORITHM)
+        .unwrap()
+        .address(Network::Testnet);
+    let tx = Transaction::new(Network::Testnet, &[], &key.into(), 0, 5, &receiver);
+    let unsigned = dir.path().join("unsigned.json");
+    fs::write(&unsigned, serde_json::to_string(&tx).unwrap()).unwrap();
+
+    let signed = dir.path().join("signed.json");
+    let output = ucoin(
+        dir.path(),
+        &[
+            "sign-tx",
+            unsigned.to_str().unwrap(),
+            "--out",
+            signed.to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.success, "{}", output.stderr);
+
+    let output = ucoin(dir.path(), &["verify-tx", signed.to_str().unwrap()], "");
+    assert!(output.success, "{}", output.stderr);
+    assert_eq!(output.json["valid"], true);
+    assert_eq!(output.json["amount"], 5);
+
+    // Read from standard input, with a changed amount.
+    let mut tampered: Value = serde_json::from_str(&fs::read_to_string(&signed).unwrap()).unwrap();
+    tampered["transaction"]["amount"] = 50.into();
+    let output = ucoin(dir.path(), &["verify-tx", "-"], &tampered.to_string());
+    assert!(!output.success);
+
+    // Another keystore cannot sign for this one.
+    let other = TempDir::new();
+    keygen(other.path(), &[]);
+    let output = ucoin(other.path(), &["sign-tx", unsigned.to_str().unwrap()], "");
+    assert!(!output.success);
+}
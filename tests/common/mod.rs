@@ -0,0 +1,154 @@
+//! Accounts, transactions, nodes and directories shared by the integration tests.
+
+#![allow(dead_code)]
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use ucoin::{
+    ledger::Status, Address, Keystore, Ledger, Network, Node, SignatureAlgorithm,
+    SignedTransaction, Transaction, TxHash, WorldState,
+};
+
+pub const NETWORK: Network = Network::Testnet;
+pub const ALGORITHM: SignatureAlgorithm = SignatureAlgorithm::Falcon512;
+
+pub struct Fixture {
+    pub account: Keystore,
+    /// Receives the fixture's transfers.
+    pub receiver: Address,
+    /// Credits the fixture's account with 1000.
+    pub genesis: WorldState,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        let account = Keystore::generate(ALGORITHM).unwrap();
+        let mut genesis = WorldState::new(NETWORK);
+        genesis.credit(account.address(NETWORK), 1_000).unwrap();
+        let receiver = Keystore::generate(ALGORITHM).unwrap().address(NETWORK);
+
+        Self {
+            account,
+            receiver,
+            genesis,
+        }
+    }
+
+    pub async fn node(&self) -> Node {
+        self.node_with(Ledger::new(self.genesis.clone())).await
+    }
+
+    pub async fn node_with(&self, ledger: Ledger) -> Node {
+        let identity = Keystore::generate(ALGORITHM).unwrap();
+
+        Node::start(NETWORK, identity, ledger, "127.0.0.1:0")
+            .await
+            .unwrap()
+    }
+
+    /// An unsigned payment of `amount` from `from` to `to`.
+    pub fn payment(
+        from: &Keystore,
+        parents: &[TxHash],
+        sequence: u64,
+        amount: u64,
+        to: &Address,
+    ) -> Transaction {
+        Transaction::new(
+            NETWORK,
+            parents,
+            &from.account_key().into(),
+            sequence,
+            amount,
+            to,
+        )
+    }
+
+    /// [`Fixture::payment`], signed by `from`.
+    pub fn pay(
+        from: &Keystore,
+        parents: &[TxHash],
+        sequence: u64,
+        amount: u64,
+        to: &Address,
+    ) -> SignedTransaction {
+        Self::payment(from, parents, sequence, amount, to)
+            .sign(from.secret_key())
+            .unwrap()
+    }
+
+    /// A payment of 1 from the fixture's account to its receiver.
+    pub fn transfer(&self, parents: &[TxHash], sequence: u64) -> SignedTransaction {
+        Self::pay(&self.account, parents, sequence, 1, &self.receiver)
+    }
+
+    /// `len` transfers, each the only child of the one before.
+    pub fn chain(&self, len: u64) -> Vec<SignedTransaction> {
+        let mut chain: Vec<SignedTransaction> = Vec::new();
+        for sequence in 0..len {
+            let parents: Vec<_> = chain.last().map(|tx| tx.hash()).into_iter().collect();
+            chain.push(self.transfer(&parents, sequence));
+        }
+
+        chain
+    }
+}
+
+/// Waits up to five seconds for `condition` to hold.
+pub async fn eventually(condition: impl Fn() -> bool) {
+    for _ in 0..200 {
+        if condition() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(25)).await;
+    }
+
+    panic!("condition not reached");
+}
+
+pub fn status(node: &Node, hash: &TxHash) -> Option<Status> {
+    node.with_ledger(|ledger| ledger.status(hash))
+}
+
+/// `tx` dated `timestamp` instead, to be signed again.
+pub fn with_timestamp(tx: Transaction, timestamp: u64) -> Transaction {
+    let mut value = serde_json::to_value(tx).unwrap();
+    value["timestamp"] = timestamp.into();
+
+    serde_json::from_value(value).unwrap()
+}
+
+/// An empty directory under the system temporary directory, removed on drop.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+
+        let name = format!(
+            "ucoin-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let dir = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        Self(dir)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,167 @@
+//! Hashes, keys and signatures, and their Base64 representations.
+
+use ucoin::crypto::{
+    self, Digest, ParseError, PublicKey, Signature, SignatureAlgorithm, TxHash, UnknownAlgorithm,
+};
+
+fn public_key() -> PublicKey {
+    crypto::init();
+    SignatureAlgorithm::default().keypair().unwrap().0
+}
+
+#[test]
+fn hashes_and_digests_round_trip_through_base64() {
+    let hash = TxHash::from_bytes([7; 32]);
+    let text = hash.to_string();
+    assert_eq!(text, "BwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwcHBwc=");
+    assert_eq!(text.parse(), Ok(hash));
+    assert_eq!(format!("{hash:?}"), format!("TxHash({text})"));
+
+    let json = serde_json::to_string(&hash).unwrap();
+    assert_eq!(json, format!("\"{text}\""));
+    assert_eq!(serde_json::from_str::<TxHash>(&json).unwrap(), hash);
+
+    // Digests share the representation, under their own name.
+    let digest = Digest::from_bytes([7; 32]);
+    assert_eq!(digest.to_string(), text);
+    assert_eq!(text.parse(), Ok(digest));
+    assert_eq!(format!("{digest:?}"), format!("Digest({text})"));
+    let json = serde_json::to_string(&digest).unwrap();
+    assert_eq!(serde_json::from_str::<Digest>(&json).unwrap(), digest);
+}
+
+#[test]
+fn hashes_and_digests_must_be_32_bytes() {
+    for len in [0, 31, 33] {
+        // Any byte string encodes like a key.
+        let text = PublicKey::from_bytes(vec![7; len]).to_string();
+        assert_eq!(
+            text.parse::<TxHash>(),
+            Err(ParseError::BadLength {
+                expected: 32,
+                actual: len
+            })
+        );
+        assert!(serde_json::from_str::<TxHash>(&format!("\"{text}\"")).is_err());
+        assert_eq!(
+            text.parse::<Digest>(),
+            Err(ParseError::BadLength {
+                expected: 32,
+                actual: len
+            })
+        );
+    }
+
+    assert!(matches!(
+        "not base64!".parse::<TxHash>(),
+        Err(ParseError::BadEncoding(_))
+    ));
+}
+
+#[test]
+fn keys_and_signatures_round_trip_through_base64() {
+    let key = public_key();
+    let text = key.to_string();
+    assert_eq!(text.parse(), Ok(key.clone()));
+    let json = serde_json::to_string(&key).unwrap();
+    assert_eq!(json, format!("\"{text}\""));
+    assert_eq!(serde_json::from_str::<PublicKey>(&json).unwrap(), key);
+
+    let signature = Signature::from_bytes(vec![1, 2, 3, 4]);
+    assert_eq!(signature.to_string(), "AQIDBA==");
+    assert_eq!("AQIDBA==".parse(), Ok(signature.clone()));
+    assert_eq!(format!("{signature:?}"), "Signature(AQIDBA==)");
+    let json = serde_json::to_string(&signature).unwrap();
+    assert_eq!(serde_json::from_str::<Signature>(&json).unwrap(), signature);
+
+    assert!(matches!(
+        "AQIDBA".parse::<Signature>(),
+        Err(ParseError::BadEncoding(_))
+    ));
+}
+
+#[test]
+fn equality_compares_the_bytes() {
+    let key = public_key();
+    assert_eq!(key, PublicKey::from_bytes(key.as_ref()));
+    assert_ne!(key, public_key());
+    assert_ne!(key, PublicKey::from_bytes(&key.as_ref()[1..]));
+
+    assert_eq!(
+        Signature::from_bytes([1, 2]),
+        Signature::from_bytes(vec![1, 2])
+    );
+    assert_ne!(Signature::from_bytes([1, 2]), Signature::from_bytes([1, 3]));
+    assert_ne!(
+        Signature::from_bytes([1, 2]),
+        Signature::from_bytes([1, 2, 3])
+    );
+
+    assert_eq!(TxHash::from_bytes([1; 32]), TxHash::from_bytes([1; 32]));
+    assert_ne!(TxHash::from_bytes([1; 32]), TxHash::from_bytes([2; 32]));
+    assert_eq!(Digest::from_bytes([1; 32]), Digest::from_bytes([1; 32]));
+    assert_ne!(Digest::from_bytes([1; 32]), Digest::from_bytes([2; 32]));
+}
+
+#[test]
+fn algorithms_round_trip_through_ids_and_names() {
+    for algorithm in SignatureAlgorithm::ALL {
+        assert_eq!(SignatureAlgorithm::from_id(algorithm.id()), Some(algorithm));
+        assert_eq!(algorithm.name().parse(), Ok(algorithm));
+        assert_eq!(algorithm.to_string(), algorithm.name());
+
+        let json = serde_json::to_string(&algorithm).unwrap();
+        assert_eq!(json, format!("\"{}\"", algorithm.name()));
+        assert_eq!(
+            serde_json::from_str::<SignatureAlgorithm>(&json).unwrap(),
+            algorithm
+        );
+    }
+
+    let mut ids: Vec<_> = SignatureAlgorithm::ALL.iter().map(|a| a.id()).collect();
+    ids.sort();
+    ids.dedup();
+    assert_eq!(ids.len(), SignatureAlgorithm::ALL.len());
+}
+
+#[test]
+fn unknown_algorithms_are_refused() {
+    let largest = SignatureAlgorithm::ALL
+        .iter()
+        .map(|a| a.id())
+        .max()
+        .unwrap();
+    for id in [0, largest + 1, u8::MAX] {
+        assert_eq!(SignatureAlgorithm::from_id(id), None, "{id}");
+    }
+
+    for name in ["", "falcon", "Falcon-512", "falcon-256", "ml-dsa-65 "] {
+        assert_eq!(
+            name.parse::<SignatureAlgorithm>(),
+            Err(UnknownAlgorithm(name.to_owned()))
+        );
+        assert!(serde_json::from_str::<SignatureAlgorithm>(&format!("\"{name}\"")).is_err());
+    }
+}
+
+#[test]
+fn falcon_and_hybrids_are_always_enabled() {
+    for algorithm in [
+        SignatureAlgorithm::Falcon512,
+        SignatureAlgorithm::Falcon1024,
+        SignatureAlgorithm::Ed25519Falcon512,
+        SignatureAlgorithm::Ed25519Falcon1024,
+    ] {
+        assert!(algorithm.is_enabled());
+        assert!(algorithm.sig().is_ok());
+    }
+
+    assert_eq!(
+        SignatureAlgorithm::MlDsa65.is_enabled(),
+        cfg!(feature = "ml-dsa")
+    );
+    assert_eq!(
+        SignatureAlgorithm::SphincsSha2128f.is_enabled(),
+        cfg!(feature = "sphincs")
+    );
+}
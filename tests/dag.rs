@@ -0,0 +1,174 @@
+//! Linking transactions through their parents, with and without orphans.
+
+use std::collections::HashSet;
+
+use ucoin::{
+    dag::{DagError, Insertion, OrphanPolicy},
+    Dag, SignedTransaction, TxHash,
+};
+
+mod common;
+
+use common::Fixture;
+
+fn hashes(transactions: &[SignedTransaction]) -> Vec<TxHash> {
+    transactions.iter().map(SignedTransaction::hash).collect()
+}
+
+/// A diamond: `left` and `right` on `root`, and `bottom` on both.
+fn diamond(fixture: &Fixture) -> [SignedTransaction; 4] {
+    let root = fixture.transfer(&[], 0);
+    let left = fixture.transfer(&[root.hash()], 1);
+    let right = fixture.transfer(&[root.hash()], 2);
+    let bottom = fixture.transfer(&[left.hash(), right.hash()], 3);
+
+    [root, left, right, bottom]
+}
+
+#[test]
+fn transactions_with_known_parents_connect() {
+    let fixture = Fixture::new();
+    let [root, left, right, bottom] = diamond(&fixture);
+    let mut dag = Dag::new();
+
+    for tx in [&root, &left, &right] {
+        let insertion = dag.insert(tx.clone()).unwrap();
+        assert_eq!(insertion, Insertion::Connected(vec![tx.hash()]));
+    }
+    let mut tips = vec![left.hash(), right.hash()];
+    tips.sort();
+    assert_eq!(dag.tips().copied().collect::<Vec<_>>(), tips);
+
+    dag.insert(bottom.clone()).unwrap();
+    assert_eq!(dag.tips().collect::<Vec<_>>(), [&bottom.hash()]);
+    assert_eq!(dag.len(), 4);
+    assert_eq!(
+        dag.insert(bottom.clone()),
+        Err(DagError::Duplicate(bottom.hash()))
+    );
+}
+
+#[test]
+fn orphans_connect_once_their_parents_arrive() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(4);
+    let mut dag = Dag::new();
+
+    for tx in chain[1..].iter().rev() {
+        let parent = tx.transaction().parents()[0];
+        assert_eq!(
+            dag.insert(tx.clone()),
+            Ok(Insertion::Orphaned(vec![parent]))
+        );
+        assert!(dag.is_orphan(&tx.hash()));
+    }
+    assert!(dag.is_empty());
+    assert_eq!(
+        dag.insert(chain[3].clone()),
+        Err(DagError::Duplicate(chain[3].hash()))
+    );
+
+    let missing: HashSet<_> = dag.missing().copied().collect();
+    assert_eq!(missing, hashes(&chain[..3]).into_iter().collect());
+
+    assert_eq!(
+        dag.insert(chain[0].clone()),
+        Ok(Insertion::Connected(hashes(&chain)))
+    );
+    assert_eq!(dag.orphans().count(), 0);
+    assert_eq!(dag.missing().count(), 0);
+    assert_eq!(dag.tips().collect::<Vec<_>>(), [&chain[3].hash()]);
+}
+
+#[test]
+fn orphans_can_be_refused() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(2);
+    let mut dag = Dag::with_orphan_policy(OrphanPolicy::Reject);
+
+    assert_eq!(
+        dag.insert(chain[1].clone()),
+        Err(DagError::UnknownParents(vec![chain[0].hash()]))
+    );
+    assert!(!dag.is_orphan(&chain[1].hash()));
+}
+
+#[test]
+fn the_oldest_orphans_are_dropped_beyond_the_cap() {
+    let fixture = Fixture::new();
+    let parents = hashes(&fixture.chain(3));
+    let orphans: Vec<_> = parents
+        .iter()
+        .map(|parent| fixture.transfer(&[*parent], 5))
+        .collect();
+    let mut dag = Dag::new().with_max_orphans(2);
+
+    for orphan in &orphans {
+        dag.insert(orphan.clone()).unwrap();
+    }
+
+    assert!(!dag.is_orphan(&orphans[0].hash()));
+    assert!(dag.is_orphan(&orphans[1].hash()));
+    assert!(dag.is_orphan(&orphans[2].hash()));
+    let missing: HashSet<_> = dag.missing().copied().collect();
+    assert_eq!(missing, HashSet::from([parents[1], parents[2]]));
+
+    // The dropped orphan is not waiting on its parent anymore, but can be sent again.
+    dag.insert(orphans[0].clone()).unwrap();
+    assert!(dag.is_orphan(&orphans[0].hash()));
+    assert!(!dag.is_orphan(&orphans[1].hash()));
+}
+
+#[test]
+fn ancestors_and_descendants() {
+    let fixture = Fixture::new();
+    let [root, left, right, bottom] = diamond(&fixture);
+    let mut dag = Dag::new();
+    for tx in [&root, &left, &right, &bottom] {
+        dag.insert(tx.clone()).unwrap();
+    }
+
+    let set = |transactions: &[&SignedTransaction]| -> HashSet<TxHash> {
+        transactions.iter().map(|tx| tx.hash()).collect()
+    };
+    assert_eq!(dag.ancestors(&bottom.hash()), set(&[&root, &left, &right]));
+    assert_eq!(dag.ancestors(&left.hash()), set(&[&root]));
+    assert_eq!(dag.ancestors(&root.hash()), set(&[]));
+    assert_eq!(
+        dag.descendants(&root.hash()),
+        set(&[&left, &right, &bottom])
+    );
+    assert_eq!(dag.descendants(&right.hash()), set(&[&bottom]));
+    assert_eq!(dag.descendants(&bottom.hash()), set(&[]));
+}
+
+#[test]
+fn topological_order_puts_parents_first() {
+    let fixture = Fixture::new();
+    let [root, left, right, bottom] = diamond(&fixture);
+    let other = fixture.transfer(&[], 9);
+
+    // Insertion order does not matter, orphans included.
+    let mut expected = None;
+    for order in [
+        [&root, &left, &right, &bottom, &other],
+        [&bottom, &other, &right, &left, &root],
+    ] {
+        let mut dag = Dag::new();
+        for tx in order {
+            dag.insert(tx.clone()).unwrap();
+        }
+
+        let sorted = dag.topological_order();
+        let position = |tx: &SignedTransaction| sorted.iter().position(|h| *h == tx.hash());
+        assert!(position(&root) < position(&left));
+        assert!(position(&root) < position(&right));
+        assert!(position(&left) < position(&bottom));
+        assert!(position(&right) < position(&bottom));
+
+        // Ties between ready transactions go to the lower hash.
+        assert_eq!(sorted[0], root.hash().min(other.hash()));
+
+        assert_eq!(*expected.get_or_insert(sorted.clone()), sorted);
+    }
+}
@@ -0,0 +1,119 @@
+//! Event streams of the HTTP API on localhost.
+
+use serde_json::Value;
+use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+};
+use ucoin::{rpc::RpcServer, Keystore, SignedTransaction};
+
+mod common;
+
+use common::{Fixture, ALGORITHM, NETWORK};
+
+struct EventStream {
+    reader: BufReader<TcpStream>,
+}
+
+impl EventStream {
+    /// Sends `GET /events?query` and returns the status code, and the stream if it is 200.
+    async fn open(server: &RpcServer, query: &str) -> (u16, Option<Self>) {
+        let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+        let request = format!("GET /events?{query} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).await.unwrap();
+        let status = line.split_whitespace().nth(1).unwrap().parse().unwrap();
+        while line != "\r\n" {
+            line.clear();
+            reader.read_line(&mut line).await.unwrap();
+        }
+
+        (status, (status == 200).then_some(Self { reader }))
+    }
+
+    /// Name and data of the next event, skipping comments.
+    async fn next(&mut self) -> (String, Value) {
+        let (mut name, mut data) = (String::new(), Value::Null);
+        loop {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).await.unwrap();
+            let line = line.trim_end();
+
+            if let Some(value) = line.strip_prefix("event: ") {
+                name = value.to_owned();
+            } else if let Some(value) = line.strip_prefix("data: ") {
+                data = serde_json::from_str(value).unwrap();
+            } else if line.is_empty() && !name.is_empty() {
+                return (name, data);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn wallets_receive_payments_and_balance_changes() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node.clone(), "127.0.0.1:0").await.unwrap();
+    let other = Keystore::generate(ALGORITHM).unwrap().address(NETWORK);
+
+    let query = format!("receiver={other}&wallet={other}");
+    let (_, stream) = EventStream::open(&server, &query).await;
+    let mut stream = stream.unwrap();
+
+    let (name, data) = stream.next().await;
+    assert_eq!(name, "balance");
+    assert_eq!(data["balance"], 0);
+
+    // Neither the first transfer nor its balance events concern `other`.
+    let first = fixture.transfer(&[], 0);
+    node.submit(first.clone()).unwrap();
+    let payment = Fixture::pay(&fixture.account, &[first.hash()], 1, 10, &other);
+    node.submit(payment.clone()).unwrap();
+
+    let (name, data) = stream.next().await;
+    assert_eq!(name, "transaction");
+    assert_eq!(data["hash"], serde_json::json!(payment.hash()));
+    let streamed: SignedTransaction = serde_json::from_value(data["transaction"].clone()).unwrap();
+    assert_eq!(streamed.hash(), payment.hash());
+
+    let (name, data) = stream.next().await;
+    assert_eq!(name, "balance");
+    assert_eq!(data["address"], other.to_string());
+    assert_eq!(data["balance"], 10);
+}
+
+#[tokio::test]
+async fn senders_see_their_own_transactions() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node.clone(), "127.0.0.1:0").await.unwrap();
+    let account = fixture.account.address(NETWORK);
+
+    let (_, stream) = EventStream::open(&server, &format!("sender={account}")).await;
+    let mut stream = stream.unwrap();
+
+    let first = fixture.transfer(&[], 0);
+    node.submit(first.clone()).unwrap();
+
+    let (name, data) = stream.next().await;
+    assert_eq!(name, "transaction");
+    assert_eq!(data["hash"], serde_json::json!(first.hash()));
+}
+
+#[tokio::test]
+async fn bad_filters_are_refused() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node, "127.0.0.1:0").await.unwrap();
+    let mainnet = fixture.account.address(ucoin::Network::Mainnet);
+
+    for query in ["wallet=nope", "color=red", &format!("sender={mainnet}")] {
+        let (status, stream) = EventStream::open(&server, query).await;
+        assert_eq!(status, 400, "{query}");
+        assert!(stream.is_none());
+    }
+}
@@ -0,0 +1,209 @@
+//! Gossip between nodes on localhost.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tokio::net::TcpStream;
+use ucoin::{
+    ledger::Status,
+    node::{self, handshake},
+    protocol::{Message, MAX_BATCH},
+    Keystore, Ledger, Network, Node, NodeError, Rules, Session, TxHash, WorldState,
+};
+
+mod common;
+
+use common::{eventually, status, with_timestamp, Fixture, ALGORITHM, NETWORK};
+
+/// A bare peer speaking the protocol directly.
+struct RawPeer {
+    stream: TcpStream,
+    session: Session,
+}
+
+impl RawPeer {
+    async fn connect(node: &Node) -> Self {
+        let peers = node.peers().len();
+        let mut stream = TcpStream::connect(node.local_addr()).await.unwrap();
+        let identity = Keystore::generate(ALGORITHM).unwrap();
+        let session = handshake(&mut stream, NETWORK, &identity).await.unwrap();
+        eventually(|| node.peers().len() > peers).await;
+
+        Self { stream, session }
+    }
+
+    async fn send(&mut self, message: Message) {
+        node::send(&mut self.stream, self.session.sealer(), &message)
+            .await
+            .unwrap();
+    }
+
+    async fn receive(&mut self) -> Result<Message, NodeError> {
+        node::receive(&mut self.stream, self.session.opener()).await
+    }
+}
+
+/// Hash of the next transaction sent by the node to a bare peer.
+async fn received(peer: &mut RawPeer) -> TxHash {
+    match peer.receive().await.unwrap() {
+        Message::Transaction(tx) => tx.hash(),
+        message => panic!("unexpected {message:?}"),
+    }
+}
+
+#[tokio::test]
+async fn transactions_spread_along_a_line() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = fixture.node().await;
+    let c = fixture.node().await;
+
+    assert_eq!(b.connect(a.local_addr()).await.unwrap(), a.key());
+    c.connect(b.local_addr()).await.unwrap();
+    eventually(|| a.peers().len() == 1 && b.peers().len() == 2).await;
+
+    let first = fixture.transfer(&[], 0);
+    a.submit(first.clone()).unwrap();
+    eventually(|| status(&c, &first.hash()) == Some(Status::Accepted)).await;
+
+    let second = fixture.transfer(&[first.hash()], 1);
+    c.submit(second.clone()).unwrap();
+    eventually(|| status(&a, &second.hash()) == Some(Status::Accepted)).await;
+    assert_eq!(status(&b, &second.hash()), Some(Status::Accepted));
+}
+
+#[tokio::test]
+async fn missing_parents_are_requested() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = fixture.node().await;
+
+    let first = fixture.transfer(&[], 0);
+    let second = fixture.transfer(&[first.hash()], 1);
+    a.submit(first.clone()).unwrap();
+    a.submit(second.clone()).unwrap();
+
+    b.connect(a.local_addr()).await.unwrap();
+    eventually(|| a.peers().len() == 1).await;
+
+    let third = fixture.transfer(&[second.hash()], 2);
+    a.submit(third.clone()).unwrap();
+
+    eventually(|| status(&b, &third.hash()) == Some(Status::Accepted)).await;
+    assert_eq!(status(&b, &first.hash()), Some(Status::Accepted));
+    assert_eq!(status(&b, &second.hash()), Some(Status::Accepted));
+}
+
+#[tokio::test]
+async fn seen_transactions_are_not_forwarded_again() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let mut sender = RawPeer::connect(&a).await;
+    let mut watcher = RawPeer::connect(&a).await;
+
+    let first = fixture.transfer(&[], 0);
+    let second = fixture.transfer(&[first.hash()], 1);
+
+    sender.send(Message::Transaction(first.clone())).await;
+    assert_eq!(received(&mut watcher).await, first.hash());
+
+    sender.send(Message::Transaction(first)).await;
+    sender.send(Message::Transaction(second.clone())).await;
+    assert_eq!(received(&mut watcher).await, second.hash());
+
+    let echo = tokio::time::timeout(Duration::from_millis(200), sender.receive()).await;
+    assert!(echo.is_err(), "transaction echoed to its sender");
+}
+
+#[tokio::test]
+async fn refused_transactions_are_checked_again() {
+    let fixture = Fixture::new();
+    let ledger = Ledger::new(fixture.genesis.clone()).with_rules(Rules {
+        max_clock_drift: Duration::from_secs(1),
+        ..Rules::default()
+    });
+    let a = fixture.node_with(ledger).await;
+    let mut sender = RawPeer::connect(&a).await;
+
+    // Two seconds ahead: refused now, but no longer once a second has passed.
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+    let tx = fixture.transfer(&[], 0).transaction().clone();
+    let tx = with_timestamp(tx, now.as_millis() as u64 + 2_000)
+        .sign(fixture.account.secret_key())
+        .unwrap();
+
+    sender.send(Message::Transaction(tx.clone())).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(status(&a, &tx.hash()), None);
+
+    tokio::time::sleep(Duration::from_millis(1_200)).await;
+    sender.send(Message::Transaction(tx.clone())).await;
+    eventually(|| status(&a, &tx.hash()) == Some(Status::Accepted)).await;
+}
+
+#[tokio::test]
+async fn requests_for_transactions_are_capped() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let chain = fixture.chain(MAX_BATCH as u64 + 1);
+    for tx in &chain {
+        a.submit(tx.clone()).unwrap();
+    }
+
+    let mut peer = RawPeer::connect(&a).await;
+    let hashes = chain.iter().map(|tx| tx.hash()).collect();
+    peer.send(Message::GetTransactions(hashes)).await;
+    for tx in &chain[..MAX_BATCH] {
+        assert_eq!(received(&mut peer).await, tx.hash());
+    }
+
+    let extra = tokio::time::timeout(Duration::from_millis(200), peer.receive()).await;
+    assert!(extra.is_err(), "more than {MAX_BATCH} transactions sent");
+}
+
+#[tokio::test]
+async fn handshake_checks_the_network() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = Node::start(
+        Network::Mainnet,
+        Keystore::generate(ALGORITHM).unwrap(),
+        Ledger::new(WorldState::new(Network::Mainnet)),
+        "127.0.0.1:0",
+    )
+    .await
+    .unwrap();
+
+    let result = b.connect(a.local_addr()).await;
+    assert!(matches!(result, Err(NodeError::WrongNetwork(NETWORK))));
+    assert!(a.peers().is_empty());
+    assert!(b.peers().is_empty());
+}
+
+#[tokio::test]
+async fn nodes_do_not_connect_twice() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = fixture.node().await;
+
+    let result = a.connect(a.local_addr()).await;
+    assert!(matches!(result, Err(NodeError::SelfConnection)));
+
+    b.connect(a.local_addr()).await.unwrap();
+    let result = b.connect(a.local_addr()).await;
+    assert!(matches!(result, Err(NodeError::AlreadyConnected)));
+    assert_eq!(b.peers().len(), 1);
+}
+
+#[tokio::test]
+async fn disconnected_peers_are_dropped() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = fixture.node().await;
+
+    b.connect(a.local_addr()).await.unwrap();
+    eventually(|| a.peers().len() == 1).await;
+
+    b.shutdown();
+    assert!(b.peers().is_empty());
+    eventually(|| a.peers().is_empty()).await;
+}
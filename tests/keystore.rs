@@ -0,0 +1,158 @@
+//! Encrypted key files: round trips, wrong passwords and damaged files.
+
+use ucoin::{
+    keystore::MAX_KDF_PARAMS, KdfParams, Keystore, KeystoreError, Network, SignatureAlgorithm,
+};
+
+mod common;
+
+use common::{TempDir, ALGORITHM};
+
+/// Cheap parameters, since the tests do not need a slow key derivation.
+const PARAMS: KdfParams = KdfParams {
+    memory: 8,
+    iterations: 1,
+    parallelism: 1,
+};
+
+const PASSWORD: &str = "correct horse battery staple";
+
+fn same(a: &Keystore, b: &Keystore) -> bool {
+    a.algorithm() == b.algorithm()
+        && a.public_key() == b.public_key()
+        && a.secret_key().as_ref() == b.secret_key().as_ref()
+}
+
+/// The JSON of a keystore, edited by `edit`.
+fn edited(json: &str, edit: impl FnOnce(&mut serde_json::Value)) -> String {
+    let mut value: serde_json::Value = serde_json::from_str(json).unwrap();
+    edit(&mut value);
+
+    value.to_string()
+}
+
+#[test]
+fn saved_keystores_load_back() {
+    let dir = TempDir::new();
+    let path = dir.path().join("keystore.json");
+
+    for algorithm in [ALGORITHM, SignatureAlgorithm::Ed25519Falcon512] {
+        let keystore = Keystore::generate(algorithm).unwrap();
+        keystore.save(&path, PASSWORD, &PARAMS).unwrap();
+
+        let loaded = Keystore::load(&path, PASSWORD).unwrap();
+        assert!(same(&loaded, &keystore), "{algorithm}");
+        assert_eq!(
+            loaded.address(Network::Mainnet),
+            keystore.address(Network::Mainnet)
+        );
+        assert_eq!(
+            Keystore::read_account_key(&path).unwrap(),
+            keystore.account_key()
+        );
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn wrong_passwords_are_told_apart_from_damage() {
+    let keystore = Keystore::generate(ALGORITHM).unwrap();
+    let json = keystore.encrypt(PASSWORD, &PARAMS).unwrap();
+
+    assert!(matches!(
+        Keystore::decrypt(&json, "wrong password"),
+        Err(KeystoreError::WrongPassword)
+    ));
+
+    // Flips a bit of the first ciphertext byte, keeping the Base64 valid.
+    let json = edited(&json, |value| {
+        let ciphertext = value["ciphertext"].as_str().unwrap();
+        let first = match &ciphertext[..1] {
+            "A" => "B",
+            _ => "A",
+        };
+        value["ciphertext"] = format!("{first}{}", &ciphertext[1..]).into();
+    });
+    assert!(matches!(
+        Keystore::decrypt(&json, PASSWORD),
+        Err(KeystoreError::Corrupt)
+    ));
+    assert!(matches!(
+        Keystore::decrypt(&json, "wrong password"),
+        Err(KeystoreError::WrongPassword)
+    ));
+}
+
+#[test]
+fn swapped_public_keys_are_detected() {
+    let keystore = Keystore::generate(ALGORITHM).unwrap();
+    let other = Keystore::generate(ALGORITHM).unwrap();
+    let json = keystore.encrypt(PASSWORD, &PARAMS).unwrap();
+
+    let json = edited(&json, |value| {
+        value["public_key"] = serde_json::to_value(other.public_key()).unwrap();
+    });
+    assert!(matches!(
+        Keystore::decrypt(&json, PASSWORD),
+        Err(KeystoreError::Corrupt)
+    ));
+}
+
+#[test]
+fn unusable_files_are_refused() {
+    let keystore = Keystore::generate(ALGORITHM).unwrap();
+    let json = keystore.encrypt(PASSWORD, &PARAMS).unwrap();
+
+    assert!(matches!(
+        Keystore::decrypt("{}", PASSWORD),
+        Err(KeystoreError::Malformed(_))
+    ));
+
+    let newer = edited(&json, |value| value["version"] = 2.into());
+    assert!(matches!(
+        Keystore::decrypt(&newer, PASSWORD),
+        Err(KeystoreError::UnsupportedVersion(2))
+    ));
+
+    let truncated_salt = edited(&json, |value| value["salt"] = "AAAA".into());
+    assert!(matches!(
+        Keystore::decrypt(&truncated_salt, PASSWORD),
+        Err(KeystoreError::BadParams(_))
+    ));
+}
+
+#[test]
+fn costly_key_derivations_are_refused() {
+    let keystore = Keystore::generate(ALGORITHM).unwrap();
+    let json = keystore.encrypt(PASSWORD, &PARAMS).unwrap();
+
+    for (field, value) in [
+        ("memory", MAX_KDF_PARAMS.memory + 1),
+        ("iterations", MAX_KDF_PARAMS.iterations + 1),
+        ("parallelism", MAX_KDF_PARAMS.parallelism + 1),
+    ] {
+        let hostile = edited(&json, |json| json["kdf"][field] = value.into());
+        assert!(
+            matches!(
+                Keystore::decrypt(&hostile, PASSWORD),
+                Err(KeystoreError::CostTooHigh(_))
+            ),
+            "{field}"
+        );
+    }
+
+    let params = KdfParams {
+        memory: u32::MAX,
+        ..PARAMS
+    };
+    assert!(matches!(
+        keystore.encrypt(PASSWORD, &params),
+        Err(KeystoreError::CostTooHigh(p)) if p == params
+    ));
+}
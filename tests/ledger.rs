@@ -0,0 +1,253 @@
+//! Resolving conflicts between transactions that reuse a sequence number.
+
+use std::slice;
+
+use ucoin::{
+    ledger::{Conflict, Direction, HistoryEntry, Status},
+    Address, Keystore, Ledger, SignedTransaction, TxHash, WorldState,
+};
+
+mod common;
+
+use common::{Fixture, ALGORITHM, NETWORK};
+
+/// The fixture, with a second funded account.
+fn fixture() -> (Fixture, Fixture) {
+    let mut fixture = Fixture::new();
+    let other = Fixture::new();
+    fixture
+        .genesis
+        .credit(other.account.address(NETWORK), 1_000)
+        .unwrap();
+
+    (fixture, other)
+}
+
+/// Two payments of the fixture's first transaction, sorted by hash.
+fn rivals(fixture: &Fixture) -> [SignedTransaction; 2] {
+    let mut rivals =
+        [1, 2].map(|amount| Fixture::pay(&fixture.account, &[], 0, amount, &fixture.receiver));
+    rivals.sort_by_key(SignedTransaction::hash);
+
+    rivals
+}
+
+fn ledger(genesis: &WorldState, transactions: &[&SignedTransaction]) -> Ledger {
+    let mut ledger = Ledger::new(genesis.clone());
+    for tx in transactions {
+        ledger.insert((*tx).clone()).unwrap();
+    }
+
+    ledger
+}
+
+fn statuses(ledger: &Ledger, transactions: &[&SignedTransaction]) -> Vec<Option<Status>> {
+    transactions
+        .iter()
+        .map(|tx| ledger.status(&tx.hash()))
+        .collect()
+}
+
+/// Every order of `items`.
+fn permutations<T: Copy>(items: &[T]) -> Vec<Vec<T>> {
+    if items.is_empty() {
+        return vec![Vec::new()];
+    }
+
+    let mut permutations = Vec::new();
+    for (i, first) in items.iter().enumerate() {
+        let mut rest = items.to_vec();
+        rest.remove(i);
+        for mut permutation in self::permutations(&rest) {
+            permutation.insert(0, *first);
+            permutations.push(permutation);
+        }
+    }
+
+    permutations
+}
+
+#[test]
+fn on_equal_weight_the_lower_hash_wins() {
+    let fixture = Fixture::new();
+    let [low, high] = rivals(&fixture);
+    let conflict = Conflict {
+        sender: fixture.account.address(NETWORK),
+        transactions: vec![low.hash(), high.hash()],
+    };
+
+    let mut ledger = ledger(&fixture.genesis, &[&low]);
+    let report = ledger.insert(high.clone()).unwrap();
+    assert_eq!(report.rejected, [high.hash()]);
+    assert_eq!(report.conflicts, slice::from_ref(&conflict));
+
+    let mut ledger = self::ledger(&fixture.genesis, &[&high]);
+    let report = ledger.insert(low.clone()).unwrap();
+    assert_eq!(report.accepted, [low.hash()]);
+    assert_eq!(report.rejected, [high.hash()]);
+    assert_eq!(report.conflicts, [conflict]);
+
+    assert_eq!(
+        ledger.conflicts(&low.hash()).collect::<Vec<_>>(),
+        [&high.hash()]
+    );
+    assert_eq!(
+        ledger
+            .spends(&fixture.account.address(NETWORK))
+            .collect::<Vec<_>>(),
+        [&low.hash()]
+    );
+}
+
+#[test]
+fn spends_must_build_on_the_senders_last_one() {
+    let fixture = Fixture::new();
+    let first = Fixture::pay(&fixture.account, &[], 0, 100, &fixture.receiver);
+    let concurrent = Fixture::pay(&fixture.account, &[], 1, 200, &fixture.receiver);
+    let child = Fixture::pay(&fixture.account, &[first.hash()], 1, 200, &fixture.receiver);
+
+    // The sender can afford both, and they do not share a sequence number, but the second does
+    // not descend from the first. Whichever arrives first, it is rejected without a conflict.
+    for order in [[&first, &concurrent], [&concurrent, &first]] {
+        let ledger = ledger(&fixture.genesis, &order);
+        assert_eq!(
+            statuses(&ledger, &[&first, &concurrent]),
+            [Some(Status::Accepted), Some(Status::Rejected)]
+        );
+        assert_eq!(ledger.conflicts(&concurrent.hash()).count(), 0);
+    }
+
+    let mut ledger = ledger(&fixture.genesis, &[&first, &concurrent]);
+    let report = ledger.insert(child.clone()).unwrap();
+    assert_eq!(report.accepted, [child.hash()]);
+    assert!(report.conflicts.is_empty());
+    assert_eq!(ledger.state().balance(&fixture.receiver), 300);
+}
+
+#[test]
+fn heavier_transactions_win() {
+    let fixture = Fixture::new();
+    let [low, high] = rivals(&fixture);
+    let mut ledger = ledger(&fixture.genesis, &[&low, &high]);
+    assert_eq!(ledger.status(&high.hash()), Some(Status::Rejected));
+
+    // Rejected along with its parent, but it still adds to the parent's weight.
+    let child = fixture.transfer(&[high.hash()], 1);
+    let report = ledger.insert(child.clone()).unwrap();
+    assert_eq!(report.accepted, [high.hash(), child.hash()]);
+    assert_eq!(report.rejected, [low.hash()]);
+    assert_eq!(
+        report.conflicts,
+        [Conflict {
+            sender: fixture.account.address(NETWORK),
+            transactions: vec![high.hash(), low.hash()],
+        }]
+    );
+    let paid = high.transaction().amount() + 1;
+    assert_eq!(ledger.state().balance(&fixture.receiver), paid);
+}
+
+#[test]
+fn evicted_transactions_take_their_descendants_along() {
+    let (fixture, other) = fixture();
+    let [low, high] = rivals(&fixture);
+    let low_child = fixture.transfer(&[low.hash()], 1);
+    let mut ledger = ledger(&fixture.genesis, &[&low, &high, &low_child]);
+    assert_eq!(
+        statuses(&ledger, &[&low, &low_child, &high]),
+        [
+            Some(Status::Accepted),
+            Some(Status::Accepted),
+            Some(Status::Rejected)
+        ]
+    );
+
+    // Transactions of another account bring `high` up to three, against two for `low`.
+    let first = Fixture::pay(&other.account, &[high.hash()], 0, 10, &fixture.receiver);
+    let report = ledger.insert(first.clone()).unwrap();
+    assert_eq!(report.rejected, [first.hash()]);
+    assert_eq!(ledger.status(&high.hash()), Some(Status::Rejected));
+
+    let second = Fixture::pay(&other.account, &[first.hash()], 1, 10, &fixture.receiver);
+    let report = ledger.insert(second.clone()).unwrap();
+    assert_eq!(report.rejected, [low.hash(), low_child.hash()]);
+    assert_eq!(report.accepted, [high.hash(), first.hash(), second.hash()]);
+
+    let paid = high.transaction().amount();
+    assert_eq!(ledger.state().balance(&fixture.receiver), paid + 20);
+    assert_eq!(
+        ledger.state().balance(&fixture.account.address(NETWORK)),
+        1_000 - paid
+    );
+}
+
+#[test]
+fn arrival_order_does_not_matter() {
+    let (fixture, other) = fixture();
+    let [low, high] = rivals(&fixture);
+    let low_child = fixture.transfer(&[low.hash()], 1);
+    let first = Fixture::pay(&other.account, &[high.hash()], 0, 10, &fixture.receiver);
+    let second = Fixture::pay(&other.account, &[first.hash()], 1, 10, &fixture.receiver);
+    // Conflicts with `first`, which only wins once `high` does.
+    let concurrent = Fixture::pay(&other.account, &[], 0, 5, &fixture.receiver);
+    let all = [&low, &high, &low_child, &first, &second, &concurrent];
+
+    let expected = ledger(&fixture.genesis, &all);
+    let balances = |ledger: &Ledger| -> Vec<u64> {
+        [
+            fixture.account.address(NETWORK),
+            other.account.address(NETWORK),
+            fixture.receiver,
+        ]
+        .iter()
+        .map(|address: &Address| ledger.state().balance(address))
+        .collect()
+    };
+    for order in permutations(&all) {
+        let ledger = ledger(&fixture.genesis, &order);
+        let hashes: Vec<TxHash> = order.iter().map(|tx| tx.hash()).collect();
+
+        assert_eq!(
+            statuses(&ledger, &all),
+            statuses(&expected, &all),
+            "{hashes:?}"
+        );
+        assert_eq!(balances(&ledger), balances(&expected), "{hashes:?}");
+    }
+}
+
+#[test]
+fn history_lists_accepted_transactions_parents_first() {
+    let (fixture, other) = fixture();
+    let sender = fixture.account.address(NETWORK);
+    let [low, high] = rivals(&fixture);
+    let received = Fixture::pay(&other.account, &[low.hash()], 0, 7, &sender);
+    let ledger = ledger(&fixture.genesis, &[&received, &high, &low]);
+    assert_eq!(ledger.status(&high.hash()), Some(Status::Rejected));
+
+    let history = ledger.history(&sender);
+    assert_eq!(
+        history,
+        [
+            HistoryEntry {
+                hash: low.hash(),
+                direction: Direction::Out,
+                counterparty: fixture.receiver,
+                amount: low.transaction().amount(),
+                sequence: 0,
+                timestamp: low.transaction().timestamp(),
+            },
+            HistoryEntry {
+                hash: received.hash(),
+                direction: Direction::In,
+                counterparty: other.account.address(NETWORK),
+                amount: 7,
+                sequence: 0,
+                timestamp: received.transaction().timestamp(),
+            },
+        ]
+    );
+    assert_eq!(ledger.history(&fixture.receiver).len(), 1);
+    let stranger = Keystore::generate(ALGORITHM).unwrap().address(NETWORK);
+    assert!(ledger.history(&stranger).is_empty());
+}
@@ -0,0 +1,165 @@
+//! Merkle trees over the world state and proofs of single wallets.
+
+use ucoin::{
+    merkle::{self, WalletProof},
+    Address, Digest, PublicKey, WorldState,
+};
+
+mod common;
+
+use common::{ALGORITHM, NETWORK};
+
+/// Tree sizes around the powers of two, where the shape of the tree changes.
+const SIZES: [usize; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 13];
+
+fn address(i: usize) -> Address {
+    Address::from_public_key(NETWORK, ALGORITHM, &PublicKey::from_bytes(i.to_be_bytes()))
+}
+
+fn leaves(size: usize) -> Vec<Digest> {
+    (0..size)
+        .map(|i| merkle::leaf(&address(i), i as u64 * 10, i as u64))
+        .collect()
+}
+
+fn node(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = blake3::Hasher::new();
+    hasher
+        .update(&[1])
+        .update(left.as_ref())
+        .update(right.as_ref());
+
+    hasher.finalize().into()
+}
+
+/// A state holding `size` wallets.
+fn state(size: usize) -> WorldState {
+    let mut state = WorldState::new(NETWORK);
+    for i in 0..size {
+        state.credit(address(i), 100 + i as u64).unwrap();
+    }
+
+    state
+}
+
+#[test]
+fn roots_of_small_trees() {
+    assert_eq!(merkle::root(&[]), Digest::from(blake3::hash(b"")));
+
+    let [a, b, c, d, e] = leaves(5).try_into().unwrap();
+    assert_eq!(merkle::root(&[a]), a);
+    assert_eq!(merkle::root(&[a, b]), node(&a, &b));
+    assert_eq!(merkle::root(&[a, b, c]), node(&node(&a, &b), &c));
+    assert_eq!(
+        merkle::root(&[a, b, c, d, e]),
+        node(&node(&node(&a, &b), &node(&c, &d)), &e)
+    );
+}
+
+#[test]
+fn leaves_commit_to_every_field() {
+    let leaf = merkle::leaf(&address(0), 10, 1);
+
+    assert_ne!(merkle::leaf(&address(1), 10, 1), leaf);
+    assert_ne!(merkle::leaf(&address(0), 11, 1), leaf);
+    assert_ne!(merkle::leaf(&address(0), 10, 2), leaf);
+}
+
+#[test]
+fn every_path_leads_to_the_root() {
+    for size in SIZES {
+        let leaves = leaves(size);
+        let root = merkle::root(&leaves);
+
+        for (index, leaf) in leaves.iter().enumerate() {
+            let path = merkle::path(&leaves, index);
+            let recomputed = merkle::root_from_path(index as u64, size as u64, *leaf, &path);
+            assert_eq!(recomputed, Some(root), "leaf {index} of {size}");
+        }
+    }
+}
+
+#[test]
+fn paths_of_the_wrong_length_are_refused() {
+    for size in SIZES {
+        let leaves = leaves(size);
+        let (index, size) = (size - 1, size as u64);
+        let mut path = merkle::path(&leaves, index);
+        let leaf = leaves[index];
+
+        assert_eq!(merkle::root_from_path(size, size, leaf, &path), None);
+
+        path.push(leaf);
+        assert_eq!(
+            merkle::root_from_path(index as u64, size, leaf, &path),
+            None,
+            "{size} leaves"
+        );
+
+        path.truncate(path.len().saturating_sub(2));
+        if size > 1 {
+            assert_eq!(
+                merkle::root_from_path(index as u64, size, leaf, &path),
+                None,
+                "{size} leaves"
+            );
+        }
+    }
+}
+
+#[test]
+fn wallet_proofs_verify_against_the_state_root() {
+    for size in SIZES {
+        let state = state(size);
+        let root = state.root();
+
+        for i in 0..size {
+            let proof = state.prove(&address(i)).unwrap();
+            assert_eq!(proof.balance, 100 + i as u64);
+            assert_eq!(proof.size, size as u64);
+            assert!(proof.verify(&root), "wallet {i} of {size}");
+        }
+    }
+
+    assert_eq!(state(3).prove(&address(3)), None);
+}
+
+#[test]
+fn tampered_proofs_fail() {
+    for size in SIZES {
+        let state = state(size);
+        let root = state.root();
+        let proof = state.prove(&address(size / 2)).unwrap();
+
+        let tampered = WalletProof {
+            balance: proof.balance + 1,
+            ..proof.clone()
+        };
+        assert!(!tampered.verify(&root), "balance, {size} wallets");
+
+        let tampered = WalletProof {
+            sequence: proof.sequence + 1,
+            ..proof.clone()
+        };
+        assert!(!tampered.verify(&root), "sequence, {size} wallets");
+
+        let tampered = WalletProof {
+            address: address(size),
+            ..proof.clone()
+        };
+        assert!(!tampered.verify(&root), "address, {size} wallets");
+
+        if size > 1 {
+            let tampered = WalletProof {
+                index: (proof.index + 1) % size as u64,
+                ..proof.clone()
+            };
+            assert!(!tampered.verify(&root), "index, {size} wallets");
+        }
+
+        assert!(
+            !proof.verify(&self::state(size + 1).root()),
+            "{size} wallets"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! Multisig policies, and cosigners signing a transaction together.
+
+use ucoin::{
+    multisig::{CosignError, PolicyError, MAX_KEYS},
+    Address, Keystore, Ledger, MultisigPolicy, PartiallySignedTransaction, SignatureAlgorithm,
+    SignedTransaction, Transaction, VerifyError, WorldState,
+};
+
+mod common;
+
+use common::{ALGORITHM, NETWORK};
+
+/// Three cosigners of mixed algorithms, ordered by their index in a 2-of-3 policy.
+fn cosigners() -> (MultisigPolicy, Vec<Keystore>) {
+    let mut cosigners = vec![
+        Keystore::generate(ALGORITHM).unwrap(),
+        Keystore::generate(SignatureAlgorithm::Ed25519Falcon512).unwrap(),
+        Keystore::generate(ALGORITHM).unwrap(),
+    ];
+    let keys = cosigners.iter().map(Keystore::account_key).collect();
+    let policy = MultisigPolicy::new(2, keys).unwrap();
+    cosigners.sort_by_key(|c| policy.index_of(&c.account_key()).unwrap());
+
+    (policy, cosigners)
+}
+
+fn payment(policy: &MultisigPolicy, amount: u64) -> PartiallySignedTransaction {
+    let receiver = Keystore::generate(ALGORITHM).unwrap().address(NETWORK);
+    let tx = Transaction::new(NETWORK, &[], &policy.clone().into(), 0, amount, &receiver);
+
+    PartiallySignedTransaction::new(tx)
+}
+
+/// `tx` with its signatures replaced by those at `indices` in its own list.
+fn with_signatures(tx: &SignedTransaction, indices: &[usize]) -> SignedTransaction {
+    let mut value = serde_json::to_value(tx).unwrap();
+    let signatures = value["signatures"].as_array().unwrap().clone();
+    value["signatures"] = indices.iter().map(|i| signatures[*i].clone()).collect();
+
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn policies_are_checked() {
+    let keys: Vec<_> = (0..MAX_KEYS + 1)
+        .map(|_| Keystore::generate(ALGORITHM).unwrap().account_key())
+        .collect();
+
+    assert_eq!(
+        MultisigPolicy::new(0, keys[..3].to_vec()),
+        Err(PolicyError::BadThreshold {
+            threshold: 0,
+            keys: 3
+        })
+    );
+    assert_eq!(
+        MultisigPolicy::new(4, keys[..3].to_vec()),
+        Err(PolicyError::BadThreshold {
+            threshold: 4,
+            keys: 3
+        })
+    );
+    assert_eq!(
+        MultisigPolicy::new(1, keys.clone()),
+        Err(PolicyError::TooManyKeys(MAX_KEYS + 1))
+    );
+    assert_eq!(
+        MultisigPolicy::new(1, vec![keys[0].clone(), keys[1].clone(), keys[0].clone()]),
+        Err(PolicyError::DuplicateKey)
+    );
+    assert!(MultisigPolicy::new(MAX_KEYS as u8, keys[..MAX_KEYS].to_vec()).is_ok());
+
+    // The order keys are listed in does not matter.
+    let policy = MultisigPolicy::new(2, keys[..3].to_vec()).unwrap();
+    let reversed = MultisigPolicy::new(2, keys[..3].iter().rev().cloned().collect()).unwrap();
+    assert_eq!(policy, reversed);
+    assert_eq!(
+        Address::from_multisig(NETWORK, &policy),
+        Address::from_multisig(NETWORK, &reversed)
+    );
+
+    let other = MultisigPolicy::new(3, keys[..3].to_vec()).unwrap();
+    assert_ne!(
+        Address::from_multisig(NETWORK, &policy),
+        Address::from_multisig(NETWORK, &other)
+    );
+}
+
+#[test]
+fn transactions_need_the_threshold_of_signatures() {
+    let (policy, cosigners) = cosigners();
+    let mut psbt = payment(&policy, 10);
+
+    psbt.sign(2, cosigners[2].secret_key()).unwrap();
+    assert!(!psbt.is_complete());
+    assert!(matches!(
+        psbt.clone().finalize(),
+        Err(CosignError::Incomplete {
+            threshold: 2,
+            actual: 1
+        })
+    ));
+
+    psbt.sign(1, cosigners[1].secret_key()).unwrap();
+    assert!(psbt.is_complete());
+    assert_eq!(psbt.signers().collect::<Vec<_>>(), [1, 2]);
+    let signed = psbt.clone().finalize().unwrap();
+    signed.verify().unwrap();
+
+    // Extra signatures are dropped, keeping those of the lowest indices.
+    psbt.sign(0, cosigners[0].secret_key()).unwrap();
+    let signed = psbt.finalize().unwrap();
+    let indices: Vec<_> = signed.signatures().iter().map(|c| c.index).collect();
+    assert_eq!(indices, [0, 1]);
+
+    let one = with_signatures(&signed, &[1]);
+    assert!(matches!(
+        one.verify(),
+        Err(VerifyError::NotEnoughSignatures {
+            threshold: 2,
+            actual: 1
+        })
+    ));
+}
+
+#[test]
+fn signatures_must_be_in_index_order_without_repeats() {
+    let (policy, cosigners) = cosigners();
+    let mut psbt = payment(&policy, 10);
+    psbt.sign(0, cosigners[0].secret_key()).unwrap();
+    psbt.sign(2, cosigners[2].secret_key()).unwrap();
+    let signed = psbt.finalize().unwrap();
+
+    let swapped = with_signatures(&signed, &[1, 0]);
+    assert!(matches!(
+        swapped.verify(),
+        Err(VerifyError::BadSignerIndex(0))
+    ));
+
+    let repeated = with_signatures(&signed, &[0, 0]);
+    assert!(matches!(
+        repeated.verify(),
+        Err(VerifyError::BadSignerIndex(0))
+    ));
+
+    let mut value = serde_json::to_value(&signed).unwrap();
+    value["signatures"][1]["index"] = 3.into();
+    let unknown: SignedTransaction = serde_json::from_value(value).unwrap();
+    assert!(matches!(
+        unknown.verify(),
+        Err(VerifyError::BadSignerIndex(3))
+    ));
+
+    // A signature tagged with another cosigner's index.
+    let mut value = serde_json::to_value(&signed).unwrap();
+    value["signatures"][1]["index"] = 1.into();
+    let mislabeled: SignedTransaction = serde_json::from_value(value).unwrap();
+    assert!(matches!(
+        mislabeled.verify(),
+        Err(VerifyError::SignatureMismatch | VerifyError::BadSignatureLength { .. })
+    ));
+}
+
+#[test]
+fn cosigners_can_only_sign_for_their_own_key() {
+    let (policy, cosigners) = cosigners();
+    let mut psbt = payment(&policy, 10);
+
+    assert!(matches!(
+        psbt.sign(3, cosigners[0].secret_key()),
+        Err(CosignError::UnknownSigner(3))
+    ));
+
+    // The other cosigner with the same algorithm.
+    let other = (1..3)
+        .find(|i| cosigners[*i].algorithm() == cosigners[0].algorithm())
+        .unwrap();
+    assert!(matches!(
+        psbt.sign(other as u8, cosigners[0].secret_key()),
+        Err(CosignError::Verify(VerifyError::SignatureMismatch))
+    ));
+    assert_eq!(psbt.signers().count(), 0);
+}
+
+#[test]
+fn partially_signed_copies_combine() {
+    let (policy, cosigners) = cosigners();
+    let mut psbt = payment(&policy, 10);
+
+    // Each cosigner signs their own copy, passed around as JSON.
+    let json = serde_json::to_string(&psbt).unwrap();
+    let mut first: PartiallySignedTransaction = serde_json::from_str(&json).unwrap();
+    let mut second: PartiallySignedTransaction = serde_json::from_str(&json).unwrap();
+    first.sign(0, cosigners[0].secret_key()).unwrap();
+    second.sign(2, cosigners[2].secret_key()).unwrap();
+
+    first.combine(second.clone()).unwrap();
+    assert_eq!(first.signers().collect::<Vec<_>>(), [0, 2]);
+    second.combine(first.clone()).unwrap();
+    assert_eq!(
+        first.finalize().unwrap().hash(),
+        second.finalize().unwrap().hash()
+    );
+
+    let mut other = payment(&policy, 11);
+    other.sign(1, cosigners[1].secret_key()).unwrap();
+    assert!(matches!(
+        psbt.combine(other),
+        Err(CosignError::DifferentTransaction)
+    ));
+}
+
+#[test]
+fn multisig_accounts_can_pay() {
+    let (policy, cosigners) = cosigners();
+    let address = Address::from_multisig(NETWORK, &policy);
+    let mut genesis = WorldState::new(NETWORK);
+    genesis.credit(address, 100).unwrap();
+
+    let mut psbt = payment(&policy, 10);
+    psbt.sign(0, cosigners[0].secret_key()).unwrap();
+    psbt.sign(1, cosigners[1].secret_key()).unwrap();
+    let signed = psbt.finalize().unwrap();
+    assert_eq!(signed.transaction().sender_address(), address);
+
+    let mut ledger = Ledger::new(genesis);
+    let report = ledger.insert(signed.clone()).unwrap();
+    assert_eq!(report.accepted, [signed.hash()]);
+    assert_eq!(ledger.state().balance(&address), 90);
+}
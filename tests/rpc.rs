@@ -0,0 +1,159 @@
+//! The JSON-RPC API of a node on localhost.
+
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpStream,
+};
+use ucoin::{
+    rpc::{code, RpcServer},
+    SignedTransaction,
+};
+
+mod common;
+
+use common::{Fixture, NETWORK};
+
+/// Sends a raw HTTP request and returns the status code and body.
+async fn http(server: &RpcServer, method: &str, body: &str) -> (u16, String) {
+    let mut stream = TcpStream::connect(server.local_addr()).await.unwrap();
+    let request = format!(
+        "{method} / HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+
+    (status, body.to_owned())
+}
+
+/// Calls `method` and returns the response object.
+async fn call(server: &RpcServer, method: &str, params: Value) -> Value {
+    let request = json!({ "jsonrpc": "2.0", "method": method, "params": params, "id": 1 });
+    let (status, body) = http(server, "POST", &request.to_string()).await;
+    assert_eq!(status, 200);
+
+    serde_json::from_str(&body).unwrap()
+}
+
+fn error_code(response: &Value) -> i64 {
+    response["error"]["code"].as_i64().unwrap()
+}
+
+#[tokio::test]
+async fn submitted_transactions_can_be_queried() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node, "127.0.0.1:0").await.unwrap();
+    let address = fixture.account.address(NETWORK);
+
+    let tx = fixture.transfer(&[], 0);
+    let hash = json!(tx.hash());
+    let response = call(&server, "submitTransaction", json!([tx])).await;
+    assert_eq!(
+        response["result"],
+        json!({ "hash": hash, "status": "accepted" })
+    );
+
+    let response = call(&server, "getTransaction", json!({ "hash": hash })).await;
+    let fetched: SignedTransaction =
+        serde_json::from_value(response["result"]["transaction"].clone()).unwrap();
+    assert_eq!(fetched.hash(), tx.hash());
+    assert_eq!(response["result"]["status"], "accepted");
+
+    let response = call(&server, "getBalance", json!([address])).await;
+    assert_eq!(response["result"]["balance"], 999);
+    assert_eq!(response["result"]["sequence"], 1);
+
+    let response = call(&server, "getHistory", json!([address])).await;
+    let history = response["result"].as_array().unwrap();
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0]["hash"], hash);
+    assert_eq!(history[0]["amount"], 1);
+
+    let response = call(&server, "getTips", json!([])).await;
+    assert_eq!(response["result"], json!([hash]));
+
+    let response = call(&server, "getStatus", json!([])).await;
+    assert_eq!(response["result"]["network"], "testnet");
+    assert_eq!(response["result"]["transactions"], 1);
+    assert_eq!(response["result"]["peers"], 0);
+    assert_eq!(response["id"], 1);
+}
+
+#[tokio::test]
+async fn failures_carry_error_codes() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node, "127.0.0.1:0").await.unwrap();
+
+    let tx = fixture.transfer(&[], 0);
+    call(&server, "submitTransaction", json!([tx])).await;
+    let response = call(&server, "submitTransaction", json!([tx])).await;
+    assert_eq!(error_code(&response), code::DUPLICATE_TRANSACTION);
+
+    let mut forged = serde_json::to_value(fixture.transfer(&[], 1)).unwrap();
+    forged["transaction"]["amount"] = json!(500);
+    let response = call(&server, "submitTransaction", json!([forged])).await;
+    assert_eq!(error_code(&response), code::INVALID_TRANSACTION);
+
+    let unknown = json!(fixture.transfer(&[], 2).hash());
+    let response = call(&server, "getTransaction", json!([unknown])).await;
+    assert_eq!(error_code(&response), code::NOT_FOUND);
+
+    let mainnet = fixture.account.address(ucoin::Network::Mainnet);
+    let response = call(&server, "getBalance", json!([mainnet])).await;
+    assert_eq!(error_code(&response), code::WRONG_NETWORK);
+
+    let response = call(&server, "getBalance", json!([])).await;
+    assert_eq!(error_code(&response), code::INVALID_PARAMS);
+    let response = call(&server, "getBalance", json!(["not an address"])).await;
+    assert_eq!(error_code(&response), code::INVALID_PARAMS);
+
+    let response = call(&server, "mine", json!([])).await;
+    assert_eq!(error_code(&response), code::METHOD_NOT_FOUND);
+
+    let (status, body) = http(&server, "POST", "{").await;
+    assert_eq!(status, 200);
+    let response: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error_code(&response), code::PARSE_ERROR);
+    assert_eq!(response["id"], Value::Null);
+
+    let (status, body) = http(&server, "POST", r#"{"method": "getTips", "id": 1}"#).await;
+    assert_eq!(status, 200);
+    let response: Value = serde_json::from_str(&body).unwrap();
+    assert_eq!(error_code(&response), code::INVALID_REQUEST);
+}
+
+#[tokio::test]
+async fn batches_and_notifications() {
+    let fixture = Fixture::new();
+    let node = fixture.node().await;
+    let server = RpcServer::start(node, "127.0.0.1:0").await.unwrap();
+
+    let batch = json!([
+        { "jsonrpc": "2.0", "method": "getTips", "id": "a" },
+        { "jsonrpc": "2.0", "method": "getStatus" },
+        { "jsonrpc": "2.0", "method": "mine", "id": "b" },
+    ]);
+    let (status, body) = http(&server, "POST", &batch.to_string()).await;
+    assert_eq!(status, 200);
+    let responses: Vec<Value> = serde_json::from_str(&body).unwrap();
+    assert_eq!(responses.len(), 2);
+    assert_eq!(responses[0]["id"], "a");
+    assert_eq!(responses[0]["result"], json!([]));
+    assert_eq!(error_code(&responses[1]), code::METHOD_NOT_FOUND);
+
+    let notification = json!({ "jsonrpc": "2.0", "method": "getTips" });
+    let (status, body) = http(&server, "POST", &notification.to_string()).await;
+    assert_eq!(status, 204);
+    assert!(body.is_empty());
+
+    let (status, _) = http(&server, "GET", "").await;
+    assert_eq!(status, 405);
+}
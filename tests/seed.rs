@@ -0,0 +1,169 @@
+//! Golden vectors for seed phrases and account key derivation.
+//!
+//! Any change to these values means existing seed phrases no longer restore the same accounts.
+
+use serde::Deserialize;
+use ucoin::{Network, Seed, SignatureAlgorithm, Transaction};
+
+#[derive(Deserialize)]
+struct Vector {
+    mnemonic: String,
+    passphrase: String,
+    seed: String,
+    accounts: Vec<Account>,
+}
+
+#[derive(Deserialize)]
+struct Account {
+    algorithm: SignatureAlgorithm,
+    index: u32,
+    key_seed: String,
+    ed25519_public_key: Option<String>,
+    /// BLAKE3 of the whole public key, which pins the post-quantum half derived through the
+    /// seeded liboqs RNG.
+    public_key_blake3: Option<String>,
+}
+
+fn vectors() -> Vec<Vector> {
+    serde_json::from_str(include_str!("vectors/seed.json")).unwrap()
+}
+
+#[test]
+fn seed_matches_vectors() {
+    for vector in vectors() {
+        let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+        assert_eq!(
+            hex::encode(seed.as_bytes()),
+            vector.seed,
+            "{}",
+            vector.mnemonic
+        );
+    }
+}
+
+#[test]
+fn key_seed_matches_vectors() {
+    for vector in vectors() {
+        let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+        for account in &vector.accounts {
+            let key_seed = seed.key_seed(account.algorithm, account.index);
+            assert_eq!(
+                hex::encode(*key_seed),
+                account.key_seed,
+                "{} {}",
+                account.algorithm,
+                account.index
+            );
+        }
+    }
+}
+
+#[test]
+fn hybrid_classical_key_matches_vectors() {
+    for vector in vectors() {
+        let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+        for account in &vector.accounts {
+            let Some(expected) = &account.ed25519_public_key else {
+                continue;
+            };
+
+            let (pk, _) = seed.keypair(account.algorithm, account.index).unwrap();
+            assert_eq!(&hex::encode(&pk.as_ref()[..32]), expected);
+        }
+    }
+}
+
+#[test]
+fn public_key_matches_vectors() {
+    let mut unpinned = Vec::new();
+    for vector in vectors() {
+        let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+        for account in &vector.accounts {
+            if !account.algorithm.is_enabled() {
+                continue;
+            }
+
+            let (pk, _) = seed.keypair(account.algorithm, account.index).unwrap();
+            let digest = hex::encode(blake3::hash(pk.as_ref()).as_bytes());
+            match &account.public_key_blake3 {
+                Some(expected) => {
+                    assert_eq!(&digest, expected, "{} {}", account.algorithm, account.index)
+                }
+                None => unpinned.push(format!(
+                    "{} {} {}: {digest}",
+                    vector.mnemonic, account.algorithm, account.index
+                )),
+            }
+        }
+    }
+
+    assert!(
+        unpinned.is_empty(),
+        "vectors without public_key_blake3:\n{}",
+        unpinned.join("\n")
+    );
+}
+
+#[test]
+fn derivation_restores_accounts() {
+    for vector in vectors() {
+        let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+        let restored = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+
+        for account in &vector.accounts {
+            if !account.algorithm.is_enabled() {
+                continue;
+            }
+
+            let (pk, sk) = seed.keypair(account.algorithm, account.index).unwrap();
+            let (restored_pk, restored_sk) =
+                restored.keypair(account.algorithm, account.index).unwrap();
+            assert_eq!(pk, restored_pk);
+            assert_eq!(sk.as_ref(), restored_sk.as_ref());
+
+            let keystore = seed.keystore(account.algorithm, account.index).unwrap();
+            let receiver = keystore.address(Network::Mainnet);
+            let transaction = Transaction::new(
+                Network::Mainnet,
+                &[],
+                &keystore.account_key().into(),
+                0,
+                1,
+                &receiver,
+            )
+            .sign(keystore.secret_key())
+            .unwrap();
+            transaction.verify().unwrap();
+        }
+    }
+}
+
+#[test]
+fn accounts_are_independent() {
+    let vector = &vectors()[0];
+    let seed = Seed::from_phrase(&vector.mnemonic, &vector.passphrase).unwrap();
+    let other = Seed::from_phrase(&vector.mnemonic, "other passphrase").unwrap();
+
+    let (first, _) = seed.keypair(SignatureAlgorithm::Falcon1024, 0).unwrap();
+    let (second, _) = seed.keypair(SignatureAlgorithm::Falcon1024, 1).unwrap();
+    let (elsewhere, _) = other.keypair(SignatureAlgorithm::Falcon1024, 0).unwrap();
+    assert_ne!(first, second);
+    assert_ne!(first, elsewhere);
+}
+
+#[test]
+fn generated_mnemonic_round_trips() {
+    let mnemonic = ucoin::seed::generate_mnemonic(24).unwrap();
+    assert_eq!(mnemonic.word_count(), 24);
+    assert!(ucoin::seed::generate_mnemonic(13).is_err());
+
+    let phrase = mnemonic.to_string();
+    let seed = Seed::from_phrase(&phrase, "").unwrap();
+    assert_eq!(
+        seed.as_bytes(),
+        Seed::from_mnemonic(&mnemonic, "").as_bytes()
+    );
+
+    let bad_checksum = ["abandon"; 12].join(" ");
+    assert!(Seed::from_phrase(&bad_checksum, "").is_err());
+}
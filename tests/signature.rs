@@ -0,0 +1,239 @@
+//! Signing transactions and checking their signatures, hybrid and malformed ones included.
+
+use ucoin::{
+    crypto::{PublicKey, Sig, Signature, ED25519_SIGNATURE_LEN},
+    Keystore, SignatureAlgorithm, SignedTransaction, VerifyError,
+};
+
+mod common;
+
+use common::{Fixture, NETWORK};
+
+/// A payment signed with a new key, and the signer for it.
+fn payment() -> (SignedTransaction, Sig) {
+    let fixture = Fixture::new();
+
+    (
+        fixture.transfer(&[], 1),
+        fixture.account.algorithm().sig().unwrap(),
+    )
+}
+
+/// A payment signed by `account`.
+fn paid_by(account: &Keystore) -> SignedTransaction {
+    Fixture::pay(account, &[], 0, 1, &account.address(NETWORK))
+}
+
+/// `tx` with the field at `pointer` holding `value` instead.
+fn edited(tx: &SignedTransaction, pointer: &str, value: impl ToString) -> SignedTransaction {
+    let mut json = serde_json::to_value(tx).unwrap();
+    *json.pointer_mut(pointer).unwrap() = value.to_string().into();
+
+    serde_json::from_value(json).unwrap()
+}
+
+/// Signs and verifies a payment with each of `algorithms`.
+fn sign_and_verify(algorithms: &[SignatureAlgorithm]) {
+    for &algorithm in algorithms {
+        let tx = paid_by(&Keystore::generate(algorithm).unwrap());
+        assert_eq!(tx.transaction().sender().keys()[0].algorithm(), algorithm);
+        assert_eq!(tx.verify(), Ok(()), "{algorithm}");
+
+        let other = paid_by(&Keystore::generate(algorithm).unwrap());
+        let forged = edited(
+            &tx,
+            "/signatures/0/signature",
+            &other.signatures()[0].signature,
+        );
+        assert_eq!(
+            forged.verify(),
+            Err(VerifyError::SignatureMismatch),
+            "{algorithm}"
+        );
+    }
+}
+
+#[test]
+fn signatures_verify() {
+    let (tx, _) = payment();
+    assert_eq!(tx.verify(), Ok(()));
+
+    let (other, _) = payment();
+    let forged = edited(
+        &tx,
+        "/signatures/0/signature",
+        &other.signatures()[0].signature,
+    );
+    assert_eq!(forged.verify(), Err(VerifyError::SignatureMismatch));
+}
+
+#[test]
+fn truncated_keys_are_refused() {
+    let (tx, sig) = payment();
+    let sender = tx.transaction().sender().keys()[0].public_key().as_ref();
+
+    let truncated = PublicKey::from_bytes(&sender[..sender.len() - 1]);
+    assert_eq!(
+        edited(&tx, "/transaction/sender/public_key", truncated).verify(),
+        Err(VerifyError::BadKeyLength {
+            expected: sig.length_public_key(),
+            actual: sender.len() - 1
+        })
+    );
+}
+
+#[test]
+fn oversized_signatures_are_refused() {
+    let (tx, sig) = payment();
+
+    let oversized = Signature::from_bytes(vec![0; sig.length_signature() + 1]);
+    assert_eq!(
+        edited(&tx, "/signatures/0/signature", oversized).verify(),
+        Err(VerifyError::BadSignatureLength {
+            max: sig.length_signature(),
+            actual: sig.length_signature() + 1
+        })
+    );
+}
+
+#[test]
+fn verification_uses_the_algorithm_of_the_transaction() {
+    sign_and_verify(&[
+        SignatureAlgorithm::Falcon512,
+        SignatureAlgorithm::Falcon1024,
+        SignatureAlgorithm::Ed25519Falcon512,
+        SignatureAlgorithm::Ed25519Falcon1024,
+    ]);
+
+    // A Falcon-1024 key read as Falcon-512 has the wrong length.
+    let account = Keystore::generate(SignatureAlgorithm::Falcon1024).unwrap();
+    let tx = paid_by(&account);
+    let relabelled = edited(
+        &tx,
+        "/transaction/sender/algorithm",
+        SignatureAlgorithm::Falcon512,
+    );
+    assert_eq!(
+        relabelled.verify(),
+        Err(VerifyError::BadKeyLength {
+            expected: SignatureAlgorithm::Falcon512
+                .sig()
+                .unwrap()
+                .length_public_key(),
+            actual: account.public_key().as_ref().len()
+        })
+    );
+}
+
+#[test]
+fn hybrid_signatures_need_both_halves() {
+    for algorithm in [
+        SignatureAlgorithm::Ed25519Falcon512,
+        SignatureAlgorithm::Ed25519Falcon1024,
+    ] {
+        let tx = paid_by(&Keystore::generate(algorithm).unwrap());
+
+        // The same message signed by another key of the same algorithm.
+        let forger = Keystore::generate(algorithm).unwrap();
+        let forged = tx.transaction().clone().sign(forger.secret_key()).unwrap();
+
+        let genuine = tx.signatures()[0].signature.as_ref();
+        let (ed25519, falcon) = genuine.split_at(ED25519_SIGNATURE_LEN);
+        let (other_ed25519, other_falcon) = forged.signatures()[0]
+            .signature
+            .as_ref()
+            .split_at(ED25519_SIGNATURE_LEN);
+        let with_signature =
+            |bytes: Vec<u8>| edited(&tx, "/signatures/0/signature", Signature::from_bytes(bytes));
+
+        assert_eq!(
+            with_signature([ed25519, other_falcon].concat()).verify(),
+            Err(VerifyError::SignatureMismatch),
+            "{algorithm}"
+        );
+        assert_eq!(
+            with_signature([other_ed25519, falcon].concat()).verify(),
+            Err(VerifyError::SignatureMismatch),
+            "{algorithm}"
+        );
+
+        // Dropping either half is caught as well.
+        assert!(
+            with_signature(ed25519.to_vec()).verify().is_err(),
+            "{algorithm}"
+        );
+        assert!(
+            with_signature(falcon.to_vec()).verify().is_err(),
+            "{algorithm}"
+        );
+
+        assert_eq!(
+            with_signature(genuine.to_vec()).verify(),
+            Ok(()),
+            "{algorithm}"
+        );
+    }
+}
+
+#[test]
+fn unknown_algorithms_are_refused() {
+    let (tx, _) = payment();
+
+    for name in ["falcon-256", "rsa"] {
+        let mut json = serde_json::to_value(&tx).unwrap();
+        json["transaction"]["sender"]["algorithm"] = name.into();
+        assert!(serde_json::from_value::<SignedTransaction>(json).is_err());
+    }
+}
+
+#[cfg(not(feature = "ml-dsa"))]
+#[test]
+fn disabled_algorithms_are_refused() {
+    let (tx, _) = payment();
+
+    let disabled = edited(
+        &tx,
+        "/transaction/sender/algorithm",
+        SignatureAlgorithm::MlDsa65,
+    );
+    assert_eq!(
+        disabled.verify(),
+        Err(VerifyError::UnsupportedAlgorithm(
+            SignatureAlgorithm::MlDsa65
+        ))
+    );
+}
+
+#[cfg(feature = "ml-dsa")]
+#[test]
+fn ml_dsa_signatures_verify() {
+    sign_and_verify(&[
+        SignatureAlgorithm::MlDsa44,
+        SignatureAlgorithm::MlDsa65,
+        SignatureAlgorithm::MlDsa87,
+    ]);
+}
+
+#[cfg(feature = "sphincs")]
+#[test]
+fn sphincs_signatures_verify() {
+    sign_and_verify(&[
+        SignatureAlgorithm::SphincsSha2128f,
+        SignatureAlgorithm::SphincsSha2128s,
+        SignatureAlgorithm::SphincsSha2192f,
+        SignatureAlgorithm::SphincsSha2192s,
+        SignatureAlgorithm::SphincsSha2256f,
+        SignatureAlgorithm::SphincsSha2256s,
+    ]);
+}
+
+#[test]
+fn malformed_base64_is_refused() {
+    let (tx, _) = payment();
+
+    for pointer in ["/signatures/0/signature", "/transaction/sender/public_key"] {
+        let mut json = serde_json::to_value(&tx).unwrap();
+        *json.pointer_mut(pointer).unwrap() = "not base64!".into();
+        assert!(serde_json::from_value::<SignedTransaction>(json).is_err());
+    }
+}
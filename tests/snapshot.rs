@@ -0,0 +1,135 @@
+//! Taking, exporting and importing snapshots of the world state.
+
+use ucoin::{
+    ledger::Status,
+    snapshot::{Snapshot, SnapshotError},
+    Ledger, SignedTransaction,
+};
+
+mod common;
+
+use common::Fixture;
+
+fn ledger(fixture: &Fixture, transactions: &[SignedTransaction]) -> Ledger {
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+    for tx in transactions {
+        ledger.insert(tx.clone()).unwrap();
+    }
+
+    ledger
+}
+
+fn export(snapshot: &Snapshot) -> serde_json::Value {
+    serde_json::to_value(snapshot).unwrap()
+}
+
+fn import(value: serde_json::Value) -> Snapshot {
+    serde_json::from_value(value).unwrap()
+}
+
+#[test]
+fn exported_snapshots_continue_the_ledger() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(5);
+    let mut full = ledger(&fixture, &chain[..4]);
+
+    let snapshot = full.snapshot();
+    assert_eq!(snapshot.cut(), [chain[3].hash()]);
+    assert_eq!(snapshot.root(), &full.state().root());
+
+    let imported = import(export(&snapshot));
+    assert_eq!(imported, snapshot);
+    assert_eq!(imported.verify(), Ok(()));
+
+    let mut top = Ledger::from_snapshot(imported).unwrap();
+    assert!(top.dag().is_empty());
+    assert_eq!(top.snapshot(), snapshot);
+
+    let next = chain[4].clone();
+    assert_eq!(top.insert(next.clone()).unwrap().accepted, [next.hash()]);
+    full.insert(next).unwrap();
+    assert_eq!(top.state().root(), full.state().root());
+}
+
+#[test]
+fn tampered_balances_fail_verification() {
+    let fixture = Fixture::new();
+    let snapshot = ledger(&fixture, &fixture.chain(2)).snapshot();
+
+    let mut value = export(&snapshot);
+    let receiver = fixture.receiver.to_string();
+    value["state"]["wallets"][receiver.as_str()]["balance"] = 1_000.into();
+    let tampered = import(value);
+
+    assert_eq!(tampered.state().balance(&fixture.receiver), 1_000);
+    assert_eq!(tampered.verify(), Err(SnapshotError::RootMismatch));
+    assert_eq!(
+        Ledger::from_snapshot(tampered).err(),
+        Some(SnapshotError::RootMismatch)
+    );
+}
+
+#[test]
+fn roots_of_other_states_fail_verification() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(3);
+    let snapshot = ledger(&fixture, &chain[..2]).snapshot();
+    let other = ledger(&fixture, &chain).snapshot();
+
+    let mut value = export(&snapshot);
+    value["root"] = export(&other)["root"].clone();
+    let tampered = import(value);
+
+    assert_eq!(tampered.verify(), Err(SnapshotError::RootMismatch));
+    assert_eq!(
+        Ledger::from_snapshot(tampered).err(),
+        Some(SnapshotError::RootMismatch)
+    );
+}
+
+#[test]
+fn snapshots_cut_at_accepted_tips() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(2);
+    let mut ledger = ledger(&fixture, &chain);
+
+    // Skips a sequence number, so it is a tip of the DAG but not accepted.
+    let rejected = fixture.transfer(&[chain[1].hash()], 5);
+    ledger.insert(rejected.clone()).unwrap();
+    assert_eq!(ledger.status(&rejected.hash()), Some(Status::Rejected));
+    assert_eq!(ledger.dag().tips().collect::<Vec<_>>(), [&rejected.hash()]);
+
+    let snapshot = ledger.snapshot();
+    assert_eq!(snapshot.cut(), [chain[1].hash()]);
+
+    let mut top = Ledger::from_snapshot(snapshot).unwrap();
+    let next = fixture.transfer(&[chain[1].hash()], 2);
+    assert_eq!(top.insert(next.clone()).unwrap().accepted, [next.hash()]);
+}
+
+#[test]
+fn snapshots_at_a_cut() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(4);
+    let mut ledger = self::ledger(&fixture, &chain);
+
+    let snapshot = ledger.snapshot_at(&[chain[1].hash()]).unwrap();
+    assert_eq!(
+        snapshot.state(),
+        self::ledger(&fixture, &chain[..2]).state()
+    );
+    assert_eq!(snapshot.verify(), Ok(()));
+
+    let unknown = fixture.transfer(&[chain[3].hash()], 4);
+    assert_eq!(
+        ledger.snapshot_at(&[unknown.hash()]),
+        Err(SnapshotError::UnknownTransaction(unknown.hash()))
+    );
+
+    let rejected = fixture.transfer(&[chain[3].hash()], 7);
+    ledger.insert(rejected.clone()).unwrap();
+    assert_eq!(
+        ledger.snapshot_at(&[chain[2].hash(), rejected.hash()]),
+        Err(SnapshotError::RejectedTransaction(rejected.hash()))
+    );
+}
@@ -0,0 +1,242 @@
+//! Applying transactions to the world state and reverting them.
+
+use std::collections::{HashMap, HashSet};
+
+use ucoin::{
+    Address, Keystore, Network, SignedTransaction, StateError, Transaction, TxHash, VerifyError,
+    WorldState,
+};
+
+mod common;
+
+use common::{Fixture, ALGORITHM, NETWORK};
+
+/// The balance, sequence number and history of every wallet.
+fn wallets(state: &WorldState) -> HashMap<Address, (u64, u64, HashSet<TxHash>)> {
+    state
+        .wallets()
+        .map(|(address, wallet)| {
+            let history = wallet.history().clone();
+            (*address, (wallet.balance(), wallet.sequence(), history))
+        })
+        .collect()
+}
+
+#[test]
+fn applied_transactions_revert_to_the_same_state() {
+    let fixture = Fixture::new();
+    let sender = fixture.account.address(NETWORK);
+    let chain = fixture.chain(3);
+    let mut state = fixture.genesis.clone();
+
+    for tx in &chain {
+        state.apply(tx).unwrap();
+    }
+    assert_eq!(state.balance(&sender), 997);
+    assert_eq!(state.balance(&fixture.receiver), 3);
+    assert_eq!(state.sequence(&sender), 3);
+    assert_eq!(state.sequence(&fixture.receiver), 0);
+    let hashes: HashSet<_> = chain.iter().map(SignedTransaction::hash).collect();
+    assert_eq!(state.wallet(&sender).unwrap().history(), &hashes);
+    assert_eq!(state.wallet(&fixture.receiver).unwrap().history(), &hashes);
+
+    for tx in chain.iter().rev() {
+        state.revert(tx).unwrap();
+    }
+    assert_eq!(wallets(&state), wallets(&fixture.genesis));
+    assert!(state.wallet(&fixture.receiver).is_none());
+}
+
+#[test]
+fn failed_transactions_leave_the_state_untouched() {
+    let fixture = Fixture::new();
+    let mut state = fixture.genesis.clone();
+
+    let tx = Fixture::pay(&fixture.account, &[], 0, 1_001, &fixture.receiver);
+    assert_eq!(
+        state.apply(&tx),
+        Err(StateError::InsufficientBalance {
+            available: 1_000,
+            required: 1_001
+        })
+    );
+    assert_eq!(wallets(&state), wallets(&fixture.genesis));
+
+    let forger = Keystore::generate(ALGORITHM).unwrap();
+    let forged = fixture
+        .transfer(&[], 0)
+        .transaction()
+        .clone()
+        .sign(forger.secret_key())
+        .unwrap();
+    assert_eq!(
+        state.apply(&forged),
+        Err(StateError::Verify(VerifyError::SignatureMismatch))
+    );
+    assert_eq!(wallets(&state), wallets(&fixture.genesis));
+
+    let tx = fixture.transfer(&[], 0);
+    state.apply(&tx).unwrap();
+    let applied = wallets(&state);
+    assert_eq!(state.apply(&tx), Err(StateError::AlreadyApplied(tx.hash())));
+    assert_eq!(wallets(&state), applied);
+}
+
+#[test]
+fn only_the_last_applied_transaction_reverts() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(2);
+    let mut state = fixture.genesis.clone();
+
+    assert_eq!(
+        state.revert(&chain[0]),
+        Err(StateError::NotApplied(chain[0].hash()))
+    );
+
+    state.apply(&chain[0]).unwrap();
+    state.apply(&chain[1]).unwrap();
+    let applied = wallets(&state);
+    assert_eq!(
+        state.revert(&chain[0]),
+        Err(StateError::BadSequence {
+            expected: 1,
+            actual: 0
+        })
+    );
+    assert_eq!(wallets(&state), applied);
+}
+
+#[test]
+fn reverts_need_the_receiver_to_still_hold_the_amount() {
+    let fixture = Fixture::new();
+    let receiver = Keystore::generate(ALGORITHM).unwrap();
+    let tx = Fixture::pay(&fixture.account, &[], 0, 10, &receiver.address(NETWORK));
+    let mut state = fixture.genesis.clone();
+    state.apply(&tx).unwrap();
+
+    let spent = Fixture::pay(&receiver, &[tx.hash()], 0, 5, &fixture.receiver);
+    state.apply(&spent).unwrap();
+    let applied = wallets(&state);
+
+    assert_eq!(
+        state.revert(&tx),
+        Err(StateError::InsufficientBalance {
+            available: 5,
+            required: 10
+        })
+    );
+    assert_eq!(wallets(&state), applied);
+}
+
+#[test]
+fn errors_name_the_network() {
+    assert_eq!(
+        StateError::WrongNetwork(Network::Mainnet).to_string(),
+        "transaction is for mainnet"
+    );
+}
+
+#[test]
+fn replayed_sequences_are_refused() {
+    let fixture = Fixture::new();
+    let mut state = fixture.genesis.clone();
+    state.apply(&fixture.transfer(&[], 0)).unwrap();
+    let applied = wallets(&state);
+
+    // Another payment reusing the sequence number of the first.
+    let replay = Fixture::pay(&fixture.account, &[], 0, 2, &fixture.receiver);
+    assert_eq!(
+        state.apply(&replay),
+        Err(StateError::BadSequence {
+            expected: 1,
+            actual: 0
+        })
+    );
+    assert_eq!(wallets(&state), applied);
+}
+
+#[test]
+fn skipped_sequences_are_refused() {
+    let fixture = Fixture::new();
+    let mut state = fixture.genesis.clone();
+
+    let early = fixture.transfer(&[], 1);
+    assert_eq!(
+        state.apply(&early),
+        Err(StateError::BadSequence {
+            expected: 0,
+            actual: 1
+        })
+    );
+    assert_eq!(wallets(&state), wallets(&fixture.genesis));
+
+    state.apply(&fixture.transfer(&[], 0)).unwrap();
+    state.apply(&early).unwrap();
+    assert_eq!(state.sequence(&fixture.account.address(NETWORK)), 2);
+}
+
+#[test]
+fn transactions_of_another_network_are_refused() {
+    let fixture = Fixture::new();
+    let mut state = fixture.genesis.clone();
+    let sender = fixture.account.account_key().into();
+    let sign = |tx: Transaction| tx.sign(fixture.account.secret_key()).unwrap();
+
+    let mainnet_receiver = Keystore::generate(ALGORITHM)
+        .unwrap()
+        .address(Network::Mainnet);
+    let tx = sign(Transaction::new(
+        Network::Mainnet,
+        &[],
+        &sender,
+        0,
+        1,
+        &mainnet_receiver,
+    ));
+    assert_eq!(
+        state.apply(&tx),
+        Err(StateError::WrongNetwork(Network::Mainnet))
+    );
+
+    // On the right network, but paying an address of the other one.
+    let tx = sign(Transaction::new(
+        NETWORK,
+        &[],
+        &sender,
+        0,
+        1,
+        &mainnet_receiver,
+    ));
+    assert_eq!(
+        state.apply(&tx),
+        Err(StateError::WrongNetwork(Network::Mainnet))
+    );
+    assert_eq!(wallets(&state), wallets(&fixture.genesis));
+
+    // The network is signed, so a transaction cannot be moved to the other one.
+    let mut value = serde_json::to_value(fixture.transfer(&[], 0)).unwrap();
+    value["transaction"]["network"] = "mainnet".into();
+    let moved = serde_json::from_value(value).unwrap();
+    let mut mainnet = WorldState::new(Network::Mainnet);
+    assert_eq!(
+        mainnet.apply(&moved),
+        Err(StateError::Verify(VerifyError::SignatureMismatch))
+    );
+
+    // The same account holds nothing on the other network.
+    let tx = sign(Transaction::new(
+        Network::Mainnet,
+        &[],
+        &sender,
+        0,
+        1,
+        &mainnet_receiver,
+    ));
+    assert_eq!(
+        mainnet.apply(&tx),
+        Err(StateError::InsufficientBalance {
+            available: 0,
+            required: 1
+        })
+    );
+}
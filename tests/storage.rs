@@ -0,0 +1,233 @@
+//! Persisting the ledger, and recovering from crashes and damage.
+
+use std::{
+    fs::{self, OpenOptions},
+    path::Path,
+};
+
+use ucoin::{ledger::LedgerError, Keystore, Ledger, SignedTransaction, StorageError, Store};
+
+mod common;
+
+use common::{with_timestamp, Fixture, TempDir, ALGORITHM};
+
+const LOG: &str = "transactions.log";
+const INDEX: &str = "transactions.idx";
+const CHECKPOINT: &str = "checkpoint.json";
+
+/// A store in `dir` holding `transactions`, checkpointed after all of them.
+fn store(dir: &Path, fixture: &Fixture, transactions: &[SignedTransaction]) -> Store {
+    let mut store = Store::open(dir, &fixture.genesis).unwrap();
+    let mut ledger = store.load().unwrap();
+    for tx in transactions {
+        ledger.insert(tx.clone()).unwrap();
+        store.append(tx).unwrap();
+    }
+    store.checkpoint(&ledger).unwrap();
+
+    store
+}
+
+fn len(dir: &Path, name: &str) -> u64 {
+    fs::metadata(dir.join(name)).unwrap().len()
+}
+
+fn truncate(dir: &Path, name: &str, by: u64) {
+    let file = OpenOptions::new().write(true).open(dir.join(name)).unwrap();
+    file.set_len(len(dir, name) - by).unwrap();
+}
+
+fn flip_byte(dir: &Path, name: &str, offset: u64) {
+    let mut bytes = fs::read(dir.join(name)).unwrap();
+    bytes[offset as usize] ^= 0xff;
+    fs::write(dir.join(name), bytes).unwrap();
+}
+
+#[test]
+fn stores_reopen_with_the_same_ledger() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(3);
+    let expected = {
+        let store = store(dir.path(), &fixture, &chain);
+        store.load().unwrap()
+    };
+
+    let store = Store::open(dir.path(), &Fixture::new().genesis).unwrap();
+    assert_eq!(store.genesis().unwrap(), fixture.genesis);
+    assert_eq!(store.len(), 3);
+    let stored = store.get(&chain[1].hash()).unwrap().unwrap();
+    assert_eq!(stored.hash(), chain[1].hash());
+    let hashes: Vec<_> = chain.iter().map(SignedTransaction::hash).collect();
+    assert!(store.hashes().eq(hashes.iter()));
+
+    let ledger = store.load().unwrap();
+    assert_eq!(ledger.state().root(), expected.state().root());
+    assert_eq!(ledger.dag().len(), 3);
+}
+
+#[test]
+fn torn_final_records_are_dropped() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(3);
+    drop(store(dir.path(), &fixture, &chain[..2]));
+    let intact = len(dir.path(), LOG);
+
+    // The crash hit while the third record was written, before its index entry.
+    let mut store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    store.append(&chain[2]).unwrap();
+    drop(store);
+    truncate(dir.path(), LOG, 5);
+    truncate(dir.path(), INDEX, 40);
+
+    let mut store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert_eq!(store.len(), 2);
+    assert!(!store.contains(&chain[2].hash()));
+    assert_eq!(len(dir.path(), LOG), intact);
+    assert_eq!(store.load().unwrap().dag().len(), 2);
+
+    // The record can be written again.
+    assert!(store.append(&chain[2]).unwrap());
+    let store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert_eq!(store.load().unwrap().dag().len(), 3);
+}
+
+#[test]
+fn final_records_failing_their_checksum_are_dropped() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(2);
+    drop(store(dir.path(), &fixture, &chain[..1]));
+    let intact = len(dir.path(), LOG);
+
+    let mut store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    store.append(&chain[1]).unwrap();
+    drop(store);
+    truncate(dir.path(), INDEX, 40);
+    flip_byte(dir.path(), LOG, len(dir.path(), LOG) - 1);
+
+    let store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert_eq!(store.len(), 1);
+    assert_eq!(len(dir.path(), LOG), intact);
+}
+
+#[test]
+fn partial_index_entries_are_rebuilt() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(3);
+    drop(store(dir.path(), &fixture, &chain));
+    let index = len(dir.path(), INDEX);
+
+    truncate(dir.path(), INDEX, 13);
+
+    let store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert_eq!(store.len(), 3);
+    let stored = store.get(&chain[2].hash()).unwrap().unwrap();
+    assert_eq!(stored.hash(), chain[2].hash());
+    assert_eq!(len(dir.path(), INDEX), index);
+    assert_eq!(store.load().unwrap().dag().len(), 3);
+}
+
+#[test]
+fn corruption_inside_the_log_is_reported() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(3);
+    drop(store(dir.path(), &fixture, &chain));
+
+    // Inside the first record, past its length.
+    flip_byte(dir.path(), LOG, 10);
+
+    let store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert!(matches!(
+        store.get(&chain[0].hash()),
+        Err(StorageError::Corrupt { offset: 0 })
+    ));
+    assert!(matches!(
+        store.load(),
+        Err(StorageError::Corrupt { offset: 0 })
+    ));
+
+    // Without the index, the damage is found while indexing the log again.
+    fs::remove_file(dir.path().join(INDEX)).unwrap();
+    assert!(matches!(
+        Store::open(dir.path(), &fixture.genesis),
+        Err(StorageError::Corrupt { offset: 0 })
+    ));
+}
+
+#[test]
+fn checkpoints_that_do_not_match_are_reported() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    drop(store(dir.path(), &fixture, &fixture.chain(2)));
+    let path = dir.path().join(CHECKPOINT);
+    let original = fs::read(&path).unwrap();
+
+    let mut checkpoint: serde_json::Value = serde_json::from_slice(&original).unwrap();
+    let receiver = fixture.receiver.to_string();
+    checkpoint["state"]["wallets"][receiver.as_str()]["balance"] = 1_000.into();
+    fs::write(&path, serde_json::to_vec(&checkpoint).unwrap()).unwrap();
+    let store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    assert!(matches!(
+        store.load(),
+        Err(StorageError::CheckpointMismatch)
+    ));
+
+    let mut checkpoint: serde_json::Value = serde_json::from_slice(&original).unwrap();
+    checkpoint["records"] = 3.into();
+    fs::write(&path, serde_json::to_vec(&checkpoint).unwrap()).unwrap();
+    assert!(matches!(
+        store.load(),
+        Err(StorageError::CheckpointMismatch)
+    ));
+}
+
+#[test]
+fn stored_transactions_are_not_validated_again() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+
+    // Stored under rules that allowed an hour of clock drift.
+    let tx = fixture.transfer(&[], 0).transaction().clone();
+    let ahead = tx.timestamp() + 60 * 60 * 1000;
+    let tx = with_timestamp(tx, ahead)
+        .sign(fixture.account.secret_key())
+        .unwrap();
+    assert!(matches!(
+        Ledger::new(fixture.genesis.clone()).insert(tx.clone()),
+        Err(LedgerError::Invalid(_))
+    ));
+
+    let mut store = Store::open(dir.path(), &fixture.genesis).unwrap();
+    store.append(&tx).unwrap();
+    let ledger = store.load().unwrap();
+    assert!(ledger.dag().contains(&tx.hash()));
+}
+
+#[test]
+fn signatures_past_the_checkpoint_are_verified() {
+    let dir = TempDir::new();
+    let fixture = Fixture::new();
+    let chain = fixture.chain(1);
+    let mut store = store(dir.path(), &fixture, &chain);
+
+    let forger = Keystore::generate(ALGORITHM).unwrap();
+    let forged = Fixture::payment(
+        &fixture.account,
+        &[chain[0].hash()],
+        1,
+        500,
+        &fixture.receiver,
+    )
+    .sign(forger.secret_key())
+    .unwrap();
+    store.append(&forged).unwrap();
+
+    assert!(matches!(
+        store.load(),
+        Err(StorageError::Ledger(LedgerError::Verify(_)))
+    ));
+}
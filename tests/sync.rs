@@ -0,0 +1,133 @@
+//! Catching up with peers on localhost.
+
+use ucoin::{ledger::Status, Ledger, NodeError, SyncError, SyncProgress, TxHash};
+
+mod common;
+
+use common::{status, Fixture};
+
+#[tokio::test]
+async fn new_node_catches_up_in_batches() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let chain = fixture.chain(600);
+    for tx in &chain {
+        a.submit(tx.clone()).unwrap();
+    }
+
+    let b = fixture.node().await;
+    let peer = b.connect(a.local_addr()).await.unwrap();
+    let mut progress = SyncProgress::new();
+    let report = b.sync(&peer, &mut progress).await.unwrap();
+
+    assert!(progress.is_complete());
+    assert_eq!(report.accepted.len(), chain.len());
+    for tx in &chain {
+        assert_eq!(status(&b, &tx.hash()), Some(Status::Accepted));
+    }
+    let root = |node: &ucoin::Node| node.with_ledger(|ledger| ledger.state().root());
+    assert_eq!(root(&a), root(&b));
+}
+
+#[tokio::test]
+async fn lagging_node_fetches_only_what_it_lacks() {
+    let fixture = Fixture::new();
+    let a = fixture.node().await;
+    let b = fixture.node().await;
+    let chain = fixture.chain(10);
+    for tx in &chain {
+        a.submit(tx.clone()).unwrap();
+    }
+    for tx in &chain[..6] {
+        b.submit(tx.clone()).unwrap();
+    }
+
+    let peer = b.connect(a.local_addr()).await.unwrap();
+    let report = b.sync(&peer, &mut SyncProgress::new()).await.unwrap();
+
+    let fetched: Vec<TxHash> = chain[6..].iter().map(|tx| tx.hash()).collect();
+    assert_eq!(report.accepted, fetched);
+}
+
+#[tokio::test]
+async fn sync_resumes_with_another_peer() {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(10);
+
+    // `a` has the whole chain; `c` started from a snapshot and only holds the top half.
+    let mut full = Ledger::new(fixture.genesis.clone());
+    for tx in &chain {
+        full.insert(tx.clone()).unwrap();
+    }
+    let snapshot = full.snapshot_at(&[chain[4].hash()]).unwrap();
+    let mut top = Ledger::from_snapshot(snapshot).unwrap();
+    for tx in &chain[5..] {
+        top.insert(tx.clone()).unwrap();
+    }
+    let a = fixture.node_with(full).await;
+    let c = fixture.node_with(top).await;
+
+    let b = fixture.node().await;
+    let from_c = b.connect(c.local_addr()).await.unwrap();
+    let mut progress = SyncProgress::new();
+    let result = b.sync(&from_c, &mut progress).await;
+
+    let Err(NodeError::Sync(SyncError::Unavailable(unavailable))) = result else {
+        panic!("expected unavailable transactions, got {result:?}");
+    };
+    assert_eq!(unavailable, [chain[4].hash()]);
+    assert_eq!(progress.pending(), 5);
+    assert_eq!(status(&b, &chain[9].hash()), None);
+
+    // The progress survives a restart.
+    let json = serde_json::to_string(&progress).unwrap();
+    let mut progress: SyncProgress = serde_json::from_str(&json).unwrap();
+
+    let from_a = b.connect(a.local_addr()).await.unwrap();
+    let report = b.sync(&from_a, &mut progress).await.unwrap();
+
+    assert!(progress.is_complete());
+    assert_eq!(report.accepted.len(), chain.len());
+    for tx in &chain {
+        assert_eq!(status(&b, &tx.hash()), Some(Status::Accepted));
+    }
+}
+
+#[test]
+fn unrequested_transactions_are_refused() {
+    let fixture = Fixture::new();
+    let ledger = Ledger::new(fixture.genesis.clone());
+    let chain = fixture.chain(2);
+
+    let mut progress = SyncProgress::new();
+    progress.want(&ledger, [chain[1].hash()]);
+    assert_eq!(progress.next_batch(10), [chain[1].hash()]);
+
+    let result = progress.receive(&ledger, vec![chain[0].clone()], &[]);
+    assert_eq!(result, Err(SyncError::Unrequested(chain[0].hash())));
+    assert_eq!(progress.pending(), 0);
+
+    progress
+        .receive(&ledger, vec![chain[1].clone()], &[])
+        .unwrap();
+    assert_eq!(progress.pending(), 1);
+    assert_eq!(progress.next_batch(10), [chain[0].hash()]);
+}
+
+#[test]
+fn fetched_transactions_apply_parents_first() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+    let chain = fixture.chain(3);
+
+    let mut progress = SyncProgress::new();
+    progress.want(&ledger, [chain[2].hash(), chain[1].hash(), chain[0].hash()]);
+    progress.next_batch(10);
+    let reversed = chain.iter().rev().cloned().collect();
+    progress.receive(&ledger, reversed, &[]).unwrap();
+
+    let report = progress.apply(&mut ledger).unwrap();
+    let hashes: Vec<_> = chain.iter().map(|tx| tx.hash()).collect();
+    assert_eq!(report.accepted, hashes);
+    assert!(progress.is_complete());
+}
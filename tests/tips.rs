@@ -0,0 +1,235 @@
+//! Choosing parents for new transactions.
+
+use std::collections::HashSet;
+
+use rand::{rngs::StdRng, SeedableRng};
+use ucoin::{
+    ledger::Status,
+    tips::{Strategy, TipSelector},
+    Keystore, Ledger, TxHash, WorldState,
+};
+
+mod common;
+
+use common::{with_timestamp, Fixture, ALGORITHM, NETWORK};
+
+const STRATEGIES: [Strategy; 4] = [
+    Strategy::Uniform,
+    Strategy::RandomWalk {
+        depth: 8,
+        alpha: 0.5,
+    },
+    Strategy::RandomWalk {
+        depth: 2,
+        alpha: 0.0,
+    },
+    Strategy::Deterministic,
+];
+
+/// `count` funded accounts and a genesis crediting them.
+fn accounts(count: usize) -> (Vec<Keystore>, WorldState) {
+    let accounts: Vec<_> = (0..count)
+        .map(|_| Keystore::generate(ALGORITHM).unwrap())
+        .collect();
+    let mut genesis = WorldState::new(NETWORK);
+    for account in &accounts {
+        genesis.credit(account.address(NETWORK), 1_000).unwrap();
+    }
+
+    (accounts, genesis)
+}
+
+fn accepted(ledger: &Ledger, parents: &[TxHash]) -> bool {
+    parents
+        .iter()
+        .all(|p| ledger.status(p) == Some(Status::Accepted))
+}
+
+#[test]
+fn empty_ledger_has_no_parents() {
+    let ledger = Ledger::new(Fixture::new().genesis);
+    let mut rng = StdRng::seed_from_u64(0);
+
+    for strategy in STRATEGIES {
+        let parents = TipSelector::new(strategy).select(&ledger, None, &mut rng);
+        assert!(parents.is_empty(), "{strategy:?}");
+    }
+}
+
+#[test]
+fn deterministic_picks_the_lowest_tips() {
+    let (accounts, genesis) = accounts(6);
+    let mut ledger = Ledger::new(genesis);
+
+    let mut tips: Vec<_> = accounts
+        .iter()
+        .zip(accounts.iter().cycle().skip(1))
+        .map(|(from, to)| {
+            let tx = Fixture::pay(from, &[], 0, 1, &to.address(NETWORK));
+            ledger.insert(tx.clone()).unwrap();
+            tx.hash()
+        })
+        .collect();
+    tips.sort();
+
+    let selector = TipSelector::new(Strategy::Deterministic);
+    let parents = selector.select(&ledger, None, &mut StdRng::seed_from_u64(0));
+    assert_eq!(parents, tips[..4]);
+
+    let selector = TipSelector {
+        max_parents: 2,
+        ..selector
+    };
+    let parents = selector.select(&ledger, None, &mut StdRng::seed_from_u64(0));
+    assert_eq!(parents, tips[..2]);
+}
+
+#[test]
+fn only_accepted_transactions_are_selected() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+
+    let first = fixture.transfer(&[], 0);
+    ledger.insert(first.clone()).unwrap();
+    // Skips a sequence number.
+    let rejected = fixture.transfer(&[first.hash()], 2);
+    ledger.insert(rejected.clone()).unwrap();
+    assert_eq!(ledger.status(&rejected.hash()), Some(Status::Rejected));
+    // Approves a transaction the ledger has not seen.
+    let unknown = fixture.transfer(&[first.hash()], 1);
+    let pending = fixture.transfer(&[unknown.hash()], 2);
+    ledger.insert(pending.clone()).unwrap();
+    assert_eq!(ledger.status(&pending.hash()), Some(Status::Pending));
+
+    let mut rng = StdRng::seed_from_u64(1);
+    for strategy in STRATEGIES {
+        for _ in 0..20 {
+            let parents = TipSelector::new(strategy).select(&ledger, None, &mut rng);
+            assert_eq!(parents, [first.hash()], "{strategy:?}");
+        }
+    }
+}
+
+#[test]
+fn parents_stay_within_bounds() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+
+    let chain = fixture.chain(3);
+    for tx in &chain {
+        ledger.insert(tx.clone()).unwrap();
+    }
+
+    let mut rng = StdRng::seed_from_u64(2);
+    for strategy in STRATEGIES {
+        // A single tip, padded with the transaction before it.
+        let parents = TipSelector::new(strategy).select(&ledger, None, &mut rng);
+        assert_eq!(parents.len(), 2, "{strategy:?}");
+        assert!(parents.contains(&chain[2].hash()), "{strategy:?}");
+        assert!(parents.contains(&chain[1].hash()), "{strategy:?}");
+
+        let selector = TipSelector {
+            min_parents: 5,
+            max_parents: 3,
+            strategy,
+        };
+        let parents = selector.select(&ledger, None, &mut rng);
+        assert_eq!(parents.len(), 3, "{strategy:?}");
+        assert!(accepted(&ledger, &parents), "{strategy:?}");
+    }
+}
+
+#[test]
+fn senders_build_on_their_last_transaction() {
+    let (accounts, genesis) = accounts(2);
+    let (alice, bob) = (&accounts[0], &accounts[1]);
+    let mut rng = StdRng::seed_from_u64(3);
+
+    for strategy in STRATEGIES {
+        let mut ledger = Ledger::new(genesis.clone());
+        let selector = TipSelector::new(strategy);
+
+        // Alternating senders each build only on what the selector hands them.
+        for sequence in 0..10 {
+            for (from, to) in [(alice, bob), (bob, alice)] {
+                let address = from.address(NETWORK);
+                let parents = selector.select(&ledger, Some(&address), &mut rng);
+                assert!(accepted(&ledger, &parents), "{strategy:?}");
+
+                let tx = Fixture::pay(from, &parents, sequence, 1, &to.address(NETWORK));
+                let report = ledger.insert(tx.clone()).unwrap();
+                assert_eq!(report.accepted, [tx.hash()], "{strategy:?}");
+            }
+        }
+
+        let state = ledger.state();
+        assert_eq!(state.balance(&alice.address(NETWORK)), 1_000);
+        assert_eq!(state.sequence(&bob.address(NETWORK)), 10);
+    }
+}
+
+#[test]
+fn transactions_dated_after_now_are_not_selected() {
+    let (accounts, genesis) = accounts(2);
+    let (alice, bob) = (&accounts[0], &accounts[1]);
+    let mut ledger = Ledger::new(genesis);
+
+    let first = Fixture::pay(alice, &[], 0, 1, &bob.address(NETWORK));
+    ledger.insert(first.clone()).unwrap();
+    // Within the clock drift allowed for another node.
+    let timestamp = first.transaction().timestamp() + 60_000;
+    let ahead = Fixture::payment(alice, &[first.hash()], 1, 1, &bob.address(NETWORK));
+    let ahead = with_timestamp(ahead, timestamp)
+        .sign(alice.secret_key())
+        .unwrap();
+    assert_eq!(
+        ledger.insert(ahead.clone()).unwrap().accepted,
+        [ahead.hash()]
+    );
+
+    let mut rng = StdRng::seed_from_u64(4);
+    for strategy in STRATEGIES {
+        let address = bob.address(NETWORK);
+        let parents = TipSelector::new(strategy).select(&ledger, Some(&address), &mut rng);
+        assert_eq!(parents, [first.hash()], "{strategy:?}");
+
+        let tx = Fixture::pay(bob, &parents, 0, 1, &alice.address(NETWORK));
+        let report = ledger.clone().insert(tx.clone()).unwrap();
+        assert_eq!(report.accepted, [tx.hash()], "{strategy:?}");
+    }
+}
+
+#[test]
+fn walks_without_usable_odds_pick_children_uniformly() {
+    let (accounts, genesis) = accounts(3);
+    let mut ledger = Ledger::new(genesis);
+    let to = accounts[0].address(NETWORK);
+
+    // Two branches on `root`, one heavier than the other.
+    let root = Fixture::pay(&accounts[0], &[], 0, 1, &accounts[1].address(NETWORK));
+    let heavy = Fixture::pay(&accounts[1], &[root.hash()], 0, 1, &to);
+    let top = Fixture::pay(&accounts[1], &[heavy.hash()], 1, 1, &to);
+    let light = Fixture::pay(&accounts[2], &[root.hash()], 0, 1, &to);
+    for tx in [&root, &heavy, &top, &light] {
+        ledger.insert(tx.clone()).unwrap();
+    }
+
+    let mut rng = StdRng::seed_from_u64(4);
+    for alpha in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, -1e300] {
+        let selector = TipSelector {
+            min_parents: 1,
+            max_parents: 1,
+            strategy: Strategy::RandomWalk { depth: 8, alpha },
+        };
+
+        let mut selected = HashSet::new();
+        for _ in 0..50 {
+            selected.extend(selector.select(&ledger, None, &mut rng));
+        }
+        assert_eq!(
+            selected,
+            HashSet::from([top.hash(), light.hash()]),
+            "{alpha}"
+        );
+    }
+}
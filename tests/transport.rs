@@ -0,0 +1,131 @@
+//! Encrypted sessions over loopback sockets.
+
+use tokio::net::{TcpListener, TcpStream};
+use ucoin::{
+    node::{self, handshake},
+    protocol::{self, Message},
+    Keystore, NodeError, Session, TransportError,
+};
+
+mod common;
+
+use common::{Fixture, ALGORITHM, NETWORK};
+
+struct End {
+    stream: TcpStream,
+    session: Session,
+    identity: Keystore,
+}
+
+/// Runs the handshake between two fresh identities over a loopback connection.
+async fn connected_pair() -> (End, End) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let accept = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let identity = Keystore::generate(ALGORITHM).unwrap();
+        let session = handshake(&mut stream, NETWORK, &identity).await.unwrap();
+
+        End {
+            stream,
+            session,
+            identity,
+        }
+    });
+
+    let mut stream = TcpStream::connect(addr).await.unwrap();
+    let identity = Keystore::generate(ALGORITHM).unwrap();
+    let session = handshake(&mut stream, NETWORK, &identity).await.unwrap();
+    let client = End {
+        stream,
+        session,
+        identity,
+    };
+
+    (client, accept.await.unwrap())
+}
+
+#[tokio::test]
+async fn sessions_carry_messages_both_ways() {
+    let fixture = Fixture::new();
+    let (mut a, mut b) = connected_pair().await;
+
+    assert_eq!(*a.session.peer(), b.identity.account_key());
+    assert_eq!(*b.session.peer(), a.identity.account_key());
+
+    for sequence in 0..3 {
+        let tx = fixture.transfer(&[], sequence);
+        let message = Message::Transaction(tx.clone());
+        node::send(&mut a.stream, a.session.sealer(), &message)
+            .await
+            .unwrap();
+        let Message::Transaction(received) = node::receive(&mut b.stream, b.session.opener())
+            .await
+            .unwrap()
+        else {
+            panic!("expected a transaction");
+        };
+        assert_eq!(received.hash(), tx.hash());
+    }
+
+    node::send(&mut b.stream, b.session.sealer(), &Message::GetTips)
+        .await
+        .unwrap();
+    let message = node::receive(&mut a.stream, a.session.opener()).await;
+    assert!(matches!(message, Ok(Message::GetTips)));
+}
+
+#[tokio::test]
+async fn frames_are_encrypted_on_the_wire() {
+    let fixture = Fixture::new();
+    let (mut a, mut b) = connected_pair().await;
+
+    let tx = fixture.transfer(&[], 0);
+    node::send(
+        &mut a.stream,
+        a.session.sealer(),
+        &Message::Transaction(tx.clone()),
+    )
+    .await
+    .unwrap();
+
+    let frame = protocol::read_frame(&mut b.stream).await.unwrap();
+    let plaintext = tx.to_bytes();
+    assert!(!frame.windows(plaintext.len()).any(|w| w == plaintext));
+
+    let message = Message::from_bytes(&b.session.opener().open(&frame).unwrap()).unwrap();
+    assert!(matches!(message, Message::Transaction(received) if received.hash() == tx.hash()));
+}
+
+#[tokio::test]
+async fn tampered_and_replayed_frames_are_rejected() {
+    let (mut a, mut b) = connected_pair().await;
+
+    let first = a.session.sealer().seal(&Message::GetTips.to_bytes());
+    let second = a.session.sealer().seal(&Message::GetTips.to_bytes());
+
+    let mut tampered = first.clone();
+    tampered[0] ^= 1;
+    let opener = b.session.opener();
+    assert!(matches!(
+        opener.open(&tampered),
+        Err(TransportError::Decrypt)
+    ));
+    // Frames must arrive in order.
+    assert!(matches!(opener.open(&second), Err(TransportError::Decrypt)));
+
+    opener.open(&first).unwrap();
+    assert!(matches!(opener.open(&first), Err(TransportError::Decrypt)));
+    opener.open(&second).unwrap();
+
+    // A node drops frames it cannot open.
+    protocol::write_frame(&mut a.stream, &tampered)
+        .await
+        .unwrap();
+    let result = node::receive(&mut b.stream, b.session.opener()).await;
+    assert!(matches!(
+        result,
+        Err(NodeError::Transport(TransportError::Decrypt))
+    ));
+}
@@ -0,0 +1,245 @@
+//! Structural rules, each broken by a crafted transaction.
+
+use std::time::Duration;
+
+use ucoin::{
+    ledger::{LedgerError, Status},
+    Ledger, Rules, SignedTransaction, Transaction, TxHash, Violation,
+};
+
+mod common;
+
+use common::{with_timestamp, Fixture, NETWORK};
+
+fn sign(fixture: &Fixture, tx: Transaction) -> SignedTransaction {
+    tx.sign(fixture.account.secret_key()).unwrap()
+}
+
+/// Hashes of unrelated transactions, to reference as parents.
+fn hashes(fixture: &Fixture, count: u64) -> Vec<TxHash> {
+    (0..count)
+        .map(|sequence| fixture.transfer(&[], sequence).hash())
+        .collect()
+}
+
+fn violations(rules: &Rules, tx: &Transaction) -> Vec<Violation> {
+    match rules.validate(tx, tx.timestamp()) {
+        Ok(()) => Vec::new(),
+        Err(violations) => violations,
+    }
+}
+
+#[test]
+fn well_formed_transactions_pass() {
+    let fixture = Fixture::new();
+    let parents = hashes(&fixture, 3);
+    let tx = Fixture::payment(&fixture.account, &parents, 0, 10, &fixture.receiver);
+
+    assert!(violations(&Rules::default(), &tx).is_empty());
+}
+
+#[test]
+fn too_many_parents() {
+    let fixture = Fixture::new();
+    let rules = Rules {
+        max_parents: 2,
+        ..Rules::default()
+    };
+
+    let tx = Fixture::payment(
+        &fixture.account,
+        &hashes(&fixture, 2),
+        0,
+        10,
+        &fixture.receiver,
+    );
+    assert!(violations(&rules, &tx).is_empty());
+
+    let tx = Fixture::payment(
+        &fixture.account,
+        &hashes(&fixture, 3),
+        0,
+        10,
+        &fixture.receiver,
+    );
+    assert_eq!(
+        violations(&rules, &tx),
+        [Violation::TooManyParents { max: 2, actual: 3 }]
+    );
+}
+
+#[test]
+fn duplicate_parents() {
+    let fixture = Fixture::new();
+    let [a, b, c] = hashes(&fixture, 3).try_into().unwrap();
+
+    let tx = Fixture::payment(
+        &fixture.account,
+        &[a, b, a, c, b, a],
+        0,
+        10,
+        &fixture.receiver,
+    );
+    assert_eq!(
+        violations(&Rules::default(), &tx),
+        [Violation::DuplicateParent(a), Violation::DuplicateParent(b)]
+    );
+}
+
+#[test]
+fn self_transfers() {
+    let fixture = Fixture::new();
+    let own = fixture.account.address(NETWORK);
+
+    let tx = Fixture::payment(&fixture.account, &[], 0, 10, &own);
+    assert_eq!(
+        violations(&Rules::default(), &tx),
+        [Violation::SelfTransfer]
+    );
+}
+
+#[test]
+fn zero_amounts() {
+    let fixture = Fixture::new();
+
+    let tx = Fixture::payment(&fixture.account, &[], 0, 0, &fixture.receiver);
+    assert_eq!(violations(&Rules::default(), &tx), [Violation::ZeroAmount]);
+}
+
+#[test]
+fn timestamps_far_in_the_future() {
+    let fixture = Fixture::new();
+    let rules = Rules {
+        max_clock_drift: Duration::from_secs(60),
+        ..Rules::default()
+    };
+    let tx = with_timestamp(
+        Fixture::payment(&fixture.account, &[], 0, 10, &fixture.receiver),
+        1_000_000,
+    );
+
+    assert_eq!(rules.validate(&tx, 940_000), Ok(()));
+    assert_eq!(
+        rules.validate(&tx, 939_999),
+        Err(vec![Violation::FutureTimestamp {
+            timestamp: 1_000_000,
+            now: 939_999
+        }])
+    );
+}
+
+#[test]
+fn timestamps_older_than_a_parent_are_stored_and_rejected() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+
+    let parent = fixture.transfer(&[], 0);
+    ledger.insert(parent.clone()).unwrap();
+    let timestamp = parent.transaction().timestamp();
+
+    let child = fixture.transfer(&[parent.hash()], 1).transaction().clone();
+    let older = sign(&fixture, with_timestamp(child.clone(), timestamp - 1));
+    let report = ledger.insert(older.clone()).unwrap();
+    assert_eq!(report.rejected, [older.hash()]);
+    assert!(ledger.dag().contains(&older.hash()));
+
+    let same = sign(&fixture, with_timestamp(child, timestamp));
+    let report = ledger.insert(same.clone()).unwrap();
+    assert_eq!(report.accepted, [same.hash()]);
+}
+
+#[test]
+fn every_violation_is_reported() {
+    let fixture = Fixture::new();
+    let own = fixture.account.address(NETWORK);
+    let rules = Rules {
+        max_parents: 1,
+        ..Rules::default()
+    };
+    let [a] = hashes(&fixture, 1).try_into().unwrap();
+
+    let tx = Fixture::payment(&fixture.account, &[a, a], 0, 0, &own);
+    assert_eq!(
+        violations(&rules, &tx),
+        [
+            Violation::TooManyParents { max: 1, actual: 2 },
+            Violation::DuplicateParent(a),
+            Violation::SelfTransfer,
+            Violation::ZeroAmount,
+        ]
+    );
+}
+
+#[test]
+fn ledger_refuses_invalid_transactions() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone()).with_rules(Rules {
+        max_parents: 1,
+        ..Rules::default()
+    });
+
+    let own = fixture.account.address(NETWORK);
+    let tx = sign(
+        &fixture,
+        Fixture::payment(&fixture.account, &[], 0, 10, &own),
+    );
+    assert_eq!(
+        ledger.insert(tx.clone()),
+        Err(LedgerError::Invalid(vec![Violation::SelfTransfer]))
+    );
+    assert_eq!(ledger.status(&tx.hash()), None);
+
+    let first = fixture.transfer(&[], 0);
+    let second = fixture.transfer(&[first.hash()], 1);
+    ledger.insert(first.clone()).unwrap();
+    ledger.insert(second.clone()).unwrap();
+    let tx = fixture.transfer(&[first.hash(), second.hash()], 2);
+    assert_eq!(
+        ledger.insert(tx),
+        Err(LedgerError::Invalid(vec![Violation::TooManyParents {
+            max: 1,
+            actual: 2
+        }]))
+    );
+    assert_eq!(ledger.dag().len(), 2);
+}
+
+#[test]
+fn orphans_older_than_their_parent_are_rejected() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+
+    let parent = fixture.transfer(&[], 0);
+    let timestamp = parent.transaction().timestamp();
+    let child = fixture.transfer(&[parent.hash()], 1).transaction().clone();
+    let child = sign(&fixture, with_timestamp(child, timestamp - 1));
+
+    let report = ledger.insert(child.clone()).unwrap();
+    assert_eq!(report.pending, [child.hash()]);
+
+    let report = ledger.insert(parent.clone()).unwrap();
+    assert_eq!(report.accepted, [parent.hash()]);
+    assert_eq!(report.rejected, [child.hash()]);
+    assert_eq!(ledger.status(&child.hash()), Some(Status::Rejected));
+}
+
+#[test]
+fn snapshots_replay_without_validating_again() {
+    let fixture = Fixture::new();
+    let mut ledger = Ledger::new(fixture.genesis.clone());
+
+    let first = fixture.transfer(&[], 0);
+    let second = fixture.transfer(&[first.hash()], 1);
+    let third = fixture.transfer(&[first.hash(), second.hash()], 2);
+    for tx in [&first, &second, &third] {
+        ledger.insert(tx.clone()).unwrap();
+    }
+
+    // The third transaction breaks the tightened rules, but is already part of the DAG.
+    let ledger = ledger.with_rules(Rules {
+        max_parents: 1,
+        ..Rules::default()
+    });
+    let snapshot = ledger.snapshot_at(&[third.hash()]).unwrap();
+    assert_eq!(snapshot.state(), ledger.state());
+}
@@ -0,0 +1,94 @@
+//! The compact binary encoding of transactions.
+
+use ucoin::{
+    wire::{WireError, VERSION},
+    Keystore, MultisigPolicy, PartiallySignedTransaction, SignatureAlgorithm, SignedTransaction,
+    Transaction,
+};
+
+mod common;
+
+use common::{Fixture, ALGORITHM, NETWORK};
+
+/// A single-key payment and a 1-of-2 multisig payment with two parents.
+fn transactions() -> Vec<SignedTransaction> {
+    let fixture = Fixture::new();
+    let chain = fixture.chain(2);
+
+    let cosigners = [ALGORITHM, SignatureAlgorithm::Ed25519Falcon512]
+        .map(|algorithm| Keystore::generate(algorithm).unwrap());
+    let policy =
+        MultisigPolicy::new(1, cosigners.iter().map(Keystore::account_key).collect()).unwrap();
+    let index = policy.index_of(&cosigners[1].account_key()).unwrap();
+    let parents = [chain[0].hash(), chain[1].hash()];
+    let tx = Transaction::new(NETWORK, &parents, &policy.into(), 7, 300, &fixture.receiver);
+    let mut psbt = PartiallySignedTransaction::new(tx);
+    psbt.sign(index, cosigners[1].secret_key()).unwrap();
+
+    vec![chain[1].clone(), psbt.finalize().unwrap()]
+}
+
+#[test]
+fn transactions_decode_to_what_json_holds() {
+    for tx in transactions() {
+        let bytes = tx.to_bytes();
+        let json = serde_json::to_string(&tx).unwrap();
+        assert!(bytes.len() < json.len());
+
+        let decoded = SignedTransaction::from_bytes(&bytes).unwrap();
+        assert_eq!(serde_json::to_string(&decoded).unwrap(), json);
+        assert_eq!(decoded.hash(), tx.hash());
+        assert_eq!(decoded.verify(), Ok(()));
+
+        let from_json: SignedTransaction = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json.to_bytes(), bytes);
+    }
+}
+
+#[test]
+fn truncated_input_is_refused() {
+    for tx in transactions() {
+        let bytes = tx.to_bytes();
+
+        for len in 0..bytes.len() {
+            assert_eq!(
+                SignedTransaction::from_bytes(&bytes[..len]).map(|tx| tx.hash()),
+                Err(WireError::UnexpectedEof),
+                "{len} of {} bytes",
+                bytes.len()
+            );
+        }
+    }
+}
+
+#[test]
+fn trailing_bytes_are_refused() {
+    for tx in transactions() {
+        let mut bytes = tx.to_bytes();
+        bytes.extend_from_slice(&[0, 1, 2]);
+
+        assert_eq!(
+            SignedTransaction::from_bytes(&bytes).map(|tx| tx.hash()),
+            Err(WireError::TrailingBytes(3))
+        );
+    }
+}
+
+#[test]
+fn unknown_versions_and_networks_are_refused() {
+    let bytes = transactions()[0].to_bytes();
+
+    let mut newer = bytes.clone();
+    newer[0] = VERSION + 1;
+    assert_eq!(
+        SignedTransaction::from_bytes(&newer).map(|tx| tx.hash()),
+        Err(WireError::UnknownVersion(VERSION + 1))
+    );
+
+    let mut elsewhere = bytes;
+    elsewhere[1] = 0xff;
+    assert_eq!(
+        SignedTransaction::from_bytes(&elsewhere).map(|tx| tx.hash()),
+        Err(WireError::UnknownNetwork(0xff))
+    );
+}